@@ -1,4 +1,5 @@
 use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum};
+use encoding_rs::Encoding;
 use std::path::PathBuf;
 
 /// Utilities for reading and converting .mds/.mdf disk image files
@@ -6,6 +7,16 @@ use std::path::PathBuf;
 pub struct Args {
     #[command(subcommand)]
     pub command: Command,
+
+    /// The encoding of 8-bit filenames in .mds files, such as windows-1251 or shift_jis. Images
+    /// made by Alcohol 120% use the codepage Windows was set up with. Defaults to UTF-8.
+    #[arg(long, global = true, value_parser = encoding)]
+    pub filename_encoding: Option<&'static Encoding>,
+
+    /// A directory to look for data files in when they aren't next to the .mds. Can be given
+    /// more than once.
+    #[arg(long = "data-dir", global = true)]
+    pub data_dirs: Vec<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
@@ -13,8 +24,36 @@ pub enum Command {
     /// Convert .mdf/.mds files to other formats
     Convert(ConvertArgs),
 
+    /// Create .mds/.mdf files from another image format
+    Create(CreateArgs),
+
+    /// Copy files out of the filesystem on an image, or write a single track to its own file
+    Extract(ExtractArgs),
+
+    /// Write each El Torito boot image on a bootable disc to its own file
+    ExtractBoot(ExtractBootArgs),
+
+    /// Print the CRC32, MD5, and SHA-1 of each track, and of the whole image
+    Hash(HashArgs),
+
     /// Print metadata contained by .mds files
     Info(InfoArgs),
+
+    /// Check a PlayStation image for LibCrypt protection, and write .sbi and .lsd files for
+    /// emulators if it has it
+    Libcrypt(LibcryptArgs),
+
+    /// List the files in a directory of the filesystem on an image
+    Ls(LsArgs),
+
+    /// Print the Q subchannel of every sector in images which contain subchannel data
+    Subq(SubqArgs),
+
+    /// Check the EDC and ECC of every data sector in images made of raw sectors
+    Verify(VerifyArgs),
+
+    /// Check the tracks of an image against a Logiqx XML DAT file, such as one from redump.org
+    VerifyDat(VerifyDatArgs),
 }
 
 #[derive(Clone, Debug, ValueEnum)]
@@ -24,12 +63,170 @@ pub enum OutputFormat {
 
     /// Convert into .bin and .cue files. This format supports multiple tracks.
     Cue,
+
+    /// Convert into CloneCD .ccd, .img, and .sub files. This format supports multiple tracks and
+    /// keeps subchannel data.
+    Ccd,
+
+    /// Convert into a compressed MAME .chd file. This format supports multiple tracks, and can
+    /// keep subchannel data with --subchannels.
+    Chd,
+
+    /// Convert an audio CD into a .flac file, with a cue sheet embedded in it and alongside it.
+    /// Use --split-tracks to write each track to its own .flac instead.
+    Flac,
+
+    /// Convert into a .cue file with each audio track in its own .wav file, and each data track in
+    /// its own .bin file
+    Wav,
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+pub enum InfoFormat {
+    /// Print a summary meant to be read by people
+    #[default]
+    Text,
+
+    /// Print a JSON document following the schema described in the README, for use by scripts
+    Json,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum FilesystemType {
+    /// ISO 9660, along with its Joliet and Rock Ridge extensions
+    Iso9660,
+
+    /// UDF, which is used by DVDs
+    Udf,
+
+    /// HFS or HFS+, which is found on Mac and hybrid Mac/PC discs
+    Hfs,
+}
+
+#[derive(Clone, Debug, Default, ValueEnum)]
+pub enum ResourceForkFormat {
+    /// Write the data fork as it is, with the resource fork and Finder information in an
+    /// AppleDouble file named like ._file, the way macOS does on other filesystems
+    #[default]
+    #[value(name = "appledouble")]
+    AppleDouble,
+
+    /// Write both forks and the Finder information into one MacBinary II file named like
+    /// file.bin
+    Macbinary,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CreateArgs {
+    /// Path to the .cue file to read. The .mds and .mdf files are written next to it.
+    #[arg(long)]
+    pub from: PathBuf,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ExtractArgs {
+    /// Path to the .mds file to extract from
+    pub mds_file: PathBuf,
+
+    /// The file or directory to copy out of the image's filesystem. Directories are copied along
+    /// with everything in them. Defaults to the whole filesystem.
+    #[arg(conflicts_with = "track")]
+    pub path: Option<String>,
+
+    /// The directory to copy files into
+    #[arg(short, long, value_name = "DIR", default_value = ".")]
+    pub output: PathBuf,
+
+    /// Which filesystem to read on hybrid discs which have more than one
+    #[arg(long, value_enum, conflicts_with = "track")]
+    pub filesystem: Option<FilesystemType>,
+
+    /// How to keep the resource forks and Finder information of files copied from HFS and HFS+
+    /// filesystems
+    #[arg(long, value_enum, default_value_t)]
+    pub resource_forks: ResourceForkFormat,
+
+    /// The number of a track to write to its own file, rather than copying files
+    #[arg(long)]
+    pub track: Option<usize>,
+
+    /// Write the track as a .wav file rather than a .bin. Only works for audio tracks.
+    #[arg(long, requires = "track")]
+    pub wav: bool,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct ExtractBootArgs {
+    /// Path to the .mds file to extract boot images from
+    pub mds_file: PathBuf,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct HashArgs {
+    /// Path to the .mds file to hash
+    pub mds_file: PathBuf,
 }
 
 #[derive(ClapArgs, Debug)]
 pub struct InfoArgs {
     /// Path to the .mds file to print information about
     pub mds_file: PathBuf,
+
+    /// The format to print the information in
+    #[arg(long, value_enum, default_value_t)]
+    pub format: InfoFormat,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct LibcryptArgs {
+    /// Path to the .mds file to check. It must have been imaged with subchannel data.
+    pub mds_file: PathBuf,
+
+    /// Name the .sbi and .lsd files after this template rather than the .mds, in the same way
+    /// as `convert --name`
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct LsArgs {
+    /// Path to the .mds file to list files from
+    pub mds_file: PathBuf,
+
+    /// The directory to list, or a file to show the details of. Defaults to the root directory.
+    #[arg(default_value = "/")]
+    pub path: String,
+
+    /// Which filesystem to read on hybrid discs which have more than one
+    #[arg(long, value_enum)]
+    pub filesystem: Option<FilesystemType>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct SubqArgs {
+    /// Path to the .mds file to read subchannel data from
+    pub mds_file: PathBuf,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct VerifyArgs {
+    /// Path to the .mds file to verify
+    pub mds_file: PathBuf,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct VerifyDatArgs {
+    /// Path to the .mds file to verify
+    pub mds_file: PathBuf,
+
+    /// Path to the DAT file to check the image against
+    #[arg(long)]
+    pub dat: PathBuf,
+
+    /// Write a DAT file describing the image to this path, which can be submitted to a dump
+    /// database if the image isn't in the DAT
+    #[arg(long, value_name = "DAT_FILE")]
+    pub emit_dat: Option<PathBuf>,
 }
 
 #[derive(ClapArgs, Debug)]
@@ -40,4 +237,40 @@ pub struct ConvertArgs {
     /// The format to convert into
     #[arg(long, value_enum)]
     pub format: OutputFormat,
+
+    /// The session to extract when converting a multi-session disc to an iso. Defaults to the
+    /// last session, which is the one a drive would mount.
+    #[arg(long)]
+    pub session: Option<usize>,
+
+    /// Write every stored byte of each sector when converting to an iso, rather than just the
+    /// 2048 bytes of user data
+    #[arg(long)]
+    pub raw: bool,
+
+    /// Keep subchannel data when converting to a .chd
+    #[arg(long)]
+    pub subchannels: bool,
+
+    /// Write each track to its own file when converting to a .cue or .flac, named the way
+    /// redump.org names them
+    #[arg(long)]
+    pub split_tracks: bool,
+
+    /// Write the CD+G graphics of each audio track to a .cdg file next to its .wav when
+    /// converting to wav. Only works for images with subchannel data.
+    #[arg(long)]
+    pub cdg: bool,
+
+    /// Name the converted files after this template rather than the .mds. `{name}` is replaced
+    /// with the name of the .mds, and `{platform}` and `{serial}` with what the disc is
+    /// identified as, like "{serial} - {name}". Discs without a serial use the name of the .mds
+    /// for `{serial}`, and it's an error to use `{platform}` for a disc that can't be identified.
+    #[arg(long)]
+    pub name: Option<String>,
+}
+
+/// Look up a character encoding by any of the names it goes by
+fn encoding(label: &str) -> Result<&'static Encoding, String> {
+    Encoding::for_label(label.as_bytes()).ok_or_else(|| format!("Unknown encoding: {label}"))
 }
@@ -0,0 +1,148 @@
+/// Writes values into a byte buffer, most significant bit first
+#[derive(Default)]
+pub struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    num_bits: usize,
+}
+
+impl BitWriter {
+    /// Write the lowest `num_bits` bits of `value`
+    pub fn write(&mut self, value: u32, num_bits: usize) {
+        if num_bits == 0 {
+            return;
+        }
+
+        let mask = u64::MAX >> (64 - num_bits);
+        self.bits = (self.bits << num_bits) | (u64::from(value) & mask);
+        self.num_bits += num_bits;
+
+        while self.num_bits >= 8 {
+            self.num_bits -= 8;
+            self.bytes.push((self.bits >> self.num_bits) as u8);
+        }
+    }
+
+    /// Pad with zeros up to the next byte boundary
+    pub fn align(&mut self) {
+        if self.num_bits > 0 {
+            self.write(0, 8 - self.num_bits);
+        }
+    }
+
+    /// The bytes written so far, not including any partial byte
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Pad out the last byte and return everything that was written
+    pub fn finish(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+/// Reads values from a byte buffer, most significant bit first. Reading past the end of the
+/// buffer produces zeros, and is reported by `overflowed`.
+pub struct BitReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, position: 0 }
+    }
+
+    /// Read a `num_bits` wide value
+    pub fn read(&mut self, num_bits: usize) -> u32 {
+        let mut value = 0;
+
+        for _ in 0..num_bits {
+            let byte = self.bytes.get(self.position / 8).copied().unwrap_or(0);
+            let bit = (byte >> (7 - self.position % 8)) & 1;
+            value = (value << 1) | u32::from(bit);
+            self.position += 1;
+        }
+
+        value
+    }
+
+    /// Whether more bits have been read than the buffer holds
+    pub fn overflowed(&self) -> bool {
+        self.position > self.bytes.len() * 8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_most_significant_bit_first() {
+        let mut w = BitWriter::default();
+        w.write(0b1, 1);
+        w.write(0b010, 3);
+        w.write(0xF, 4);
+        w.write(0xABC, 12);
+
+        assert_eq!(w.bytes(), [0b1010_1111, 0xAB]);
+        assert_eq!(w.finish(), [0b1010_1111, 0xAB, 0xC0]);
+    }
+
+    #[test]
+    fn write_ignores_bits_above_num_bits() {
+        let mut w = BitWriter::default();
+        w.write(0xFFFF_FFFF, 4);
+        w.write(0, 0);
+        w.write(0x1234_5670, 4);
+
+        assert_eq!(w.finish(), [0xF0]);
+    }
+
+    #[test]
+    fn align_pads_with_zeros() {
+        let mut w = BitWriter::default();
+        w.align();
+        w.write(0b111, 3);
+        w.align();
+        w.write(0xFF, 8);
+        w.align();
+
+        assert_eq!(w.bytes(), [0b1110_0000, 0xFF]);
+    }
+
+    #[test]
+    fn reader_reads_what_writer_wrote() {
+        let values: Vec<(u32, usize)> = (0..200)
+            .map(|i: u32| {
+                let num_bits = (i as usize * 7) % 33;
+                let value = i.wrapping_mul(0x9E37_79B9) >> (32 - num_bits.max(1));
+                (if num_bits == 0 { 0 } else { value }, num_bits)
+            })
+            .collect();
+
+        let mut w = BitWriter::default();
+        for &(value, num_bits) in &values {
+            w.write(value, num_bits);
+        }
+        let bytes = w.finish();
+
+        let mut r = BitReader::new(&bytes);
+        for &(value, num_bits) in &values {
+            assert_eq!(r.read(num_bits), value, "{num_bits} bits");
+        }
+        assert!(!r.overflowed());
+    }
+
+    #[test]
+    fn reading_past_the_end_gives_zeros() {
+        let mut r = BitReader::new(&[0xFF]);
+
+        assert_eq!(r.read(4), 0xF);
+        assert_eq!(r.read(4), 0xF);
+        assert!(!r.overflowed());
+        assert_eq!(r.read(8), 0);
+        assert!(r.overflowed());
+    }
+}
@@ -0,0 +1,425 @@
+use crate::{
+    error::{Error, Result},
+    flac::encode_frames,
+};
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::io::{self, Read, Write};
+use xz2::{
+    read::XzDecoder,
+    stream::{Action, LzmaOptions, Status, Stream},
+};
+
+// All of the CD codecs split each hunk into two parts before compressing it: the main data of
+// every frame, followed by the subcode of every frame. The main data is compressed with the
+// codec's own algorithm, and the subcode is always deflated.
+
+/// The number of bytes of main data in a CD frame
+pub const CD_SECTOR_SIZE: usize = 0x930;
+
+/// The number of bytes of subcode in a CD frame
+pub const CD_SUBCODE_SIZE: usize = 0x60;
+
+/// The number of bytes in a CD frame, which is a sector and its subcode
+pub const CD_FRAME_SIZE: usize = CD_SECTOR_SIZE + CD_SUBCODE_SIZE;
+
+/// The LZMA properties byte for lc=3, lp=0, pb=2. MAME configures its decoder this way.
+const LZMA_PROPERTIES: u8 = 0x5D;
+
+/// The length of the header the .lzma format puts ahead of the raw LZMA stream
+const LZMA_HEADER_SIZE: usize = 13;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Codec {
+    /// The main data is compressed with LZMA
+    Lzma,
+
+    /// The main data is deflated
+    Zlib,
+
+    /// The main data is compressed with FLAC, treating it as big endian 16-bit stereo samples.
+    /// This only does well on audio tracks.
+    Flac,
+}
+
+impl Codec {
+    /// The four character code which identifies this codec in a .chd header
+    pub fn tag(self) -> [u8; 4] {
+        match self {
+            Codec::Lzma => *b"cdlz",
+            Codec::Zlib => *b"cdzl",
+            Codec::Flac => *b"cdfl",
+        }
+    }
+
+    pub fn from_tag(tag: [u8; 4]) -> Option<Self> {
+        match &tag {
+            b"cdlz" => Some(Codec::Lzma),
+            b"cdzl" => Some(Codec::Zlib),
+            b"cdfl" => Some(Codec::Flac),
+            _ => None,
+        }
+    }
+
+    /// Compress a hunk made up of whole CD frames
+    pub fn compress(self, hunk: &[u8]) -> Result<Vec<u8>> {
+        let frames = hunk.len() / CD_FRAME_SIZE;
+        let (sectors, subcode) = split_frames(hunk);
+        let mut out = Vec::with_capacity(hunk.len());
+
+        match self {
+            Codec::Lzma | Codec::Zlib => {
+                let base = match self {
+                    Codec::Lzma => lzma(&sectors, frames)?,
+                    _ => deflate(&sectors)?,
+                };
+
+                // The header starts with a bitmap of which frames had their ECC stripped out to
+                // be regenerated on decompression. That is optional, and nothing is stripped
+                // here.
+                out.resize(ecc_bitmap_size(frames), 0);
+
+                let base_len = base.len().to_be_bytes();
+                let len_bytes = length_size(hunk.len());
+                out.extend_from_slice(&base_len[base_len.len() - len_bytes..]);
+                out.extend_from_slice(&base);
+            }
+            Codec::Flac => {
+                let samples: Vec<i16> = sectors
+                    .chunks_exact(2)
+                    .map(|x| i16::from_be_bytes([x[0], x[1]]))
+                    .collect();
+
+                out.extend(encode_frames(&samples, flac_block_size(frames), 44_100, 0));
+            }
+        }
+
+        out.extend(deflate(&subcode)?);
+
+        Ok(out)
+    }
+
+    /// Decompress a hunk of `hunk_bytes` bytes
+    pub fn decompress(self, data: &[u8], hunk_bytes: usize) -> Result<Vec<u8>> {
+        let frames = hunk_bytes / CD_FRAME_SIZE;
+
+        let (sectors, subcode) = match self {
+            Codec::Lzma | Codec::Zlib => {
+                let bitmap_size = ecc_bitmap_size(frames);
+                let len_bytes = length_size(hunk_bytes);
+                let header_size = bitmap_size + len_bytes;
+                let header = data.get(0..header_size).ok_or(Error::ChdVerification)?;
+
+                // Frames with their ECC stripped out are never written, so they can't be read
+                // either
+                if header[0..bitmap_size].iter().any(|&x| x != 0) {
+                    Err(Error::ChdVerification)?;
+                }
+
+                let base_len = header[bitmap_size..]
+                    .iter()
+                    .fold(0, |len, &x| len << 8 | x as usize);
+                let base = data
+                    .get(header_size..header_size + base_len)
+                    .ok_or(Error::ChdVerification)?;
+                let sectors = match self {
+                    Codec::Lzma => unlzma(base)?,
+                    _ => inflate(base)?,
+                };
+
+                (sectors, inflate(&data[header_size + base_len..])?)
+            }
+            Codec::Flac => {
+                let block_size = flac_block_size(frames);
+                let samples = unflac(data, block_size, frames * CD_SECTOR_SIZE / 2)?;
+
+                // FLAC data doesn't record its own length, and the decoder reads ahead, so the
+                // only way to find where the subcode starts is to encode the samples again
+                let flac_len = encode_frames(&samples, block_size, 44_100, 0).len();
+                let sectors = samples.iter().flat_map(|x| x.to_be_bytes()).collect();
+
+                (sectors, inflate(&data[flac_len..])?)
+            }
+        };
+
+        if sectors.len() != frames * CD_SECTOR_SIZE || subcode.len() != frames * CD_SUBCODE_SIZE {
+            Err(Error::ChdVerification)?;
+        }
+
+        let hunk = sectors
+            .chunks_exact(CD_SECTOR_SIZE)
+            .zip(subcode.chunks_exact(CD_SUBCODE_SIZE))
+            .flat_map(|(sector, subcode)| sector.iter().chain(subcode))
+            .copied()
+            .collect();
+
+        Ok(hunk)
+    }
+}
+
+/// Separate the main data of each frame from its subcode
+fn split_frames(hunk: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let frames = hunk.chunks_exact(CD_FRAME_SIZE);
+    let sectors = frames.clone().flat_map(|f| &f[..CD_SECTOR_SIZE]).copied();
+    let subcode = frames.flat_map(|f| &f[CD_SECTOR_SIZE..]).copied();
+
+    (sectors.collect(), subcode.collect())
+}
+
+fn ecc_bitmap_size(frames: usize) -> usize {
+    frames.div_ceil(8)
+}
+
+/// The number of bytes used to store the length of the compressed main data
+fn length_size(hunk_bytes: usize) -> usize {
+    if hunk_bytes < 0x10000 {
+        2
+    } else {
+        3
+    }
+}
+
+/// MAME picks the FLAC block size from the amount of main data in a hunk
+fn flac_block_size(frames: usize) -> usize {
+    let mut block_size = frames * CD_SECTOR_SIZE / 4;
+    while block_size > CD_SECTOR_SIZE {
+        block_size /= 2;
+    }
+
+    block_size
+}
+
+/// The LZMA dictionary size MAME's decoder uses, which depends on how much data it will decode
+fn lzma_dict_size(frames: usize) -> u32 {
+    let size = (frames * CD_SECTOR_SIZE) as u32;
+
+    (11..=30)
+        .flat_map(|i| [2 << i, 3 << i])
+        .find(|&dict_size| size <= dict_size)
+        .unwrap_or(1 << 26)
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(data).map_err(Error::Io)?;
+    encoder.finish().map_err(Error::Io)
+}
+
+fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    DeflateDecoder::new(data)
+        .read_to_end(&mut out)
+        .map_err(|_| Error::ChdVerification)?;
+
+    Ok(out)
+}
+
+/// Compress `data` into a raw LZMA stream, with the settings MAME's decoder expects
+fn lzma(data: &[u8], frames: usize) -> Result<Vec<u8>> {
+    let mut options = LzmaOptions::new_preset(9).map_err(lzma_error)?;
+    options
+        .dict_size(lzma_dict_size(frames))
+        .literal_context_bits(3)
+        .literal_position_bits(0)
+        .position_bits(2);
+
+    let mut stream = Stream::new_lzma_encoder(&options).map_err(lzma_error)?;
+    let mut out = Vec::with_capacity(data.len() + LZMA_HEADER_SIZE);
+
+    // `process_vec` only fills the space the vector already has, and data which doesn't compress
+    // comes out bigger than it went in
+    loop {
+        let input = &data[stream.total_in() as usize..];
+        let status = stream
+            .process_vec(input, &mut out, Action::Finish)
+            .map_err(lzma_error)?;
+        if status == Status::StreamEnd {
+            break;
+        }
+        out.reserve(out.capacity());
+    }
+
+    // liblzma only knows how to write the .lzma format, which is a header followed by a raw
+    // stream. MAME only wants the stream.
+    Ok(out.split_off(LZMA_HEADER_SIZE))
+}
+
+fn unlzma(data: &[u8]) -> Result<Vec<u8>> {
+    // Give liblzma the .lzma header which was stripped off when compressing
+    let mut input = Vec::with_capacity(data.len() + LZMA_HEADER_SIZE);
+    input.push(LZMA_PROPERTIES);
+    input.extend_from_slice(&(1u32 << 16).to_le_bytes()); // dictionary size
+    input.extend_from_slice(&u64::MAX.to_le_bytes()); // unknown uncompressed size
+    input.extend_from_slice(data);
+
+    let stream = Stream::new_lzma_decoder(u64::MAX).map_err(lzma_error)?;
+    let mut out = Vec::new();
+    XzDecoder::new_stream(input.as_slice(), stream)
+        .read_to_end(&mut out)
+        .map_err(|_| Error::ChdVerification)?;
+
+    Ok(out)
+}
+
+fn lzma_error(err: xz2::stream::Error) -> Error {
+    Error::Io(io::Error::from(err))
+}
+
+/// Decode raw FLAC frames into `num_samples` interleaved stereo samples
+fn unflac(data: &[u8], block_size: usize, num_samples: usize) -> Result<Vec<i16>> {
+    // Claxon only reads complete FLAC streams, so give it the same header MAME's decoder makes up
+    let mut stream = Vec::with_capacity(data.len() + 42);
+    stream.extend_from_slice(b"fLaC");
+    stream.extend_from_slice(&[0x80, 0, 0, 34]); // last metadata block, STREAMINFO, 34 bytes
+    stream.extend_from_slice(&(block_size as u16).to_be_bytes()); // min block size
+    stream.extend_from_slice(&(block_size as u16).to_be_bytes()); // max block size
+    stream.extend_from_slice(&[0; 6]); // min & max frame size are unknown
+    stream.extend_from_slice(&[0x0A, 0xC4, 0x42, 0xF0, 0, 0, 0, 0]); // 44.1 kHz, 2 ch, 16 bit
+    stream.extend_from_slice(&[0; 16]); // no MD5
+    stream.extend_from_slice(data);
+
+    let mut reader =
+        claxon::FlacReader::new(stream.as_slice()).map_err(|_| Error::ChdVerification)?;
+    let mut blocks = reader.blocks();
+    let mut samples = Vec::with_capacity(num_samples);
+    let mut buffer = Vec::new();
+
+    while samples.len() < num_samples {
+        let block = blocks
+            .read_next_or_eof(buffer)
+            .map_err(|_| Error::ChdVerification)?
+            .ok_or(Error::ChdVerification)?;
+
+        for (left, right) in block.channel(0).iter().zip(block.channel(1)) {
+            samples.push(*left as i16);
+            samples.push(*right as i16);
+        }
+
+        buffer = block.into_buffer();
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random bytes from a xorshift generator, so tests don't need a dependency for them
+    fn noise(len: usize, mut seed: u32) -> Vec<u8> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect()
+    }
+
+    /// Hunks of `frames` frames filled with random data, silence, and a single repeated sample
+    fn hunks(frames: usize) -> [(&'static str, Vec<u8>); 3] {
+        let len = frames * CD_FRAME_SIZE;
+        [
+            ("random", noise(len, 0x1234_5678)),
+            ("silent", vec![0; len]),
+            ("repeated", [0x12, 0x34].repeat(len / 2)),
+        ]
+    }
+
+    #[test]
+    fn codecs_round_trip() {
+        for codec in [Codec::Lzma, Codec::Zlib, Codec::Flac] {
+            // Hunks of 32 frames need three bytes to store the compressed length
+            for frames in [8, 32] {
+                for (name, hunk) in hunks(frames) {
+                    let compressed = codec.compress(&hunk).unwrap();
+                    let decompressed = codec.decompress(&compressed, hunk.len()).unwrap();
+
+                    assert!(
+                        decompressed == hunk,
+                        "{codec:?} didn't round trip a {name} hunk of {frames} frames"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tags_round_trip() {
+        for codec in [Codec::Lzma, Codec::Zlib, Codec::Flac] {
+            assert_eq!(Codec::from_tag(codec.tag()).unwrap().tag(), codec.tag());
+        }
+        assert!(Codec::from_tag(*b"zlib").is_none());
+    }
+
+    #[test]
+    fn header_has_an_empty_ecc_bitmap_and_the_base_length() {
+        for (frames, bitmap_size, len_bytes) in [(8, 1, 2), (26, 4, 2), (27, 4, 3), (32, 4, 3)] {
+            let hunk = noise(frames * CD_FRAME_SIZE, frames as u32);
+            let (sectors, subcode) = split_frames(&hunk);
+            let compressed = Codec::Zlib.compress(&hunk).unwrap();
+
+            assert!(compressed[..bitmap_size].iter().all(|&x| x == 0));
+
+            let header_size = bitmap_size + len_bytes;
+            let base_len = compressed[bitmap_size..header_size]
+                .iter()
+                .fold(0, |len, &x| len << 8 | x as usize);
+
+            // Each part is a plain deflate stream, which any decoder can read
+            let mut base = Vec::new();
+            DeflateDecoder::new(&compressed[header_size..header_size + base_len])
+                .read_to_end(&mut base)
+                .unwrap();
+            let mut rest = Vec::new();
+            DeflateDecoder::new(&compressed[header_size + base_len..])
+                .read_to_end(&mut rest)
+                .unwrap();
+
+            assert!(base == sectors, "main data differs for {frames} frames");
+            assert!(rest == subcode, "subcode differs for {frames} frames");
+        }
+    }
+
+    #[test]
+    fn lzma_strips_the_whole_header() {
+        let frames = 8;
+        let data = noise(frames * CD_SECTOR_SIZE, 99);
+
+        let mut options = LzmaOptions::new_preset(9).unwrap();
+        options
+            .dict_size(lzma_dict_size(frames))
+            .literal_context_bits(3)
+            .literal_position_bits(0)
+            .position_bits(2);
+        let mut stream = Stream::new_lzma_encoder(&options).unwrap();
+        let mut full = Vec::with_capacity(data.len() * 2 + 1024);
+        stream
+            .process_vec(&data, &mut full, Action::Finish)
+            .unwrap();
+
+        // The .lzma header is the properties byte, the dictionary size, and the uncompressed
+        // size. A raw stream always starts with a zero byte from the range coder.
+        assert_eq!(full[0], LZMA_PROPERTIES);
+        assert_eq!(full[1..5], lzma_dict_size(frames).to_le_bytes());
+        assert_eq!(full[5..13], u64::MAX.to_le_bytes());
+        assert_eq!(full[LZMA_HEADER_SIZE], 0);
+
+        assert!(lzma(&data, frames).unwrap() == full[LZMA_HEADER_SIZE..]);
+    }
+
+    #[test]
+    fn lzma_dict_size_matches_mame() {
+        // 8 frames of main data is 18816 bytes, which fits in 3 << 13
+        assert_eq!(lzma_dict_size(8), 3 << 13);
+        assert_eq!(lzma_dict_size(1), 2 << 11);
+    }
+
+    #[test]
+    fn flac_block_size_matches_mame() {
+        // 8 frames of main data is 4704 stereo samples, which is halved until it's no more than
+        // the size of a sector
+        assert_eq!(flac_block_size(8), 2352);
+        assert_eq!(flac_block_size(1), 588);
+    }
+}
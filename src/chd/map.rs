@@ -0,0 +1,362 @@
+use super::crc16;
+use crate::{
+    bits::{BitReader, BitWriter},
+    error::{Error, Result},
+};
+
+// The map says where each hunk is stored in the file and how it was compressed. In memory, each
+// hunk's entry is 12 bytes long, but on disk the map is compressed: first a list of compression
+// types, which is run-length encoded and then Huffman coded, followed by a bitstream with the
+// length and CRC of each hunk. Offsets aren't stored at all, since hunks are laid out one after
+// another.
+
+/// The hunk is stored uncompressed
+pub const COMPRESSION_NONE: u8 = 4;
+
+/// The previous compression type is repeated 3 - 18 more times
+const COMPRESSION_RLE_SMALL: u8 = 7;
+
+/// The previous compression type is repeated 19 - 274 more times
+const COMPRESSION_RLE_LARGE: u8 = 8;
+
+/// The number of bytes in the header which comes before the compressed map
+pub const MAP_HEADER_SIZE: usize = 16;
+
+/// The Huffman coder MAME uses for compression types has 16 symbols...
+const NUM_CODES: usize = 16;
+
+/// ...and codes which are at most 8 bits long
+const MAX_CODE_BITS: u32 = 8;
+
+/// The length of every code `compress_map` writes
+const CODE_BITS: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapEntry {
+    /// Either the index of the codec which compressed the hunk, or `COMPRESSION_NONE`
+    pub compression: u8,
+    pub length: u32,
+    pub offset: u64,
+
+    /// The CRC of the hunk's uncompressed contents
+    pub crc: u16,
+}
+
+impl MapEntry {
+    /// The entry as it is laid out in memory, which is what the map's CRC covers
+    fn to_bytes(self) -> [u8; 12] {
+        let mut bytes = [0; 12];
+        bytes[0] = self.compression;
+        bytes[1..4].copy_from_slice(&self.length.to_be_bytes()[1..]);
+        bytes[4..10].copy_from_slice(&self.offset.to_be_bytes()[2..]);
+        bytes[10..12].copy_from_slice(&self.crc.to_be_bytes());
+
+        bytes
+    }
+}
+
+/// Compress the map, including its header. `entries` must describe hunks which are stored one
+/// after another in the file.
+pub fn compress_map(entries: &[MapEntry]) -> Vec<u8> {
+    let mut w = BitWriter::default();
+
+    // Every code is given the same length, which makes for a valid (if not very efficient)
+    // Huffman tree. There are so few hunks compared to the amount of data they hold that it isn't
+    // worth doing better.
+    for _ in 0..NUM_CODES {
+        w.write(CODE_BITS, 4);
+    }
+
+    let mut i = 0;
+    while i < entries.len() {
+        let compression = entries[i].compression;
+        w.write(compression.into(), CODE_BITS as usize);
+
+        let run = entries[i + 1..]
+            .iter()
+            .take_while(|entry| entry.compression == compression)
+            .count();
+        i += 1 + run;
+
+        let mut remaining = run;
+        while remaining > 0 {
+            if remaining < 3 {
+                w.write(compression.into(), CODE_BITS as usize);
+                remaining -= 1;
+            } else if remaining <= 18 {
+                w.write(COMPRESSION_RLE_SMALL.into(), CODE_BITS as usize);
+                w.write((remaining - 3) as u32, CODE_BITS as usize);
+                remaining = 0;
+            } else {
+                let count = remaining.min(274);
+                w.write(COMPRESSION_RLE_LARGE.into(), CODE_BITS as usize);
+                w.write(((count - 19) >> 4) as u32, CODE_BITS as usize);
+                w.write(((count - 19) & 0x0F) as u32, CODE_BITS as usize);
+                remaining -= count;
+            }
+        }
+    }
+
+    let max_length = entries
+        .iter()
+        .filter(|entry| entry.compression < COMPRESSION_NONE)
+        .map(|entry| entry.length)
+        .max()
+        .unwrap_or(0);
+    let length_bits = (u32::BITS - max_length.leading_zeros()) as usize;
+
+    for entry in entries {
+        if entry.compression < COMPRESSION_NONE {
+            w.write(entry.length, length_bits);
+        }
+        w.write(entry.crc.into(), 16);
+    }
+
+    let compressed = w.finish();
+    let first_offset = entries.first().map(|entry| entry.offset).unwrap_or(0);
+    let raw_map: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+
+    let mut map = Vec::with_capacity(MAP_HEADER_SIZE + compressed.len());
+    map.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+    map.extend_from_slice(&first_offset.to_be_bytes()[2..]);
+    map.extend_from_slice(&crc16(&raw_map).to_be_bytes());
+    map.push(length_bits as u8);
+    map.push(0); // bits per self reference
+    map.push(0); // bits per parent reference
+    map.push(0); // reserved
+    map.extend_from_slice(&compressed);
+
+    map
+}
+
+/// Decompress the map for `num_hunks` hunks of `hunk_bytes` each. `map` must start with the
+/// map's header. Self and parent references aren't supported, since `compress_map` never
+/// produces them.
+pub fn decompress_map(map: &[u8], num_hunks: usize, hunk_bytes: u32) -> Result<Vec<MapEntry>> {
+    let header = map.get(0..MAP_HEADER_SIZE).ok_or(Error::ChdVerification)?;
+    let map_bytes = u32::from_be_bytes(header[0..4].try_into().unwrap()) as usize;
+    let mut offset = u64::from_be_bytes([
+        0, 0, header[4], header[5], header[6], header[7], header[8], header[9],
+    ]);
+    let map_crc = u16::from_be_bytes([header[10], header[11]]);
+    let length_bits = header[12] as usize;
+
+    let compressed = map
+        .get(MAP_HEADER_SIZE..MAP_HEADER_SIZE + map_bytes)
+        .ok_or(Error::ChdVerification)?;
+    let mut r = BitReader::new(compressed);
+    let codes = read_huffman_tree(&mut r)?;
+
+    let mut compressions = Vec::with_capacity(num_hunks);
+    let mut last = 0;
+    while compressions.len() < num_hunks {
+        let repeats = match decode(&mut r, &codes)? {
+            COMPRESSION_RLE_SMALL => 3 + decode(&mut r, &codes)? as usize,
+            COMPRESSION_RLE_LARGE => {
+                let high = decode(&mut r, &codes)? as usize;
+                let low = decode(&mut r, &codes)? as usize;
+                19 + (high << 4) + low
+            }
+            compression => {
+                last = compression;
+                1
+            }
+        };
+
+        compressions.extend(std::iter::repeat_n(last, repeats));
+    }
+    compressions.truncate(num_hunks);
+
+    let mut entries = Vec::with_capacity(num_hunks);
+    for compression in compressions {
+        let length = match compression {
+            0..=3 => r.read(length_bits),
+            COMPRESSION_NONE => hunk_bytes,
+            _ => Err(Error::ChdVerification)?,
+        };
+        let crc = r.read(16) as u16;
+
+        entries.push(MapEntry {
+            compression,
+            length,
+            offset,
+            crc,
+        });
+        offset += u64::from(length);
+    }
+
+    let raw_map: Vec<u8> = entries.iter().flat_map(|entry| entry.to_bytes()).collect();
+    if r.overflowed() || crc16(&raw_map) != map_crc {
+        Err(Error::ChdVerification)?;
+    }
+
+    Ok(entries)
+}
+
+/// Read the code lengths of a Huffman tree and assign each symbol its code. Returns the
+/// `(code, length)` of every symbol, where a length of 0 means the symbol is unused.
+fn read_huffman_tree(r: &mut BitReader) -> Result<Vec<(u32, u32)>> {
+    let mut lengths = Vec::with_capacity(NUM_CODES);
+
+    while lengths.len() < NUM_CODES {
+        match r.read(4) {
+            1 => match r.read(4) {
+                // A double 1 is just a 1...
+                1 => lengths.push(1),
+                // ...anything else is repeated
+                length => {
+                    let repeats = r.read(4) + 3;
+                    lengths.extend(std::iter::repeat_n(length, repeats as usize));
+                }
+            },
+            length => lengths.push(length),
+        }
+    }
+
+    if lengths.len() != NUM_CODES || lengths.iter().any(|&len| len > MAX_CODE_BITS) {
+        Err(Error::ChdVerification)?;
+    }
+
+    // Codes are assigned canonically, but starting from the longest codes rather than the
+    // shortest
+    let mut starts = [0; MAX_CODE_BITS as usize + 1];
+    let mut start = 0;
+    for len in (1..=MAX_CODE_BITS).rev() {
+        let count = lengths.iter().filter(|&&l| l == len).count() as u32;
+        starts[len as usize] = start;
+        start = (start + count) >> 1;
+    }
+
+    let codes = lengths
+        .iter()
+        .map(|&len| {
+            if len == 0 {
+                return (0, 0);
+            }
+
+            let code = starts[len as usize];
+            starts[len as usize] += 1;
+            (code, len)
+        })
+        .collect();
+
+    Ok(codes)
+}
+
+/// Decode a single symbol from `r` with the given `(code, length)` pairs
+fn decode(r: &mut BitReader, codes: &[(u32, u32)]) -> Result<u8> {
+    let mut code = 0;
+
+    for len in 1..=MAX_CODE_BITS {
+        code = (code << 1) | r.read(1);
+
+        let symbol = codes.iter().position(|&c| c == (code, len));
+        if let Some(symbol) = symbol {
+            return Ok(symbol as u8);
+        }
+    }
+
+    Err(Error::ChdVerification)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HUNK_BYTES: u32 = 8 * 2448;
+
+    /// Entries for hunks stored one after another, compressed in the given ways
+    fn entries(compressions: &[u8]) -> Vec<MapEntry> {
+        let mut offset = 0x1000;
+
+        compressions
+            .iter()
+            .enumerate()
+            .map(|(i, &compression)| {
+                let length = match compression {
+                    COMPRESSION_NONE => HUNK_BYTES,
+                    _ => 1000 + (i as u32 * 37) % 5000,
+                };
+                let entry = MapEntry {
+                    compression,
+                    length,
+                    offset,
+                    crc: (i as u16).wrapping_mul(0x9E37),
+                };
+                offset += u64::from(length);
+
+                entry
+            })
+            .collect()
+    }
+
+    fn round_trip(compressions: &[u8]) {
+        let entries = entries(compressions);
+        let map = compress_map(&entries);
+        let decompressed = decompress_map(&map, entries.len(), HUNK_BYTES).unwrap();
+
+        assert_eq!(decompressed, entries);
+    }
+
+    /// A run of `1 + repeats` hunks compressed the same way
+    fn run(compression: u8, repeats: usize) -> Vec<u8> {
+        vec![compression; 1 + repeats]
+    }
+
+    #[test]
+    fn round_trips_short_runs() {
+        // Runs of 1 or 2 repeats are written out, and 3 - 18 use the small RLE code
+        for repeats in [1, 2, 3, 4, 17, 18] {
+            round_trip(&[run(0, repeats), run(1, repeats), vec![2]].concat());
+        }
+    }
+
+    #[test]
+    fn round_trips_long_runs() {
+        // Runs of 19 - 274 use the large RLE code, and longer ones are split up
+        for repeats in [19, 20, 100, 274, 275, 276, 293, 300, 548, 1000] {
+            round_trip(&[run(1, repeats), run(COMPRESSION_NONE, repeats), vec![0]].concat());
+        }
+    }
+
+    #[test]
+    fn round_trips_mixed_compressions() {
+        round_trip(&[0, 1, 2, 3, COMPRESSION_NONE, 0, 0, 3, 3, 3, 3]);
+        round_trip(&[COMPRESSION_NONE]);
+    }
+
+    #[test]
+    fn writes_a_small_rle_run() {
+        let entries: Vec<MapEntry> = (0..6)
+            .map(|i| MapEntry {
+                compression: 0,
+                length: 3,
+                offset: 0x2000 + i * 3,
+                crc: 0xABCD,
+            })
+            .collect();
+        let map = compress_map(&entries);
+
+        // Sixteen 4-bit code lengths of 4, then codec 0, the small RLE code with 5 - 3 = 2
+        // repeats, and the 2-bit length and 16-bit CRC of each hunk
+        let expected = [
+            0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x44, 0x07, 0x2E, 0xAF, 0x37, 0xAB, 0xCD,
+            0xEA, 0xF3, 0x7A, 0xBC, 0xDE, 0xAF, 0x37, 0xAB, 0xCD,
+        ];
+        assert_eq!(map[MAP_HEADER_SIZE..], expected);
+
+        assert_eq!(map[0..4], (expected.len() as u32).to_be_bytes());
+        assert_eq!(map[4..10], [0, 0, 0, 0, 0x20, 0x00]);
+        assert_eq!(map[12], 2); // bits per length
+        assert_eq!(map[13..16], [0, 0, 0]);
+    }
+
+    #[test]
+    fn rejects_a_bad_crc() {
+        let entries = entries(&[0, 1, 2]);
+        let mut map = compress_map(&entries);
+        map[10] ^= 0xFF;
+
+        assert!(decompress_map(&map, entries.len(), HUNK_BYTES).is_err());
+    }
+}
@@ -0,0 +1,36 @@
+mod codec;
+mod map;
+mod verify;
+mod writer;
+
+// Code for writing MAME's compressed hunks of data (.chd) files. There is no formal spec for the
+// format, so MAME's own implementation is the reference:
+// https://github.com/mamedev/mame/blob/master/src/lib/util/chd.cpp for the container, and
+// https://github.com/mamedev/mame/blob/master/src/lib/util/chdcodec.cpp for the compression codecs.
+// Only version 5 of the format, which is what current versions of chdman write, is supported.
+
+pub use codec::Codec;
+pub use verify::verify;
+pub use writer::ChdWriter;
+
+/// The number of bytes in a version 5 header
+const HEADER_SIZE: usize = 124;
+
+/// The number of bytes ahead of each metadata entry
+const METADATA_HEADER_SIZE: usize = 16;
+
+/// Metadata flag marking entries which are included in the file's overall SHA1
+const METADATA_CHECKSUM: u8 = 0x01;
+
+/// The CRC-16/CCITT checksum MAME uses for hunks and the map
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
@@ -0,0 +1,174 @@
+use super::{
+    codec::Codec,
+    crc16,
+    map::{decompress_map, COMPRESSION_NONE, MAP_HEADER_SIZE},
+    HEADER_SIZE, METADATA_CHECKSUM, METADATA_HEADER_SIZE,
+};
+use crate::error::{Error, Result};
+use nom::{
+    bytes::complete::{tag, take},
+    multi::count,
+    number::complete::{be_u32, be_u64},
+    sequence::tuple,
+    Finish, IResult,
+};
+use sha1::{Digest, Sha1};
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+struct Header {
+    codecs: Vec<Option<Codec>>,
+    logical_bytes: u64,
+    map_offset: u64,
+    metadata_offset: u64,
+    hunk_bytes: u32,
+    raw_sha1: Vec<u8>,
+    sha1: Vec<u8>,
+}
+
+/// Read back a .chd file, decompressing every hunk and checking it against its CRC, and checking
+/// the data and metadata against the SHA1s in the header
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<()> {
+    let file = File::open(path).map_err(Error::Io)?;
+    let mut reader = BufReader::new(file);
+
+    let header_bytes = read_at(&mut reader, 0, HEADER_SIZE)?;
+    let (_, header) = header(&header_bytes)
+        .finish()
+        .map_err(|_| Error::ChdVerification)?;
+
+    let hunk_bytes = header.hunk_bytes as u64;
+    let num_hunks = header.logical_bytes.div_ceil(hunk_bytes);
+
+    let map_header = read_at(&mut reader, header.map_offset, MAP_HEADER_SIZE)?;
+    let map_bytes = u32::from_be_bytes(map_header[0..4].try_into().unwrap()) as usize;
+    let map = read_at(&mut reader, header.map_offset, MAP_HEADER_SIZE + map_bytes)?;
+    let entries = decompress_map(&map, num_hunks as usize, header.hunk_bytes)?;
+
+    let mut raw_sha1 = Sha1::new();
+    let mut remaining = header.logical_bytes;
+
+    for entry in entries {
+        let data = read_at(&mut reader, entry.offset, entry.length as usize)?;
+        let hunk = match entry.compression {
+            COMPRESSION_NONE => data,
+            i => header
+                .codecs
+                .get(i as usize)
+                .copied()
+                .flatten()
+                .ok_or(Error::ChdVerification)?
+                .decompress(&data, hunk_bytes as usize)?,
+        };
+
+        if hunk.len() as u64 != hunk_bytes || crc16(&hunk) != entry.crc {
+            Err(Error::ChdVerification)?;
+        }
+
+        let logical_len = remaining.min(hunk_bytes);
+        raw_sha1.update(&hunk[..logical_len as usize]);
+        remaining -= logical_len;
+    }
+
+    let raw_sha1 = raw_sha1.finalize();
+    if raw_sha1.as_slice() != header.raw_sha1 {
+        Err(Error::ChdVerification)?;
+    }
+
+    let mut metadata_hashes = Vec::new();
+    let mut metadata_offset = header.metadata_offset;
+
+    while metadata_offset != 0 {
+        let entry = read_at(&mut reader, metadata_offset, METADATA_HEADER_SIZE)?;
+        let flags = entry[4];
+        let len = u32::from_be_bytes([0, entry[5], entry[6], entry[7]]) as usize;
+        let data = read_at(
+            &mut reader,
+            metadata_offset + METADATA_HEADER_SIZE as u64,
+            len,
+        )?;
+
+        if flags & METADATA_CHECKSUM != 0 {
+            let mut hash = entry[0..4].to_vec();
+            hash.extend_from_slice(&Sha1::digest(&data));
+            metadata_hashes.push(hash);
+        }
+
+        metadata_offset = u64::from_be_bytes(entry[8..16].try_into().unwrap());
+    }
+
+    metadata_hashes.sort();
+
+    let mut sha1 = Sha1::new();
+    sha1.update(raw_sha1);
+    for hash in metadata_hashes {
+        sha1.update(hash);
+    }
+
+    if sha1.finalize().as_slice() != header.sha1 {
+        Err(Error::ChdVerification)?;
+    }
+
+    Ok(())
+}
+
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0; len];
+    reader.seek(SeekFrom::Start(offset)).map_err(Error::Io)?;
+    reader.read_exact(&mut buf).map_err(Error::Io)?;
+
+    Ok(buf)
+}
+
+fn header(input: &[u8]) -> IResult<&[u8], Header> {
+    let (
+        rest,
+        (
+            _,
+            _,
+            _,
+            codecs,
+            logical_bytes,
+            map_offset,
+            metadata_offset,
+            hunk_bytes,
+            _,
+            raw_sha1,
+            sha1,
+            _,
+        ),
+    ) = tuple((
+        tag("MComprHD"),
+        be_u32,                 // header length
+        tag(&[0, 0, 0, 5][..]), // version
+        count(codec, 4),
+        be_u64,        // logical bytes
+        be_u64,        // map offset
+        be_u64,        // metadata offset
+        be_u32,        // hunk bytes
+        be_u32,        // unit bytes
+        take(20usize), // raw SHA1
+        take(20usize), // SHA1
+        take(20usize), // parent SHA1
+    ))(input)?;
+
+    let header = Header {
+        codecs,
+        logical_bytes,
+        map_offset,
+        metadata_offset,
+        hunk_bytes,
+        raw_sha1: raw_sha1.to_vec(),
+        sha1: sha1.to_vec(),
+    };
+
+    Ok((rest, header))
+}
+
+fn codec(input: &[u8]) -> IResult<&[u8], Option<Codec>> {
+    let (rest, tag) = take(4usize)(input)?;
+    Ok((rest, Codec::from_tag(tag.try_into().unwrap())))
+}
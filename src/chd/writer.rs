@@ -0,0 +1,180 @@
+use super::{
+    codec::Codec,
+    crc16,
+    map::{compress_map, MapEntry, COMPRESSION_NONE},
+    HEADER_SIZE, METADATA_CHECKSUM, METADATA_HEADER_SIZE,
+};
+use crate::error::{Error, Result};
+use sha1::{Digest, Sha1};
+use std::io::{Seek, SeekFrom, Write};
+
+/// Writes a .chd file one hunk at a time. The header, metadata, and map are only written once
+/// every hunk has been, by `finish`.
+pub struct ChdWriter<W> {
+    writer: W,
+    codecs: Vec<Codec>,
+    hunk_bytes: u32,
+    unit_bytes: u32,
+    logical_bytes: u64,
+    bytes_hashed: u64,
+    offset: u64,
+    map: Vec<MapEntry>,
+    metadata: Vec<([u8; 4], Vec<u8>)>,
+    raw_sha1: Sha1,
+}
+
+impl<W: Write + Seek> ChdWriter<W> {
+    /// Start a new .chd holding `logical_bytes` of data, split into hunks of `hunk_bytes`. Each
+    /// hunk is compressed with whichever of `codecs` does best. There can be up to four of them.
+    pub fn new(
+        mut writer: W,
+        codecs: Vec<Codec>,
+        hunk_bytes: u32,
+        unit_bytes: u32,
+        logical_bytes: u64,
+    ) -> Result<Self> {
+        // Leave space for the header, which can't be filled in until the end
+        writer.write_all(&[0; HEADER_SIZE]).map_err(Error::Io)?;
+
+        Ok(Self {
+            writer,
+            codecs,
+            hunk_bytes,
+            unit_bytes,
+            logical_bytes,
+            bytes_hashed: 0,
+            offset: HEADER_SIZE as u64,
+            map: Vec::new(),
+            metadata: Vec::new(),
+            raw_sha1: Sha1::new(),
+        })
+    }
+
+    /// Add a metadata entry, which will be included in the file's overall SHA1
+    pub fn add_metadata(&mut self, tag: [u8; 4], data: Vec<u8>) {
+        self.metadata.push((tag, data));
+    }
+
+    /// Compress and write the next hunk. `skip` can be used to leave out codecs which are
+    /// unlikely to do well on this hunk, to save time.
+    pub fn write_hunk<F>(&mut self, hunk: &[u8], skip: F) -> Result<()>
+    where
+        F: Fn(Codec) -> bool,
+    {
+        let remaining = self.logical_bytes - self.bytes_hashed;
+        let logical_len = remaining.min(hunk.len() as u64);
+        self.raw_sha1.update(&hunk[..logical_len as usize]);
+        self.bytes_hashed += logical_len;
+
+        let mut best: Option<(u8, Vec<u8>)> = None;
+        for (i, &codec) in self.codecs.iter().enumerate() {
+            if skip(codec) {
+                continue;
+            }
+
+            let compressed = codec.compress(hunk)?;
+            let is_better = best
+                .as_ref()
+                .is_none_or(|(_, data)| compressed.len() < data.len());
+
+            if is_better {
+                best = Some((i as u8, compressed));
+            }
+        }
+
+        let (compression, data) = match best {
+            Some((i, data)) if data.len() < hunk.len() => (i, data),
+            _ => (COMPRESSION_NONE, hunk.to_vec()),
+        };
+
+        self.writer.write_all(&data).map_err(Error::Io)?;
+        self.map.push(MapEntry {
+            compression,
+            length: data.len() as u32,
+            offset: self.offset,
+            crc: crc16(hunk),
+        });
+        self.offset += data.len() as u64;
+
+        Ok(())
+    }
+
+    /// Write the metadata, map, and header, and hand back the underlying writer
+    pub fn finish(mut self) -> Result<W> {
+        let metadata_offset = if self.metadata.is_empty() {
+            0
+        } else {
+            self.offset
+        };
+
+        let mut metadata_hashes = Vec::with_capacity(self.metadata.len());
+        let num_metadata = self.metadata.len();
+
+        for (i, (tag, data)) in self.metadata.iter().enumerate() {
+            let entry_len = (METADATA_HEADER_SIZE + data.len()) as u64;
+            let next = if i + 1 < num_metadata {
+                self.offset + entry_len
+            } else {
+                0
+            };
+
+            let flags_and_len = u32::from(METADATA_CHECKSUM) << 24 | data.len() as u32;
+            let mut header = Vec::with_capacity(METADATA_HEADER_SIZE);
+            header.extend_from_slice(tag);
+            header.extend_from_slice(&flags_and_len.to_be_bytes());
+            header.extend_from_slice(&next.to_be_bytes());
+
+            self.writer.write_all(&header).map_err(Error::Io)?;
+            self.writer.write_all(data).map_err(Error::Io)?;
+            self.offset += entry_len;
+
+            let mut hash = tag.to_vec();
+            hash.extend_from_slice(&Sha1::digest(data));
+            metadata_hashes.push(hash);
+        }
+
+        let map_offset = self.offset;
+        self.writer
+            .write_all(&compress_map(&self.map))
+            .map_err(Error::Io)?;
+
+        let raw_sha1 = self.raw_sha1.finalize();
+        metadata_hashes.sort();
+
+        let mut sha1 = Sha1::new();
+        sha1.update(raw_sha1);
+        for hash in metadata_hashes {
+            sha1.update(hash);
+        }
+        let sha1 = sha1.finalize();
+
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        header.extend_from_slice(b"MComprHD");
+        header.extend_from_slice(&(HEADER_SIZE as u32).to_be_bytes());
+        header.extend_from_slice(&5u32.to_be_bytes()); // version
+
+        for i in 0..4 {
+            let tag = self
+                .codecs
+                .get(i)
+                .map(|codec| codec.tag())
+                .unwrap_or([0; 4]);
+            header.extend_from_slice(&tag);
+        }
+
+        header.extend_from_slice(&self.logical_bytes.to_be_bytes());
+        header.extend_from_slice(&map_offset.to_be_bytes());
+        header.extend_from_slice(&metadata_offset.to_be_bytes());
+        header.extend_from_slice(&self.hunk_bytes.to_be_bytes());
+        header.extend_from_slice(&self.unit_bytes.to_be_bytes());
+        header.extend_from_slice(&raw_sha1);
+        header.extend_from_slice(&sha1);
+        header.extend_from_slice(&[0; 20]); // no parent
+
+        self.writer.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+        self.writer.write_all(&header).map_err(Error::Io)?;
+        self.writer.flush().map_err(Error::Io)?;
+
+        Ok(self.writer)
+    }
+}
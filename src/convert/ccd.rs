@@ -0,0 +1,246 @@
+use crate::{
+    error::{Error, Result},
+    loader::{load_mds, LoadOptions},
+    mds::{Mds, Track, TrackMode},
+    subchannel::Subchannel,
+    timecode::Timecode,
+    util::{reader_for_track, writer_with_extension},
+};
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+// CloneCD images are made up of three files: a .ccd text file holding the disc's TOC, an .img
+// with the raw contents of every sector, and an optional .sub with each sector's subchannel data.
+// There's no official documentation of the format, but a description can be found at
+// https://psx-spx.consoledev.net/cdromdrive/#cdrom-disk-images-ccdimgsub-clonecd.
+
+/// CloneCD images can only hold raw sectors
+const RAW_SECTOR_SIZE: usize = 0x930;
+
+/// Convert a .mdf file (described by a .mds file) into CloneCD .ccd, .img, and .sub files. The
+/// .sub file is only written if every track was imaged with subchannel data.
+pub fn convert<P: AsRef<Path>>(mds_file: P, options: &LoadOptions, output: &Path) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks = mds.sessions().flat_map(|session| session.data_tracks());
+    let mut without_subchannels = None;
+
+    for track in tracks {
+        let data_size = track.sector_data_size();
+        if data_size != RAW_SECTOR_SIZE {
+            Err(Error::RawSectorsRequired(track.mode, data_size))?;
+        }
+
+        if track.sector_subchannel_size() == 0 {
+            without_subchannels.get_or_insert(track.number());
+        }
+    }
+
+    let ccd_writer = writer_with_extension(output, "ccd")?;
+    let img_writer = writer_with_extension(output, "img")?;
+    let sub_writer = match without_subchannels {
+        Some(number) => {
+            println!("Not writing a .sub, as track {number} was imaged without subchannel data");
+            None
+        }
+        None => Some(writer_with_extension(output, "sub")?),
+    };
+
+    mds_to_ccd(&mds, ccd_writer)?;
+    mds_to_img(&mds, &mds_file, options, img_writer, sub_writer)
+}
+
+/// Generate a .ccd file from the TOC of every session in an .mds file and write it to `writer`
+fn mds_to_ccd<W: Write>(mds: &Mds, mut writer: W) -> Result<()> {
+    let num_entries: usize = mds.sessions().map(|s| s.toc_entries().count()).sum();
+    let num_sessions = mds.sessions().count();
+
+    writeln!(writer, "[CloneCD]").map_err(Error::Io)?;
+    writeln!(writer, "Version=3").map_err(Error::Io)?;
+    writeln!(writer, "[Disc]").map_err(Error::Io)?;
+    writeln!(writer, "TocEntries={num_entries}").map_err(Error::Io)?;
+    writeln!(writer, "Sessions={num_sessions}").map_err(Error::Io)?;
+    writeln!(writer, "DataTracksScrambled=0").map_err(Error::Io)?;
+    writeln!(writer, "CDTextLength=0").map_err(Error::Io)?;
+
+    for session in mds.sessions() {
+        let pregap_mode = session.data_tracks().next().map(ccd_mode).unwrap_or(0);
+
+        writeln!(writer, "[Session {}]", session.number()).map_err(Error::Io)?;
+        writeln!(writer, "PreGapMode={pregap_mode}").map_err(Error::Io)?;
+        writeln!(writer, "PreGapSubC=0").map_err(Error::Io)?;
+    }
+
+    let entries = mds
+        .sessions()
+        .flat_map(|session| session.toc_entries().map(move |entry| (session, entry)));
+
+    for (i, (session, entry)) in entries.enumerate() {
+        let alba = lba(Timecode::from_msf(
+            entry.absolute_minute,
+            entry.absolute_second,
+            entry.absolute_frame,
+        ));
+        let plba = lba(Timecode::from_track(entry));
+
+        writeln!(writer, "[Entry {i}]").map_err(Error::Io)?;
+        writeln!(writer, "Session={}", session.number()).map_err(Error::Io)?;
+        writeln!(writer, "Point=0x{:02x}", entry.number()).map_err(Error::Io)?;
+        writeln!(writer, "ADR=0x{:02x}", entry.adr()).map_err(Error::Io)?;
+        writeln!(writer, "Control=0x{:02x}", entry.control()).map_err(Error::Io)?;
+        writeln!(writer, "TrackNo={}", entry.track_number()).map_err(Error::Io)?;
+        writeln!(writer, "AMin={}", entry.absolute_minute).map_err(Error::Io)?;
+        writeln!(writer, "ASec={}", entry.absolute_second).map_err(Error::Io)?;
+        writeln!(writer, "AFrame={}", entry.absolute_frame).map_err(Error::Io)?;
+        writeln!(writer, "ALBA={alba}").map_err(Error::Io)?;
+        writeln!(writer, "Zero=0").map_err(Error::Io)?;
+        writeln!(writer, "PMin={}", entry.minute).map_err(Error::Io)?;
+        writeln!(writer, "PSec={}", entry.second).map_err(Error::Io)?;
+        writeln!(writer, "PFrame={}", entry.frame).map_err(Error::Io)?;
+        writeln!(writer, "PLBA={plba}").map_err(Error::Io)?;
+    }
+
+    let tracks = mds.sessions().flat_map(|session| session.data_tracks());
+
+    for track in tracks {
+        let index1 = track.track_start_sector;
+        let pregap = track.pregap_sectors() as i32;
+
+        writeln!(writer, "[TRACK {}]", track.number()).map_err(Error::Io)?;
+        writeln!(writer, "MODE={}", ccd_mode(track)).map_err(Error::Io)?;
+
+        if pregap > 0 {
+            writeln!(writer, "INDEX 0={}", index1 - pregap).map_err(Error::Io)?;
+        }
+
+        writeln!(writer, "INDEX 1={index1}").map_err(Error::Io)?;
+    }
+
+    Ok(())
+}
+
+/// Write the raw sectors of every track to `img_writer`, and their deinterleaved subchannel data
+/// to `sub_writer` if there is one
+fn mds_to_img<P, W>(
+    mds: &Mds,
+    mds_path: P,
+    options: &LoadOptions,
+    mut img_writer: W,
+    mut sub_writer: Option<W>,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    let tracks = mds.sessions().flat_map(|session| session.data_tracks());
+
+    for track in tracks {
+        let mut mdf_reader = reader_for_track(&mds_path, track, options)?;
+        mdf_reader
+            .seek(SeekFrom::Start(track.track_start_offset))
+            .map_err(Error::Io)?;
+
+        let data_size = track.sector_data_size();
+        let mut buf = vec![0; track.sector_size()];
+
+        for _ in 0..track.pregap_sectors() + track.num_sectors() {
+            mdf_reader.read_exact(&mut buf).map_err(Error::Io)?;
+            img_writer
+                .write_all(&buf[0..data_size])
+                .map_err(Error::Io)?;
+
+            if let Some(sub_writer) = sub_writer.as_mut() {
+                let subchannel = Subchannel::from_raw(&buf[data_size..]);
+                sub_writer
+                    .write_all(&subchannel.to_bytes())
+                    .map_err(Error::Io)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The MODE of a track as it appears in a .ccd file
+fn ccd_mode(track: &Track) -> u8 {
+    use TrackMode::*;
+
+    match track.mode {
+        None | Audio => 0,
+        Mode1 => 1,
+        Mode2 | Mode2Form1 | Mode2Form2 => 2,
+    }
+}
+
+/// Convert an MSF address from the TOC into an LBA. MSF addresses include the two second pregap
+/// ahead of the first track, while LBAs start counting after it.
+fn lba(timecode: Timecode) -> i32 {
+    timecode.sectors() - 150
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+    use std::fs;
+
+    /// A data track followed by an audio track with a stored pregap, both in disc.bin
+    fn two_track_disc(name: &str) -> TestDisc {
+        let cue = "FILE disc.bin BINARY\n\
+                   TRACK 01 MODE1/2352\n\
+                   INDEX 01 00:00:00\n\
+                   TRACK 02 AUDIO\n\
+                   INDEX 00 00:00:10\n\
+                   INDEX 01 00:00:30\n";
+        let data: Vec<u8> = (0..2352 * 70).map(|i| (i % 251) as u8).collect();
+        TestDisc::from_cue(name, cue, &[("disc.bin", &data)])
+    }
+
+    fn ccd_text(mds: &Mds) -> String {
+        let mut ccd = Vec::new();
+        mds_to_ccd(mds, &mut ccd).unwrap();
+        String::from_utf8(ccd).unwrap()
+    }
+
+    #[test]
+    fn ccds_list_the_toc_and_track_indexes() {
+        let disc = two_track_disc("ccd-text");
+        let ccd = ccd_text(disc.mds());
+
+        assert!(ccd.starts_with(
+            "[CloneCD]\nVersion=3\n[Disc]\nTocEntries=5\nSessions=1\nDataTracksScrambled=0\n\
+             CDTextLength=0\n[Session 1]\nPreGapMode=1\nPreGapSubC=0\n"
+        ));
+
+        // The first and last track numbers are given as PMin
+        assert!(ccd.contains("[Entry 0]\nSession=1\nPoint=0xa0\nADR=0x01\nControl=0x04\n"));
+        assert!(ccd.contains("ALBA=-150\nZero=0\nPMin=1\nPSec=0\nPFrame=0\nPLBA=4350\n[Entry 1]"));
+        assert!(ccd.contains("ALBA=-150\nZero=0\nPMin=2\nPSec=0\nPFrame=0\nPLBA=8850\n[Entry 2]"));
+
+        // The lead-out entry, whose PMSF is where the session ends
+        assert!(ccd.contains(
+            "[Entry 2]\nSession=1\nPoint=0xa2\nADR=0x01\nControl=0x00\nTrackNo=0\nAMin=0\n\
+             ASec=0\nAFrame=0\nALBA=-150\nZero=0\nPMin=0\nPSec=2\nPFrame=70\nPLBA=70\n"
+        ));
+
+        // Track 2's entry points at its INDEX 01
+        assert!(ccd.contains(
+            "[Entry 4]\nSession=1\nPoint=0x02\nADR=0x01\nControl=0x00\nTrackNo=0\nAMin=0\n\
+             ASec=0\nAFrame=0\nALBA=-150\nZero=0\nPMin=0\nPSec=2\nPFrame=30\nPLBA=30\n"
+        ));
+
+        assert!(ccd.ends_with(
+            "[TRACK 1]\nMODE=1\nINDEX 1=0\n[TRACK 2]\nMODE=0\nINDEX 0=10\nINDEX 1=30\n"
+        ));
+    }
+
+    #[test]
+    fn imgs_hold_every_stored_sector_in_order() {
+        let disc = two_track_disc("ccd-img");
+        let mut img = Vec::new();
+        mds_to_img(disc.mds(), disc.mds_path(), disc.options(), &mut img, None).unwrap();
+
+        assert_eq!(img, fs::read(disc.dir().join("disc.bin")).unwrap());
+    }
+}
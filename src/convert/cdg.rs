@@ -0,0 +1,129 @@
+use crate::{
+    error::{Error, Result},
+    loader::LoadOptions,
+    mds::Track,
+    subchannel::track_subchannels,
+};
+use std::{io::Write, path::Path};
+
+// CD+G graphics are stored in the R-W subchannels. Each sector carries 96 six-bit symbols, one
+// from each byte of its subchannel data, which make up four 24-symbol packets. A .cdg file is just
+// those packets one after another, with each symbol in its own byte. See
+// https://jbum.com/cdg_revealed.html for the packet layout.
+//
+// On the disc the packets are interleaved to spread out damage from scratches: three pairs of
+// symbols in each packet are swapped, and then each symbol is delayed by between zero and seven
+// packets depending on its position. Images made from raw subchannel data keep this interleaving,
+// so it has to be undone before the packets are any use to a karaoke player.
+
+/// The number of symbols in a CD+G packet
+const PACKET_SIZE: usize = 24;
+
+/// The pairs of symbols in each packet which are swapped with each other on the disc
+const SWAPPED_SYMBOLS: [(usize, usize); 3] = [(1, 18), (2, 5), (3, 23)];
+
+/// Write the CD+G packets of an audio track to `writer` in the .cdg format, starting with its
+/// pregap so that they line up with the track's .wav
+pub fn track_to_cdg<P, W>(
+    track: &Track,
+    mds_path: P,
+    options: &LoadOptions,
+    mut writer: W,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    let mut symbols = Vec::new();
+    for sector in track_subchannels(track, mds_path, options)? {
+        let (_, subchannel) = sector?;
+        symbols.extend_from_slice(&subchannel.rw_symbols());
+    }
+
+    let packets = deinterleave(&symbols);
+    writer.write_all(&packets).map_err(Error::Io)
+}
+
+/// Undo the interleaving of a stream of R-W symbols. The last few packets of a track borrow
+/// symbols from the track after it, which are filled in with zeros.
+fn deinterleave(symbols: &[u8]) -> Vec<u8> {
+    let num_packets = symbols.len() / PACKET_SIZE;
+    let mut packets = vec![0; num_packets * PACKET_SIZE];
+
+    for (p, packet) in packets.chunks_exact_mut(PACKET_SIZE).enumerate() {
+        for (i, symbol) in packet.iter_mut().enumerate() {
+            let source = (p + i % 8) * PACKET_SIZE + i;
+            *symbol = symbols.get(source).copied().unwrap_or(0);
+        }
+
+        for (a, b) in SWAPPED_SYMBOLS {
+            packet.swap(a, b);
+        }
+    }
+
+    packets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Interleave packets the way they are written to the disc, the reverse of `deinterleave`
+    fn interleave(packets: &[u8]) -> Vec<u8> {
+        let num_packets = packets.len() / PACKET_SIZE;
+        let mut symbols = vec![0; (num_packets + 7) * PACKET_SIZE];
+
+        for (p, packet) in packets.chunks_exact(PACKET_SIZE).enumerate() {
+            let mut packet = packet.to_vec();
+            for (a, b) in SWAPPED_SYMBOLS {
+                packet.swap(a, b);
+            }
+
+            for (i, symbol) in packet.into_iter().enumerate() {
+                symbols[(p + i % 8) * PACKET_SIZE + i] = symbol;
+            }
+        }
+
+        symbols
+    }
+
+    #[test]
+    fn symbols_are_delayed_by_their_position() {
+        let mut symbols = vec![0; 8 * PACKET_SIZE];
+        symbols[0] = 0x01;
+        symbols[7 * PACKET_SIZE + 7] = 0x07;
+        symbols[PACKET_SIZE + 9] = 0x09;
+
+        let packets = deinterleave(&symbols);
+        assert_eq!(packets[0], 0x01);
+        assert_eq!(packets[7], 0x07);
+        assert_eq!(packets[9], 0x09);
+    }
+
+    #[test]
+    fn swapped_symbols_are_put_back() {
+        let mut symbols = vec![0; 8 * PACKET_SIZE];
+
+        // Symbol 1 is stored in place of symbol 18, so it's delayed by two packets
+        symbols[2 * PACKET_SIZE + 18] = 0x2A;
+        // And symbol 18 is stored in place of symbol 1, one packet late
+        symbols[PACKET_SIZE + 1] = 0x15;
+
+        let packets = deinterleave(&symbols);
+        assert_eq!(packets[1], 0x2A);
+        assert_eq!(packets[18], 0x15);
+    }
+
+    #[test]
+    fn interleaved_packets_come_back_unchanged() {
+        let packets: Vec<u8> = (0..3 * PACKET_SIZE).map(|i| (i % 0x3F) as u8 + 1).collect();
+
+        let deinterleaved = deinterleave(&interleave(&packets));
+        assert_eq!(deinterleaved[..packets.len()], packets);
+
+        // Nothing follows the last packet, so the packets after it are left empty
+        assert!(deinterleaved[packets.len()..]
+            .iter()
+            .all(|&symbol| symbol == 0));
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{
+    chd::{self, ChdWriter, Codec},
+    error::{Error, Result},
+    loader::{load_mds, LoadOptions},
+    mds::{Track, TrackMode},
+    util::{reader_for_track, set_extension},
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+// CD images are stored in a .chd as a series of 2448 byte frames, each holding one sector
+// followed by its subchannel data. Sectors which hold less than 2352 bytes are padded with zeros,
+// and audio is stored big endian. The layout of the tracks is described by one CHT2 metadata
+// entry per track. See https://github.com/mamedev/mame/blob/master/src/lib/util/cdrom.cpp.
+
+/// The number of bytes in each frame, which is a raw sector and its subchannel data
+const FRAME_SIZE: usize = 0x930 + 0x60;
+
+/// The number of frames in each hunk. This is what chdman uses for CDs.
+const FRAMES_PER_HUNK: usize = 8;
+
+/// Each track is padded out to a multiple of this many frames
+const TRACK_PADDING: usize = 4;
+
+/// Convert a .mdf file (described by a .mds file) into a .chd file, which is then read back to
+/// make sure it was written correctly. When `subchannels` is true, subchannel data is kept for
+/// tracks which have it.
+pub fn convert<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    output: &Path,
+    subchannels: bool,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks: Vec<&Track> = mds
+        .sessions()
+        .flat_map(|session| session.data_tracks())
+        .collect();
+
+    let mut metadata = Vec::with_capacity(tracks.len());
+    let mut num_frames = 0;
+
+    for track in &tracks {
+        let keep_subchannels = subchannels && track.sector_subchannel_size() > 0;
+        let track_metadata = cht2_metadata(track, keep_subchannels)?;
+        metadata.push(track_metadata);
+
+        let frames = track.pregap_sectors() + track.num_sectors();
+        num_frames += padded_frames(frames);
+    }
+
+    let chd_path = set_extension(output, "chd");
+    let file = File::create(&chd_path).map_err(Error::Io)?;
+    let codecs = vec![Codec::Lzma, Codec::Zlib, Codec::Flac];
+    let mut writer = ChdWriter::new(
+        BufWriter::new(file),
+        codecs,
+        (FRAMES_PER_HUNK * FRAME_SIZE) as u32,
+        FRAME_SIZE as u32,
+        (num_frames * FRAME_SIZE) as u64,
+    )?;
+
+    for data in metadata {
+        writer.add_metadata(*b"CHT2", data);
+    }
+
+    let mut hunk = HunkBuffer::default();
+
+    for track in tracks {
+        let mut reader = reader_for_track(&mds_file, track, options)?;
+        reader
+            .seek(SeekFrom::Start(track.track_start_offset))
+            .map_err(Error::Io)?;
+
+        let data_size = track.sector_data_size();
+        let subchannel_size = track.sector_subchannel_size();
+        let is_audio = matches!(track.mode, TrackMode::Audio);
+        let frames = track.pregap_sectors() + track.num_sectors();
+
+        let mut buf = vec![0; track.sector_size()];
+        let mut frame = [0; FRAME_SIZE];
+
+        for _ in 0..frames {
+            reader.read_exact(&mut buf).map_err(Error::Io)?;
+
+            frame.fill(0);
+            frame[..data_size].copy_from_slice(&buf[..data_size]);
+
+            if is_audio {
+                for sample in frame[..data_size].chunks_exact_mut(2) {
+                    sample.swap(0, 1);
+                }
+            }
+
+            if subchannels && subchannel_size > 0 {
+                frame[FRAME_SIZE - subchannel_size..].copy_from_slice(&buf[data_size..]);
+            }
+
+            hunk.push(&frame, is_audio, &mut writer)?;
+        }
+
+        for _ in frames..padded_frames(frames) {
+            hunk.push(&[0; FRAME_SIZE], is_audio, &mut writer)?;
+        }
+    }
+
+    hunk.flush(&mut writer)?;
+    writer.finish()?;
+
+    chd::verify(&chd_path)
+}
+
+/// Collects frames until there are enough to fill a hunk
+#[derive(Default)]
+struct HunkBuffer {
+    data: Vec<u8>,
+    has_audio: bool,
+}
+
+impl HunkBuffer {
+    fn push<W: Write + Seek>(
+        &mut self,
+        frame: &[u8],
+        is_audio: bool,
+        writer: &mut ChdWriter<W>,
+    ) -> Result<()> {
+        self.data.extend_from_slice(frame);
+        self.has_audio |= is_audio;
+
+        if self.data.len() == FRAMES_PER_HUNK * FRAME_SIZE {
+            self.flush(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Write out whatever has been collected, padding it with zeros to fill a whole hunk
+    fn flush<W: Write + Seek>(&mut self, writer: &mut ChdWriter<W>) -> Result<()> {
+        if self.data.is_empty() {
+            return Ok(());
+        }
+
+        self.data.resize(FRAMES_PER_HUNK * FRAME_SIZE, 0);
+
+        // FLAC only does well on audio, so don't waste time trying it on anything else
+        let has_audio = self.has_audio;
+        writer.write_hunk(&self.data, |codec| {
+            matches!(codec, Codec::Flac) && !has_audio
+        })?;
+
+        self.data.clear();
+        self.has_audio = false;
+
+        Ok(())
+    }
+}
+
+/// Round a track's length up to the next multiple of `TRACK_PADDING` frames
+fn padded_frames(frames: usize) -> usize {
+    frames.div_ceil(TRACK_PADDING) * TRACK_PADDING
+}
+
+/// Describe a track in the format of a CHT2 metadata entry
+fn cht2_metadata(track: &Track, subchannels: bool) -> Result<Vec<u8>> {
+    let track_type = chd_track_type(track)?;
+    let subtype = if subchannels { "RW_RAW" } else { "NONE" };
+    let pregap = track.pregap_sectors();
+    let frames = pregap + track.num_sectors();
+
+    // The pregap is stored along with the rest of the track, which is marked by a V in front of
+    // its type
+    let (pregap_type, pregap_subtype) = if pregap > 0 {
+        (format!("V{track_type}"), subtype)
+    } else {
+        ("MODE1".to_owned(), "NONE")
+    };
+
+    let mut data = format!(
+        "TRACK:{} TYPE:{track_type} SUBTYPE:{subtype} FRAMES:{frames} PREGAP:{pregap} \
+         PGTYPE:{pregap_type} PGSUB:{pregap_subtype} POSTGAP:0",
+        track.number()
+    )
+    .into_bytes();
+
+    // MAME reads metadata as a C string
+    data.push(0);
+
+    Ok(data)
+}
+
+/// The TYPE of a track as it appears in CHT2 metadata
+fn chd_track_type(track: &Track) -> Result<&'static str> {
+    use TrackMode::*;
+
+    let data_size = track.sector_data_size();
+    let track_type = match (track.mode, data_size) {
+        (Audio, 2352) => "AUDIO",
+        (Mode1, 2048) => "MODE1",
+        (Mode1, 2352) => "MODE1_RAW",
+        (Mode2, 2336) => "MODE2",
+        (Mode2Form1, 2048) => "MODE2_FORM1",
+        (Mode2Form2, 2324) => "MODE2_FORM2",
+        (Mode2 | Mode2Form1 | Mode2Form2, 2352) => "MODE2_RAW",
+        (mode, data_size) => Err(Error::UnknownChdTrackSize(mode, data_size))?,
+    };
+
+    Ok(track_type)
+}
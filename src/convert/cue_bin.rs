@@ -1,13 +1,14 @@
 use crate::{
     error::{Error, Result},
-    loader::load_mds,
-    mds::{Mds, Track, TrackMode},
+    loader::{load_mds, LoadOptions},
+    mds::{Mds, Session, Track, TrackMode},
     timecode::Timecode,
     util::{reader_for_track, set_extension, writer_with_extension},
 };
 use std::{
-    io::{Read, Seek, SeekFrom, Write},
-    path::Path,
+    fs::File,
+    io::{BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
 };
 
 // Information about the .cue file format can be found at
@@ -16,71 +17,228 @@ use std::{
 // https://web.archive.org/web/20070614044112/http://www.goldenhawk.com/download/cdrwin.pdf. The
 // various cuesheet commands are in appendix A.
 
-pub fn convert<P: AsRef<Path>>(mds_file: P) -> Result<()> {
-    let mds = load_mds(&mds_file)?;
-    let bin_writer = writer_with_extension(&mds_file, "bin")?;
-    let cue_writer = writer_with_extension(&mds_file, "cue")?;
+/// The length of the pregap ahead of the first track on a disc
+const PREGAP_SECTORS: i32 = 150;
 
-    mds_to_cue(&mds, &mds_file, cue_writer)?;
-    mds_to_bin(&mds, &mds_file, bin_writer)
+/// Convert an .mds/.mdf image into a .cue file and the .bin files it refers to. Normally every
+/// track is written to a single .bin. When `split_tracks` is true, each track gets its own .bin
+/// named the way redump.org names them, like `Name (Track 01).bin`, and the cue sheet is written
+/// in the same style as redump's.
+pub fn convert<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    output: &Path,
+    split_tracks: bool,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks: Vec<&Track> = mds
+        .sessions()
+        .flat_map(|session| session.data_tracks())
+        .collect();
+
+    let bin_paths: Vec<PathBuf> = if split_tracks {
+        (1..=tracks.len())
+            .map(|number| track_bin_path(output, number, tracks.len()))
+            .collect()
+    } else {
+        vec![set_extension(output, "bin")]
+    };
+
+    let cue = cue_sheet(&mds, &bin_paths, split_tracks)?;
+    let mut cue_writer = writer_with_extension(output, "cue")?;
+    cue_writer.write_all(&cue).map_err(Error::Io)?;
+    cue_writer.flush().map_err(Error::Io)?;
+
+    if split_tracks {
+        for (track, bin_path) in tracks.iter().zip(&bin_paths) {
+            let file = File::create(bin_path).map_err(Error::Io)?;
+            let mut writer = BufWriter::new(file);
+            track_to_bin(track, &mds_file, options, &mut writer)?;
+            writer.flush().map_err(Error::Io)?;
+        }
+    } else {
+        let mut writer = writer_with_extension(output, "bin")?;
+        for track in tracks {
+            track_to_bin(track, &mds_file, options, &mut writer)?;
+        }
+        writer.flush().map_err(Error::Io)?;
+    }
+
+    Ok(())
 }
 
-/// Generate a .cue file with metadata contained in an .mds file and write it to `writer`
-fn mds_to_cue<P, W>(mds: &Mds, mds_path: P, mut writer: W) -> Result<()>
-where
-    P: AsRef<Path>,
-    W: Write,
-{
-    let bin_path = set_extension(mds_path, "bin");
-    let filename = bin_path.file_name().unwrap().to_str().unwrap();
-    writeln!(writer, "FILE \"{filename}\" BINARY").map_err(Error::Io)?;
+/// The contents of a .cue file for an image whose tracks are stored in `bin_paths`, which hold
+/// either every track or one track each, depending on `split_tracks`
+pub fn cue_sheet(mds: &Mds, bin_paths: &[PathBuf], split_tracks: bool) -> Result<Vec<u8>> {
+    let mut cue = Vec::new();
+    mds_to_cue(mds, bin_paths, split_tracks, &mut cue)?;
 
-    let session = mds.single_session()?;
-    let pregap_correction = session.start_time;
-    let tracks = session
-        .data_tracks()
-        .enumerate()
-        .map(|(i, sess)| (i + 1, sess)); // CUE tracks are 1-indexed
+    // Cue sheets come from Windows, and redump's are written with CRLF line endings, so every cue
+    // sheet is written with them
+    let cue = String::from_utf8_lossy(&cue)
+        .replace('\n', "\r\n")
+        .into_bytes();
 
-    for (i, track) in tracks {
-        let mode = cue_media_type(&track)?;
-        let addr = cue_address(&track, pregap_correction);
+    Ok(cue)
+}
 
-        writeln!(writer, "  TRACK {i} {mode}").map_err(Error::Io)?;
-        writeln!(writer, "    INDEX 01 {addr}").map_err(Error::Io)?;
+/// The path of the .bin holding track `number` of `num_tracks`, following redump's naming. Discs
+/// with only one track don't have a track number in the name.
+pub fn track_bin_path<P: AsRef<Path>>(mds_path: P, number: usize, num_tracks: usize) -> PathBuf {
+    if num_tracks == 1 {
+        return set_extension(mds_path, "bin");
+    }
+
+    let stem = mds_path.as_ref().file_stem().unwrap().to_string_lossy();
+    mds_path
+        .as_ref()
+        .with_file_name(format!("{stem} (Track {number:02}).bin"))
+}
+
+/// Generate a .cue file with metadata contained in an .mds file and write it to `writer`. When
+/// there is one file per track, each track starts a new FILE and its indexes count from the start
+/// of that file. Files ending in .wav or .flac are listed as WAVE files rather than BINARY.
+fn mds_to_cue<W: Write>(
+    mds: &Mds,
+    bin_paths: &[PathBuf],
+    split_tracks: bool,
+    mut writer: W,
+) -> Result<()> {
+    let mut bin_paths = bin_paths.iter();
+    let mut write_file_line = |writer: &mut W| -> Result<()> {
+        let bin_path = bin_paths.next().unwrap();
+        let filename = bin_path.file_name().unwrap().to_str().unwrap();
+        let file_type = match bin_path.extension().and_then(|ext| ext.to_str()) {
+            Some("wav" | "flac") => "WAVE",
+            _ => "BINARY",
+        };
+        writeln!(writer, "FILE \"{filename}\" {file_type}").map_err(Error::Io)
+    };
+
+    if !split_tracks {
+        write_file_line(&mut writer)?;
+    }
+
+    let multi_session = mds.sessions().count() > 1;
+    let mut prev_session: Option<&Session> = None;
+    let mut track_number = 1; // CUE tracks are 1-indexed
+
+    // The current position in the .bin. Only sectors which are stored in the .mdf end up in the
+    // .bin; any other gaps are described with PREGAP and POSTGAP instead.
+    let mut bin_position = Timecode::default();
+
+    for session in mds.sessions() {
+        match prev_session {
+            None if multi_session => {
+                writeln!(writer, "REM SESSION {:02}", session.number()).map_err(Error::Io)?;
+            }
+            None => {}
+            Some(prev) => {
+                let (lead_out, lead_in) = session_gap(prev, session);
+                writeln!(writer, "REM LEAD-OUT {lead_out}").map_err(Error::Io)?;
+                writeln!(writer, "REM SESSION {:02}", session.number()).map_err(Error::Io)?;
+                writeln!(writer, "REM LEAD-IN {lead_in}").map_err(Error::Io)?;
+            }
+        }
+
+        // The first track on a disc always has a two second pregap which isn't stored anywhere.
+        // The cue format implies it, so it shouldn't be written out as a PREGAP.
+        let mut prev_end = session.start_sector;
+        if prev_session.is_none() {
+            prev_end += PREGAP_SECTORS;
+        }
+
+        let mut tracks = session.data_tracks().peekable();
+        while let Some(track) = tracks.next() {
+            let mode = cue_media_type(track)?;
+            let stored_pregap = track.pregap_sectors() as i32;
+            let unstored_pregap = track.track_start_sector - stored_pregap - prev_end;
+            let track_end = track.track_start_sector + track.num_sectors() as i32;
+
+            if split_tracks {
+                write_file_line(&mut writer)?;
+                bin_position = Timecode::default();
+                writeln!(writer, "  TRACK {track_number:02} {mode}").map_err(Error::Io)?;
+            } else {
+                writeln!(writer, "  TRACK {track_number} {mode}").map_err(Error::Io)?;
+            }
+
+            if let Some(flags) = cue_flags(track) {
+                writeln!(writer, "    FLAGS {flags}").map_err(Error::Io)?;
+            }
+
+            if unstored_pregap > 0 {
+                let pregap = Timecode::from_sectors(unstored_pregap);
+                writeln!(writer, "    PREGAP {pregap}").map_err(Error::Io)?;
+            }
+
+            if stored_pregap > 0 {
+                writeln!(writer, "    INDEX 00 {bin_position}").map_err(Error::Io)?;
+                bin_position += Timecode::from_sectors(stored_pregap);
+            }
+
+            writeln!(writer, "    INDEX 01 {bin_position}").map_err(Error::Io)?;
+            bin_position += Timecode::from_sectors(track.num_sectors() as i32);
+
+            // Any space between the last track and the lead-out belongs to that track
+            let postgap = session.end_sector - track_end;
+            if tracks.peek().is_none() && postgap > 0 {
+                let postgap = Timecode::from_sectors(postgap);
+                writeln!(writer, "    POSTGAP {postgap}").map_err(Error::Io)?;
+            }
+
+            prev_end = track_end;
+            track_number += 1;
+        }
+
+        prev_session = Some(session);
     }
 
     Ok(())
 }
 
-/// Write all tracks of an .mdf to the given writer
-fn mds_to_bin<P, W>(mds: &Mds, mds_path: P, mut writer: W) -> Result<()>
+/// Write a track of an .mdf to the given writer, starting with its pregap
+pub fn track_to_bin<P, W>(
+    track: &Track,
+    mds_path: P,
+    options: &LoadOptions,
+    mut writer: W,
+) -> Result<()>
 where
     P: AsRef<Path>,
     W: Write,
 {
-    let session = mds.single_session()?;
-    let tracks = session.data_tracks();
-
-    for track in tracks {
-        let mut mdf_reader = reader_for_track(&mds_path, track)?;
-        mdf_reader
-            .seek(SeekFrom::Start(track.track_start_offset))
-            .map_err(Error::Io)?;
-
-        let sector_size = track.sector_size();
-        let data_size = track.sector_data_size();
-        let mut buf = vec![0; sector_size];
-
-        for _ in 0..track.num_sectors() {
-            mdf_reader.read_exact(&mut buf).map_err(Error::Io)?;
-            writer.write_all(&buf[0..data_size]).map_err(Error::Io)?;
-        }
+    let mut mdf_reader = reader_for_track(&mds_path, track, options)?;
+    mdf_reader
+        .seek(SeekFrom::Start(track.track_start_offset))
+        .map_err(Error::Io)?;
+
+    let sector_size = track.sector_size();
+    let data_size = track.sector_data_size();
+    let mut buf = vec![0; sector_size];
+
+    // Pregap sectors are stored ahead of the track's data, and are kept in the .bin so that
+    // INDEX 00 can point at them
+    for _ in 0..track.pregap_sectors() + track.num_sectors() {
+        mdf_reader.read_exact(&mut buf).map_err(Error::Io)?;
+        writer.write_all(&buf[0..data_size]).map_err(Error::Io)?;
     }
 
     Ok(())
 }
 
+/// The FLAGS of a track, for the control bits which are set in its TOC entry
+fn cue_flags(track: &Track) -> Option<String> {
+    let control = track.control();
+    let flags: Vec<&str> = [(0x2, "DCP"), (0x8, "4CH"), (0x1, "PRE")]
+        .into_iter()
+        .filter(|(bit, _)| control & bit != 0)
+        .map(|(_, flag)| flag)
+        .collect();
+
+    (!flags.is_empty()).then(|| flags.join(" "))
+}
+
 /// Determine the track type that should be printed in a .cue file. There are a few well-known
 /// ones, but not every possibility is accounted for. If there are additional possible combinations
 /// which have been seen in the wild, add them here.
@@ -101,14 +259,169 @@ fn cue_media_type(track: &Track) -> Result<&str> {
     }
 }
 
-/// Format a track's timecode as it should appear in an INDEX directive.
-/// Since .cue files do not include pregap information, .mds discs which do have pregap data (most
-/// of them) need to offset their track timecodes, because the first INDEX must have a timecode of
-/// 00:00:00. However, with a pregap an .mds file would typically start the first track at
-/// 00:02:00.
-fn cue_address(track: &Track, pregap_correction: Timecode) -> String {
-    let timecode = Timecode::from_track(track) + pregap_correction;
-    let (m, s, f) = timecode.msf();
+/// Sessions after the first are separated from the one before them by that session's lead-out
+/// and their own lead-in. Neither area is stored in the .mdf, so the gap between the sessions has
+/// to be split up the way a burner would write it: 6750 sectors of lead-out after the first
+/// session and 2250 after any later ones, with the remainder being lead-in.
+fn session_gap(prev: &Session, next: &Session) -> (Timecode, Timecode) {
+    let gap = next.start_sector - prev.end_sector;
+    let lead_out = if prev.number() == 1 { 6750 } else { 2250 }.min(gap);
+    let lead_in = gap - lead_out;
+
+    (
+        Timecode::from_sectors(lead_out),
+        Timecode::from_sectors(lead_in),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mds::multi_session_mds, testing::TestDisc};
+
+    /// The cue sheet for `mds` as one .bin, with LF line endings to keep the tests readable
+    fn cue_text(mds: &Mds) -> String {
+        let cue = cue_sheet(mds, &[PathBuf::from("disc.bin")], false).unwrap();
+        String::from_utf8(cue).unwrap().replace("\r\n", "\n")
+    }
+
+    /// A data track followed by an audio track with a stored pregap, both in disc.bin
+    fn two_track_disc(name: &str) -> TestDisc {
+        let cue = "FILE disc.bin BINARY\n\
+                   TRACK 01 MODE1/2352\n\
+                   INDEX 01 00:00:00\n\
+                   TRACK 02 AUDIO\n\
+                   INDEX 00 00:00:10\n\
+                   INDEX 01 00:00:30\n";
+        TestDisc::from_cue(name, cue, &[("disc.bin", &[0; 2352 * 70])])
+    }
+
+    #[test]
+    fn single_track_discs_dont_number_their_bin() {
+        let mds_path = Path::new("out/Game.mds");
+
+        assert_eq!(track_bin_path(mds_path, 1, 1), Path::new("out/Game.bin"));
+        assert_eq!(
+            track_bin_path(mds_path, 2, 12),
+            Path::new("out/Game (Track 02).bin")
+        );
+    }
+
+    #[test]
+    fn split_tracks_each_have_a_file_and_restart_their_indexes() {
+        let disc = two_track_disc("split-tracks");
+        let bin_paths: Vec<PathBuf> = (1..=2)
+            .map(|number| track_bin_path("Game.mds", number, 2))
+            .collect();
+        let cue = cue_sheet(disc.mds(), &bin_paths, true).unwrap();
+
+        assert_eq!(
+            String::from_utf8(cue).unwrap(),
+            "FILE \"Game (Track 01).bin\" BINARY\r\n\
+             \x20 TRACK 01 MODE1/2352\r\n\
+             \x20   INDEX 01 00:00:00\r\n\
+             FILE \"Game (Track 02).bin\" BINARY\r\n\
+             \x20 TRACK 02 AUDIO\r\n\
+             \x20   INDEX 00 00:00:00\r\n\
+             \x20   INDEX 01 00:00:20\r\n"
+        );
+    }
+
+    #[test]
+    fn every_cue_sheet_has_crlf_line_endings() {
+        let disc = two_track_disc("crlf");
+        let single = cue_sheet(disc.mds(), &[PathBuf::from("disc.bin")], false).unwrap();
+        let split = cue_sheet(
+            disc.mds(),
+            &[PathBuf::from("1.bin"), PathBuf::from("2.bin")],
+            true,
+        )
+        .unwrap();
 
-    format!("{m:02}:{s:02}:{f:02}")
+        for cue in [single, split] {
+            let cue = String::from_utf8(cue).unwrap();
+            assert!(cue.ends_with("\r\n"));
+            assert_eq!(cue.matches('\n').count(), cue.matches("\r\n").count());
+        }
+    }
+
+    #[test]
+    fn sessions_are_separated_by_their_lead_out_and_lead_in() {
+        let mds = Mds::from_bytes(&multi_session_mds()).unwrap();
+        let cue = cue_text(&mds);
+
+        let layout: Vec<&str> = cue
+            .lines()
+            .filter(|line| line.starts_with("REM") || line.contains("TRACK"))
+            .collect();
+        assert_eq!(
+            layout,
+            [
+                "REM SESSION 01",
+                "  TRACK 1 MODE1/2352",
+                "REM LEAD-OUT 01:30:00",
+                "REM SESSION 02",
+                "REM LEAD-IN 01:02:00",
+                "  TRACK 2 MODE1/2352",
+            ]
+        );
+    }
+
+    #[test]
+    fn short_gaps_between_sessions_are_all_lead_out() {
+        let mut bytes = multi_session_mds();
+
+        // Move the second session to start 2000 sectors after the first one ends
+        bytes[0x58 + 0x18..0x58 + 0x1C].copy_from_slice(&3000i32.to_le_bytes());
+        let mds = Mds::from_bytes(&bytes).unwrap();
+        let cue = cue_text(&mds);
+
+        assert!(cue.contains("REM LEAD-OUT 00:26:50\nREM SESSION 02\nREM LEAD-IN 00:00:00\n"));
+    }
+
+    #[test]
+    fn session_pregaps_are_written_as_pregap() {
+        let mds = Mds::from_bytes(&multi_session_mds()).unwrap();
+
+        assert_eq!(
+            cue_text(&mds),
+            "FILE \"disc.bin\" BINARY\n\
+             REM SESSION 01\n\
+             \x20 TRACK 1 MODE1/2352\n\
+             \x20   INDEX 01 00:00:00\n\
+             \x20   POSTGAP 00:02:00\n\
+             REM LEAD-OUT 01:30:00\n\
+             REM SESSION 02\n\
+             REM LEAD-IN 01:02:00\n\
+             \x20 TRACK 2 MODE1/2352\n\
+             \x20   PREGAP 00:02:00\n\
+             \x20   INDEX 01 00:11:25\n"
+        );
+    }
+
+    #[test]
+    fn stored_and_unstored_gaps_round_trip() {
+        let cue = "FILE \"disc.bin\" BINARY\n\
+                   \x20 TRACK 1 MODE1/2352\n\
+                   \x20   INDEX 01 00:00:00\n\
+                   \x20 TRACK 2 AUDIO\n\
+                   \x20   INDEX 00 00:00:10\n\
+                   \x20   INDEX 01 00:00:30\n\
+                   \x20 TRACK 3 AUDIO\n\
+                   \x20   PREGAP 00:01:00\n\
+                   \x20   INDEX 01 00:00:60\n\
+                   \x20   POSTGAP 00:00:05\n";
+        let disc = TestDisc::from_cue("gaps", cue, &[("disc.bin", &[0; 2352 * 70])]);
+
+        assert_eq!(cue_text(disc.mds()), cue);
+    }
+
+    #[test]
+    fn single_session_discs_have_no_session_comments() {
+        let cue = "FILE disc.bin BINARY\nTRACK 01 MODE1/2352\nINDEX 01 00:00:00\n";
+        let disc = TestDisc::from_cue("single-session", cue, &[("disc.bin", &[0; 2352 * 4])]);
+        let cue = cue_text(disc.mds());
+
+        assert!(!cue.contains("REM"));
+    }
 }
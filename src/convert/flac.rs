@@ -0,0 +1,219 @@
+use super::cue_bin::{cue_sheet, track_bin_path, track_to_bin};
+use crate::{
+    bits::BitWriter,
+    error::{Error, Result},
+    flac::FlacWriter,
+    loader::{load_mds, LoadOptions},
+    mds::{Track, TrackMode},
+    util::{set_extension, writer_with_extension},
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+/// The type of a CUESHEET metadata block
+const CUESHEET: u8 = 5;
+
+/// The number of samples in each sector of CD audio
+const SAMPLES_PER_SECTOR: u64 = 588;
+
+/// The track number FLAC uses for the lead-out of a CD
+const LEAD_OUT_TRACK: u8 = 170;
+
+/// Convert an audio CD into FLAC. Normally the whole disc is written to one .flac, with a
+/// CUESHEET block holding the position of every track and index so that the original bin/cue can
+/// be rebuilt from it. When `split_tracks` is true, each track is written to its own .flac
+/// instead, and any data tracks to their own .bin. Either way a .cue file listing the new files
+/// is written too, and every .flac is decoded afterwards to check it holds the original audio.
+pub fn convert<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    output: &Path,
+    split_tracks: bool,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks: Vec<&Track> = mds
+        .sessions()
+        .flat_map(|session| session.data_tracks())
+        .collect();
+
+    let paths: Vec<PathBuf> = if split_tracks {
+        tracks
+            .iter()
+            .enumerate()
+            .map(|(i, track)| {
+                let bin_path = track_bin_path(output, i + 1, tracks.len());
+                match track.mode {
+                    TrackMode::Audio => bin_path.with_extension("flac"),
+                    _ => bin_path,
+                }
+            })
+            .collect()
+    } else {
+        if let Some(track) = tracks
+            .iter()
+            .find(|track| !matches!(track.mode, TrackMode::Audio))
+        {
+            Err(Error::NotAudio(track.number()))?;
+        }
+
+        vec![set_extension(output, "flac")]
+    };
+
+    let cue = cue_sheet(&mds, &paths, split_tracks)?;
+    let mut cue_writer = writer_with_extension(output, "cue")?;
+    cue_writer.write_all(&cue).map_err(Error::Io)?;
+    cue_writer.flush().map_err(Error::Io)?;
+
+    if split_tracks {
+        for (track, path) in tracks.iter().zip(&paths) {
+            if matches!(track.mode, TrackMode::Audio) {
+                write_flac(&[track], &mds_file, options, path, &[])?;
+                verify_flac(&[track], &mds_file, options, path)?;
+            } else {
+                let file = File::create(path).map_err(Error::Io)?;
+                let mut writer = BufWriter::new(file);
+                track_to_bin(track, &mds_file, options, &mut writer)?;
+                writer.flush().map_err(Error::Io)?;
+            }
+        }
+    } else {
+        let cuesheet = (CUESHEET, cuesheet_block(&tracks));
+        write_flac(&tracks, &mds_file, options, &paths[0], &[cuesheet])?;
+        verify_flac(&tracks, &mds_file, options, &paths[0])?;
+    }
+
+    Ok(())
+}
+
+/// Encode `tracks` one after another into a .flac at `path`, each starting with its pregap
+fn write_flac<P: AsRef<Path>>(
+    tracks: &[&Track],
+    mds_file: P,
+    options: &LoadOptions,
+    path: &Path,
+    metadata: &[(u8, Vec<u8>)],
+) -> Result<()> {
+    let file = File::create(path).map_err(Error::Io)?;
+    let mut writer = FlacWriter::new(BufWriter::new(file), metadata).map_err(Error::Io)?;
+
+    for track in tracks {
+        track_to_bin(track, &mds_file, options, &mut writer)?;
+    }
+
+    writer.finish().map_err(Error::Io)?;
+    Ok(())
+}
+
+/// Decode the .flac at `path`, and check that it holds exactly the audio of `tracks`
+fn verify_flac<P: AsRef<Path>>(
+    tracks: &[&Track],
+    mds_file: P,
+    options: &LoadOptions,
+    path: &Path,
+) -> Result<()> {
+    let mut reader = claxon::FlacReader::open(path).map_err(|_| Error::FlacVerification)?;
+    let mut comparer = Comparer {
+        samples: reader.samples(),
+        matches: true,
+    };
+
+    for track in tracks {
+        track_to_bin(track, &mds_file, options, &mut comparer)?;
+    }
+
+    if !comparer.matches || comparer.samples.next().is_some() {
+        Err(Error::FlacVerification)?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the samples written to it are the same as those decoded from a FLAC file
+struct Comparer<I> {
+    samples: I,
+    matches: bool,
+}
+
+impl<I, E> Write for Comparer<I>
+where
+    I: Iterator<Item = std::result::Result<i32, E>>,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for sample in buf.chunks_exact(2) {
+            let expected = i16::from_le_bytes([sample[0], sample[1]]);
+            let decoded = self.samples.next().and_then(|sample| sample.ok());
+            self.matches &= decoded == Some(expected.into());
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Build a CUESHEET metadata block describing where each track and index starts in a .flac
+/// holding `tracks` one after another, starting with each track's pregap. See
+/// https://xiph.org/flac/format.html#metadata_block_cuesheet.
+fn cuesheet_block(tracks: &[&Track]) -> Vec<u8> {
+    let mut w = BitWriter::default();
+    write_bytes(&mut w, &[0; 128]); // media catalog number
+    write_u64(&mut w, 2 * 44_100); // lead-in samples
+    w.write(1, 1); // this is a CD
+    w.write(0, 7);
+    write_bytes(&mut w, &[0; 258]);
+    w.write(tracks.len() as u32 + 1, 8);
+
+    let mut offset = 0;
+    for (i, track) in tracks.iter().enumerate() {
+        let pregap = track.pregap_sectors() as u64 * SAMPLES_PER_SECTOR;
+        let mut indexes = Vec::new();
+        if pregap > 0 {
+            indexes.push((0, 0));
+        }
+        indexes.push((1, pregap));
+
+        write_u64(&mut w, offset);
+        w.write(i as u32 + 1, 8);
+        write_bytes(&mut w, &[0; 12]); // ISRC
+        w.write(0, 1); // audio
+        w.write(u32::from(track.control() & 0x1), 1); // pre-emphasis
+        w.write(0, 6);
+        write_bytes(&mut w, &[0; 13]);
+        w.write(indexes.len() as u32, 8);
+
+        for (number, index_offset) in indexes {
+            write_u64(&mut w, index_offset);
+            w.write(number, 8);
+            write_bytes(&mut w, &[0; 3]);
+        }
+
+        let num_sectors = track.pregap_sectors() + track.num_sectors();
+        offset += num_sectors as u64 * SAMPLES_PER_SECTOR;
+    }
+
+    // The lead-out comes right after the last track
+    write_u64(&mut w, offset);
+    w.write(LEAD_OUT_TRACK.into(), 8);
+    write_bytes(&mut w, &[0; 12]);
+    w.write(0, 8);
+    write_bytes(&mut w, &[0; 13]);
+    w.write(0, 8);
+
+    w.finish()
+}
+
+fn write_u64(w: &mut BitWriter, value: u64) {
+    w.write((value >> 32) as u32, 32);
+    w.write(value as u32, 32);
+}
+
+fn write_bytes(w: &mut BitWriter, bytes: &[u8]) {
+    for &byte in bytes {
+        w.write(byte.into(), 8);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::{
+    cue::{CueSheet, CueTrack},
+    error::{Error, Result},
+    locate::locate_data_file,
+    mds::{toc_msf, Mds, MediaType, Session, Track, TrackMode},
+};
+use std::{fs::metadata, path::Path};
+
+/// The length of the pregap ahead of the first track on a disc, which isn't stored in the file
+const PREGAP_SECTORS: i32 = 150;
+
+/// The length of a session's lead-in, when the cue sheet doesn't say
+const LEAD_IN_SECTORS: i32 = 4500;
+
+/// Build an .mds describing the tracks of a cue sheet, with each track pointing at its data in the
+/// files named by the cue sheet. `cue_path` is used to find those files, since their names are
+/// relative to it.
+///
+/// Gaps between sessions are taken from `REM LEAD-OUT` and `REM LEAD-IN` comments when they are
+/// present, or otherwise are laid out the way a burner would write them.
+pub fn mds_from_cue<P: AsRef<Path>>(cue: &CueSheet, cue_path: P) -> Result<Mds> {
+    let tracks: Vec<&CueTrack> = cue.sessions.iter().flat_map(|s| &s.tracks).collect();
+    let extents = track_extents(&tracks, cue_path)?;
+    let mut extents = extents.into_iter();
+
+    let mut sessions = Vec::with_capacity(cue.sessions.len());
+    let mut position = 0;
+    let mut prev_end: Option<i32> = None;
+
+    for cue_session in &cue.sessions {
+        let start_sector = match prev_end {
+            None => -PREGAP_SECTORS,
+            Some(prev_end) => {
+                let default_lead_out = if sessions.len() == 1 { 6750 } else { 2250 };
+                let lead_out = cue_session
+                    .lead_out
+                    .map_or(default_lead_out, |t| t.sectors());
+                let lead_in = cue_session.lead_in.map_or(LEAD_IN_SECTORS, |t| t.sectors());
+
+                position = prev_end + lead_out + lead_in;
+                position
+            }
+        };
+
+        let mut session_tracks = Vec::with_capacity(cue_session.tracks.len());
+        for cue_track in &cue_session.tracks {
+            let (file_offset, num_sectors) = extents.next().unwrap();
+            let track_start_sector =
+                position + cue_track.pregap.sectors() + cue_track.stored_pregap();
+
+            let track = mds_track(cue_track, track_start_sector, num_sectors, file_offset);
+            session_tracks.push(track);
+
+            position = track_start_sector + num_sectors as i32 + cue_track.postgap.sectors();
+        }
+
+        let first = cue_session.tracks.first().ok_or(Error::NoDataTracks)?;
+        let last = cue_session.tracks.last().unwrap();
+        let (lead_out_m, lead_out_s, lead_out_f) = toc_msf(position);
+        let disc_type = if has_xa_tracks(&cue_session.tracks) {
+            0x20
+        } else {
+            0x00
+        };
+
+        let mut toc = vec![
+            Track::lead_in(0xA0, first.control(), first.number, disc_type, 0),
+            Track::lead_in(0xA1, last.control(), last.number, 0, 0),
+            Track::lead_in(0xA2, last.control(), lead_out_m, lead_out_s, lead_out_f),
+        ];
+        toc.extend(session_tracks);
+
+        let number = cue_session.number as u16;
+        sessions.push(Session::new(number, start_sector, position, toc));
+        prev_end = Some(position);
+    }
+
+    Ok(Mds::new(MediaType::CdRom, sessions))
+}
+
+/// Create the TOC entry for a track read from a cue sheet. The track's data starts
+/// `track_start_offset` bytes into the file named in the cue sheet, beginning with any pregap
+/// stored there.
+fn mds_track(
+    cue_track: &CueTrack,
+    track_start_sector: i32,
+    num_sectors: u32,
+    track_start_offset: u64,
+) -> Track {
+    let mut track = Track::new(
+        cue_track.number,
+        cue_track.mode,
+        cue_track.control(),
+        cue_track.sector_size as u16,
+        track_start_sector,
+    );
+    track.set_index(cue_track.stored_pregap() as u32, num_sectors);
+    track.set_data_file(&cue_track.file, track_start_offset);
+
+    track
+}
+
+/// Find where each track starts in its file, in bytes, and how many sectors follow INDEX 01.
+/// Tracks end where the next track in the same file starts, or at the end of the file.
+/// `parse_cue` rejects positions which go backwards, so the gaps between them are never negative.
+fn track_extents<P: AsRef<Path>>(tracks: &[&CueTrack], cue_path: P) -> Result<Vec<(u64, u32)>> {
+    let mut extents = Vec::with_capacity(tracks.len());
+    let mut file_offset = 0;
+
+    for (i, track) in tracks.iter().enumerate() {
+        if i == 0 || tracks[i - 1].file != track.file {
+            file_offset = 0;
+        } else {
+            // Positions in a cue sheet are counted in sectors, which may be a different size in
+            // each track
+            let prev = tracks[i - 1];
+            let prev_sectors = track.file_start().sectors() - prev.file_start().sectors();
+            file_offset += (prev_sectors as usize * prev.sector_size) as u64;
+        }
+
+        let track_start = file_offset + (track.stored_pregap() as usize * track.sector_size) as u64;
+        let track_end = match tracks.get(i + 1) {
+            Some(next) if next.file == track.file => {
+                let sectors = next.file_start().sectors() - track.file_start().sectors();
+                file_offset + (sectors as usize * track.sector_size) as u64
+            }
+            _ => {
+                let path = locate_data_file(&cue_path, &track.file, &[])?;
+                metadata(path).map_err(Error::Io)?.len()
+            }
+        };
+
+        let num_sectors = track_end.saturating_sub(track_start) / track.sector_size as u64;
+        extents.push((file_offset, num_sectors as u32));
+    }
+
+    Ok(extents)
+}
+
+/// Whether any of `tracks` are mode 2, which makes the session a CD-ROM XA one
+fn has_xa_tracks(tracks: &[CueTrack]) -> bool {
+    use TrackMode::*;
+
+    tracks
+        .iter()
+        .any(|track| matches!(track.mode, Mode2 | Mode2Form1 | Mode2Form2))
+}
@@ -1,30 +1,66 @@
 use crate::{
     error::{Error, Result},
-    loader::load_mds,
+    loader::{load_mds, LoadOptions},
     mds::Track,
+    sector::{user_data_offset, USER_DATA_SIZE},
     util::{reader_for_track, writer_with_extension},
 };
 use std::{
-    io::{Read, Write},
+    io::{Read, Seek, SeekFrom, Write},
     path::Path,
 };
 
 /// Convert a .mdf file (described by a .mds file) into a .iso file. This is not always possible,
 /// because an ISO represents the bytes of one track. If the .mds refers to a multi-track disc,
 /// writing to an ISO doesn't make sense. Try using BIN/CUE in that case.
-pub fn convert<P: AsRef<Path>>(mds_file: P) -> Result<()> {
-    let mds = load_mds(&mds_file)?;
-    let track = mds.single_track()?;
-    let writer = writer_with_extension(&mds_file, "iso")?;
+///
+/// Multi-session discs hold a separate filesystem in each session. `session` chooses which one to
+/// extract; when it is `None` the last session is used, as that is the one a drive would mount.
+///
+/// An ISO normally holds only the 2048 bytes of user data from each sector. When `raw` is true,
+/// every byte stored for each sector is written instead, which for raw images includes the
+/// sector's header and error correction data.
+pub fn convert<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    output: &Path,
+    session: Option<usize>,
+    raw: bool,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let track = mds.session(session)?.single_track()?;
+    let writer = writer_with_extension(output, "iso")?;
 
-    track_to_iso(&track, &mds_file, writer)
+    track_to_iso(track, &mds_file, options, raw, writer)
 }
 
-fn track_to_iso<P: AsRef<Path>, W: Write>(track: &Track, mds_path: P, mut writer: W) -> Result<()> {
+fn track_to_iso<P, W>(
+    track: &Track,
+    mds_path: P,
+    options: &LoadOptions,
+    raw: bool,
+    mut writer: W,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
     let sector_size = track.sector_size();
     let data_size = track.sector_data_size();
     let num_sectors = track.num_sectors();
-    let mut reader = reader_for_track(&mds_path, track)?;
+
+    let user_data = if raw {
+        0..data_size
+    } else {
+        let offset = user_data_offset(track.mode, data_size)
+            .ok_or(Error::NoUserData(track.mode, data_size))?;
+        offset..offset + USER_DATA_SIZE
+    };
+
+    let mut reader = reader_for_track(&mds_path, track, options)?;
+    reader
+        .seek(SeekFrom::Start(track.index1_offset()))
+        .map_err(Error::Io)?;
 
     let mut buf = vec![0; sector_size];
     for _ in 0..num_sectors {
@@ -33,8 +69,51 @@ fn track_to_iso<P: AsRef<Path>, W: Write>(track: &Track, mds_path: P, mut writer
         // In order to convert the .mdf, take only the main track's data from each sector. Each
         // sector may also contain subchannel data which is stored at the end of the sector. ISO
         // files don't store subchannel data, so just discard this.
-        writer.write_all(&buf[0..data_size]).map_err(Error::Io)?;
+        writer
+            .write_all(&buf[user_data.clone()])
+            .map_err(Error::Io)?;
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+    use std::fs;
+
+    fn iso(disc: &TestDisc, raw: bool) -> Vec<u8> {
+        let track = disc
+            .mds()
+            .sessions()
+            .next()
+            .unwrap()
+            .data_tracks()
+            .next()
+            .unwrap();
+        let mut iso = Vec::new();
+        track_to_iso(track, disc.mds_path(), disc.options(), raw, &mut iso).unwrap();
+
+        iso
+    }
+
+    #[test]
+    fn isos_hold_the_user_data_of_raw_sectors() {
+        let data: Vec<u8> = (0..USER_DATA_SIZE * 3).map(|i| (i % 253) as u8).collect();
+        let disc = TestDisc::new("iso-user-data", &data, true);
+
+        assert_eq!(iso(&disc, false), data);
+    }
+
+    #[test]
+    fn raw_isos_hold_whole_sectors() {
+        let data: Vec<u8> = (0..USER_DATA_SIZE * 3).map(|i| (i % 253) as u8).collect();
+        let disc = TestDisc::new("iso-raw", &data, true);
+        let bin = fs::read(disc.dir().join("disc.bin")).unwrap();
+
+        let iso = iso(&disc, true);
+        assert_eq!(iso.len(), 0x930 * 3);
+        assert_eq!(iso, bin);
+    }
+}
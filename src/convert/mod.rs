@@ -1,5 +1,16 @@
+mod ccd;
+mod cdg;
+mod chd;
 mod cue_bin;
+mod flac;
+mod from_cue;
 mod iso;
+mod wav;
 
-pub use cue_bin::convert as convert_to_cue_bin;
+pub use ccd::convert as convert_to_ccd;
+pub use chd::convert as convert_to_chd;
+pub use cue_bin::{convert as convert_to_cue_bin, cue_sheet, track_bin_path, track_to_bin};
+pub use flac::convert as convert_to_flac;
+pub use from_cue::mds_from_cue;
 pub use iso::convert as convert_to_iso;
+pub use wav::{convert as convert_to_wav, track_to_wav};
@@ -0,0 +1,196 @@
+use super::{
+    cdg::track_to_cdg,
+    cue_bin::{cue_sheet, track_bin_path, track_to_bin},
+};
+use crate::{
+    error::{Error, Result},
+    loader::{load_mds, LoadOptions},
+    mds::{Track, TrackMode},
+    util::writer_with_extension,
+};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+// CD audio is 44.1 kHz 16-bit stereo PCM, stored little endian, which is exactly what a WAVE file
+// holds after its header. See http://soundfile.sapp.org/doc/WaveFormat/ for the layout.
+
+const SAMPLE_RATE: u32 = 44100;
+const NUM_CHANNELS: u16 = 2;
+const BITS_PER_SAMPLE: u16 = 16;
+
+/// Convert an image into a .cue file with each audio track in its own .wav file. Data tracks are
+/// written to .bin files, the same as they would be with `--split-tracks`. When `cdg` is true, the
+/// CD+G graphics of each audio track are written to a .cdg file next to its .wav.
+pub fn convert<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    output: &Path,
+    cdg: bool,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks: Vec<&Track> = mds
+        .sessions()
+        .flat_map(|session| session.data_tracks())
+        .collect();
+
+    let missing_subchannels = tracks
+        .iter()
+        .any(|track| matches!(track.mode, TrackMode::Audio) && track.sector_subchannel_size() == 0);
+    if cdg && missing_subchannels {
+        Err(Error::NoSubchannels)?;
+    }
+
+    let paths: Vec<PathBuf> = tracks
+        .iter()
+        .enumerate()
+        .map(|(i, track)| {
+            let bin_path = track_bin_path(output, i + 1, tracks.len());
+            match track.mode {
+                TrackMode::Audio => bin_path.with_extension("wav"),
+                _ => bin_path,
+            }
+        })
+        .collect();
+
+    let cue = cue_sheet(&mds, &paths, true)?;
+    let mut cue_writer = writer_with_extension(output, "cue")?;
+    cue_writer.write_all(&cue).map_err(Error::Io)?;
+    cue_writer.flush().map_err(Error::Io)?;
+
+    for (track, path) in tracks.iter().zip(&paths) {
+        let file = File::create(path).map_err(Error::Io)?;
+        let mut writer = BufWriter::new(file);
+
+        if matches!(track.mode, TrackMode::Audio) {
+            track_to_wav(track, &mds_file, options, &mut writer)?;
+        } else {
+            track_to_bin(track, &mds_file, options, &mut writer)?;
+        }
+
+        writer.flush().map_err(Error::Io)?;
+
+        if cdg && matches!(track.mode, TrackMode::Audio) {
+            let file = File::create(path.with_extension("cdg")).map_err(Error::Io)?;
+            let mut writer = BufWriter::new(file);
+            track_to_cdg(track, &mds_file, options, &mut writer)?;
+            writer.flush().map_err(Error::Io)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write an audio track to `writer` as a WAVE file, starting with its pregap
+pub fn track_to_wav<P, W>(
+    track: &Track,
+    mds_path: P,
+    options: &LoadOptions,
+    mut writer: W,
+) -> Result<()>
+where
+    P: AsRef<Path>,
+    W: Write,
+{
+    if !matches!(track.mode, TrackMode::Audio) {
+        Err(Error::NotAudio(track.number()))?;
+    }
+
+    let num_sectors = track.pregap_sectors() + track.num_sectors();
+    let data_len = (num_sectors * track.sector_data_size()) as u32;
+    write_wav_header(&mut writer, data_len).map_err(Error::Io)?;
+
+    track_to_bin(track, mds_path, options, writer)
+}
+
+/// Write the header of a WAVE file holding `data_len` bytes of CD audio
+fn write_wav_header<W: Write>(writer: &mut W, data_len: u32) -> std::io::Result<()> {
+    let block_align = NUM_CHANNELS * BITS_PER_SAMPLE / 8;
+    let byte_rate = SAMPLE_RATE * block_align as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&NUM_CHANNELS.to_le_bytes())?;
+    writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+
+    fn u16_at(wav: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes(wav[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn u32_at(wav: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(wav[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn wavs_have_a_cd_audio_header_before_the_samples() {
+        let cue = "FILE disc.bin BINARY\nTRACK 01 AUDIO\nINDEX 01 00:00:00\n";
+        let samples: Vec<u8> = (0..2352 * 4).map(|i| (i % 251) as u8).collect();
+        let disc = TestDisc::from_cue("wav", cue, &[("disc.bin", &samples)]);
+        let track = disc
+            .mds()
+            .sessions()
+            .next()
+            .unwrap()
+            .data_tracks()
+            .next()
+            .unwrap();
+
+        let mut wav = Vec::new();
+        track_to_wav(track, disc.mds_path(), disc.options(), &mut wav).unwrap();
+        let data_len = samples.len() as u32;
+
+        assert_eq!(&wav[0..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4), 36 + data_len);
+        assert_eq!(&wav[8..12], b"WAVE");
+
+        assert_eq!(&wav[12..16], b"fmt ");
+        assert_eq!(u32_at(&wav, 16), 16);
+        assert_eq!(u16_at(&wav, 20), 1);
+        assert_eq!(u16_at(&wav, 22), 2);
+        assert_eq!(u32_at(&wav, 24), 44100);
+        assert_eq!(u32_at(&wav, 28), 44100 * 4);
+        assert_eq!(u16_at(&wav, 32), 4);
+        assert_eq!(u16_at(&wav, 34), 16);
+
+        assert_eq!(&wav[36..40], b"data");
+        assert_eq!(u32_at(&wav, 40), data_len);
+        assert_eq!(&wav[44..], samples);
+    }
+
+    #[test]
+    fn data_tracks_are_not_audio() {
+        let disc = TestDisc::new("wav-data", &[0; 2048], false);
+        let track = disc
+            .mds()
+            .sessions()
+            .next()
+            .unwrap()
+            .data_tracks()
+            .next()
+            .unwrap();
+
+        assert!(matches!(
+            track_to_wav(track, disc.mds_path(), disc.options(), Vec::new()),
+            Err(Error::NotAudio(1))
+        ));
+    }
+}
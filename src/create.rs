@@ -0,0 +1,46 @@
+use crate::{
+    convert::mds_from_cue,
+    cue::load_cue,
+    error::{Error, Result},
+    loader::LoadOptions,
+    locate::locate_track_files,
+    util::{reader_for_track, writer_with_extension},
+};
+use std::{
+    io::{copy, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Create .mds and .mdf files from a cue sheet and the .bin files it refers to. The new files are
+/// written next to the cue sheet, with the same name.
+pub fn create<P: AsRef<Path>>(cue_file: P) -> Result<()> {
+    let cue = load_cue(&cue_file)?;
+    let mds = mds_from_cue(&cue, &cue_file)?;
+    let mut mdf_writer = writer_with_extension(&cue_file, "mdf")?;
+
+    // Until it is repointed at the .mdf, each track refers to its data in the .bin files
+    for track in mds.sessions().flat_map(|session| session.data_tracks()) {
+        let options = LoadOptions::default();
+        let mut bin_reader = reader_for_track(&cue_file, track, &options)?;
+        bin_reader
+            .seek(SeekFrom::Start(track.track_start_offset))
+            .map_err(Error::Io)?;
+
+        let num_sectors = track.pregap_sectors() + track.num_sectors();
+        let len = (num_sectors * track.sector_size()) as u64;
+        let copied = copy(&mut bin_reader.take(len), &mut mdf_writer).map_err(Error::Io)?;
+
+        // A .bin which ends early would leave the .mds pointing past the end of the .mdf
+        if copied < len {
+            let path = locate_track_files(&cue_file, track, &options)?.remove(0);
+            Err(Error::TruncatedFile(path, len, copied))?;
+        }
+    }
+
+    mdf_writer.flush().map_err(Error::Io)?;
+
+    let mds = mds.into_single_file("*.mdf");
+    let mut mds_writer = writer_with_extension(&cue_file, "mds")?;
+    mds_writer.write_all(&mds.to_bytes()).map_err(Error::Io)?;
+    mds_writer.flush().map_err(Error::Io)
+}
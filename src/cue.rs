@@ -0,0 +1,448 @@
+use crate::{
+    error::{Error, Result},
+    mds::TrackMode,
+    timecode::Timecode,
+};
+use std::{fs::read_to_string, path::Path};
+
+// A parser for the subset of the cue sheet format which can be represented in an .mds file. See
+// convert/cue_bin.rs for links to documentation of the format. Only BINARY files are supported,
+// and only INDEX 00 and INDEX 01 are kept since an .mds has nowhere to store any others.
+
+#[derive(Debug)]
+pub struct CueSheet {
+    pub sessions: Vec<CueSession>,
+}
+
+#[derive(Debug)]
+pub struct CueSession {
+    pub number: usize,
+
+    /// The length of the previous session's lead-out, from a `REM LEAD-OUT` comment
+    pub lead_out: Option<Timecode>,
+
+    /// The length of this session's lead-in, from a `REM LEAD-IN` comment
+    pub lead_in: Option<Timecode>,
+
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug)]
+pub struct CueTrack {
+    pub number: u8,
+
+    /// The name of the file holding this track's data, as written in the cue sheet
+    pub file: String,
+
+    pub mode: TrackMode,
+
+    /// The number of bytes each sector of this track takes up in its file
+    pub sector_size: usize,
+
+    /// The control bits set by FLAGS, not including the data bit
+    pub flags: u8,
+
+    /// Silence ahead of the track which isn't stored in its file
+    pub pregap: Timecode,
+
+    /// Silence after the track which isn't stored in its file
+    pub postgap: Timecode,
+
+    /// The position of INDEX 00 in the file, if the track has one
+    pub index0: Option<Timecode>,
+
+    /// The position of INDEX 01 in the file
+    pub index1: Timecode,
+}
+
+impl CueTrack {
+    /// The position in the file of the first sector stored for this track
+    pub fn file_start(&self) -> Timecode {
+        self.index0.unwrap_or(self.index1)
+    }
+
+    /// The control bits of this track's TOC entry
+    pub fn control(&self) -> u8 {
+        let data_bit = match self.mode {
+            TrackMode::Audio => 0,
+            _ => 0x04,
+        };
+
+        data_bit | self.flags
+    }
+
+    /// The number of pregap sectors stored in the file ahead of INDEX 01
+    pub fn stored_pregap(&self) -> i32 {
+        self.index1.sectors() - self.file_start().sectors()
+    }
+}
+
+/// Read and parse the cue sheet at `path`
+pub fn load_cue<P: AsRef<Path>>(path: P) -> Result<CueSheet> {
+    let text = read_to_string(path).map_err(Error::Io)?;
+    parse_cue(&text)
+}
+
+/// A track which has been started but not finished, since its indexes come on later lines
+struct PartialTrack {
+    number: u8,
+    file: String,
+    mode: TrackMode,
+    sector_size: usize,
+    flags: u8,
+    pregap: Timecode,
+    postgap: Timecode,
+    index0: Option<Timecode>,
+    index1: Option<Timecode>,
+}
+
+impl PartialTrack {
+    fn finish(self, line: usize) -> Result<CueTrack> {
+        Ok(CueTrack {
+            number: self.number,
+            file: self.file,
+            mode: self.mode,
+            sector_size: self.sector_size,
+            flags: self.flags,
+            pregap: self.pregap,
+            postgap: self.postgap,
+            index0: self.index0,
+            index1: self.index1.ok_or(Error::CueParse(line))?,
+        })
+    }
+}
+
+pub fn parse_cue(text: &str) -> Result<CueSheet> {
+    let mut sessions = vec![CueSession {
+        number: 1,
+        lead_out: None,
+        lead_in: None,
+        tracks: Vec::new(),
+    }];
+    let mut file: Option<String> = None;
+    let mut track: Option<PartialTrack> = None;
+    let mut lead_out = None;
+    let mut file_position: Option<Timecode> = None;
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let syntax_error = || Error::CueParse(line_number);
+        let tokens = tokenize(line);
+        let words: Vec<&str> = tokens.iter().map(String::as_str).collect();
+
+        match words.as_slice() {
+            ["FILE", name, file_type] => {
+                if *file_type != "BINARY" {
+                    Err(Error::UnsupportedCueFileType(file_type.to_string()))?;
+                }
+
+                // Tracks are measured by how far apart their indexes are, so positions have to keep
+                // counting up through a file even if it's named again
+                if file.as_deref() != Some(*name) {
+                    file_position = None;
+                }
+                file = Some(name.to_string());
+            }
+            ["TRACK", number, mode] => {
+                let session = sessions.last_mut().unwrap();
+                if let Some(track) = track.take() {
+                    session.tracks.push(track.finish(line_number)?);
+                }
+
+                let (mode, sector_size) = track_mode(mode).ok_or_else(syntax_error)?;
+                track = Some(PartialTrack {
+                    number: number.parse().map_err(|_| syntax_error())?,
+                    file: file.clone().ok_or_else(syntax_error)?,
+                    mode,
+                    sector_size,
+                    flags: 0,
+                    pregap: Timecode::default(),
+                    postgap: Timecode::default(),
+                    index0: None,
+                    index1: None,
+                });
+            }
+            ["INDEX", number, time] => {
+                let track = track.as_mut().ok_or_else(syntax_error)?;
+                let time = cue_time(time).ok_or_else(syntax_error)?;
+                if file_position.is_some_and(|prev| time.sectors() < prev.sectors()) {
+                    Err(syntax_error())?;
+                }
+                file_position = Some(time);
+
+                match number.parse::<u8>().map_err(|_| syntax_error())? {
+                    0 if track.index1.is_some() => Err(syntax_error())?,
+                    0 => track.index0 = Some(time),
+                    1 => track.index1 = Some(time),
+                    _ => {}
+                }
+            }
+            ["PREGAP", time] => {
+                let track = track.as_mut().ok_or_else(syntax_error)?;
+                track.pregap = cue_time(time).ok_or_else(syntax_error)?;
+            }
+            ["POSTGAP", time] => {
+                let track = track.as_mut().ok_or_else(syntax_error)?;
+                track.postgap = cue_time(time).ok_or_else(syntax_error)?;
+            }
+            ["FLAGS", flags @ ..] => {
+                let track = track.as_mut().ok_or_else(syntax_error)?;
+                for flag in flags {
+                    track.flags |= match *flag {
+                        "PRE" => 0x1,
+                        "DCP" => 0x2,
+                        "4CH" => 0x8,
+                        _ => 0,
+                    };
+                }
+            }
+            ["REM", "LEAD-OUT", time] => {
+                lead_out = Some(cue_time(time).ok_or_else(syntax_error)?);
+            }
+            ["REM", "LEAD-IN", time] => {
+                let session = sessions.last_mut().unwrap();
+                session.lead_in = Some(cue_time(time).ok_or_else(syntax_error)?);
+            }
+            ["REM", "SESSION", number] => {
+                let number = number.parse().map_err(|_| syntax_error())?;
+                let session = sessions.last_mut().unwrap();
+
+                if let Some(track) = track.take() {
+                    session.tracks.push(track.finish(line_number)?);
+                }
+
+                // The first session is there from the start, so that sheets without any sessions
+                // marked still have one
+                if session.tracks.is_empty() {
+                    session.number = number;
+                } else {
+                    sessions.push(CueSession {
+                        number,
+                        lead_out: lead_out.take(),
+                        lead_in: None,
+                        tracks: Vec::new(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let num_lines = text.lines().count();
+    if let Some(track) = track.take() {
+        let session = sessions.last_mut().unwrap();
+        session.tracks.push(track.finish(num_lines)?);
+    }
+
+    if sessions.iter().all(|session| session.tracks.is_empty()) {
+        Err(Error::NoDataTracks)?;
+    }
+
+    Ok(CueSheet { sessions })
+}
+
+/// Split a line into words, keeping quoted strings together
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            tokens.push(chars.by_ref().take_while(|&c| c != '"').collect());
+        } else {
+            let mut token = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                token.push(c);
+            }
+            tokens.push(token);
+        }
+    }
+
+    tokens
+}
+
+/// The mode of a track and the size of its sectors, from the track type in a cue sheet
+fn track_mode(s: &str) -> Option<(TrackMode, usize)> {
+    let mode = match s {
+        "AUDIO" => (TrackMode::Audio, 0x930),
+        "MODE1/2048" => (TrackMode::Mode1, 0x800),
+        "MODE1/2352" => (TrackMode::Mode1, 0x930),
+        "MODE2/2048" => (TrackMode::Mode2Form1, 0x800),
+        "MODE2/2324" => (TrackMode::Mode2Form2, 0x914),
+        "MODE2/2336" => (TrackMode::Mode2, 0x920),
+        "MODE2/2352" => (TrackMode::Mode2, 0x930),
+        _ => return None,
+    };
+
+    Some(mode)
+}
+
+/// Parse a timecode in the MM:SS:FF format used throughout .cue files
+fn cue_time(s: &str) -> Option<Timecode> {
+    let mut parts = s.split(':').map(|part| part.parse::<i32>());
+
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(m)), Some(Ok(s)), Some(Ok(f)), None) => Some(Timecode::from_msf(m, s, f)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_indexes_and_gaps() {
+        let cue = parse_cue(
+            r#"
+FILE "Game (Track 1).bin" BINARY
+  TRACK 01 MODE2/2352
+    INDEX 01 00:00:00
+FILE "Game (Track 2).bin" BINARY
+  TRACK 02 AUDIO
+    FLAGS DCP PRE
+    PREGAP 00:02:00
+    INDEX 00 00:00:00
+    INDEX 01 00:01:33
+    INDEX 02 00:05:00
+    POSTGAP 01:00:74
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(cue.sessions.len(), 1);
+        let tracks = &cue.sessions[0].tracks;
+        assert_eq!(tracks.len(), 2);
+
+        let data = &tracks[0];
+        assert_eq!(data.number, 1);
+        assert_eq!(data.file, "Game (Track 1).bin");
+        assert!(matches!(data.mode, TrackMode::Mode2));
+        assert_eq!(data.sector_size, 0x930);
+        assert_eq!(data.control(), 0x04);
+        assert!(data.index0.is_none());
+        assert_eq!(data.stored_pregap(), 0);
+
+        let audio = &tracks[1];
+        assert_eq!(audio.file, "Game (Track 2).bin");
+        assert!(matches!(audio.mode, TrackMode::Audio));
+        assert_eq!(audio.control(), 0x03);
+        assert_eq!(audio.pregap.sectors(), 150);
+        assert_eq!(audio.index0.unwrap().sectors(), 0);
+        assert_eq!(audio.index1.sectors(), 75 + 33);
+        assert_eq!(audio.stored_pregap(), 108);
+        assert_eq!(audio.file_start().sectors(), 0);
+        assert_eq!(audio.postgap.sectors(), 60 * 75 + 74);
+    }
+
+    #[test]
+    fn parses_sessions_and_their_gaps() {
+        let cue = parse_cue(
+            r#"
+FILE "disc.bin" BINARY
+REM SESSION 01
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+REM LEAD-OUT 01:30:00
+REM SESSION 02
+REM LEAD-IN 01:00:00
+  TRACK 02 MODE1/2352
+    INDEX 00 00:08:00
+    INDEX 01 00:10:00
+"#,
+        )
+        .unwrap();
+
+        let sessions = &cue.sessions;
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].number, 1);
+        assert!(sessions[0].lead_out.is_none() && sessions[0].lead_in.is_none());
+        assert_eq!(sessions[0].tracks.len(), 1);
+
+        assert_eq!(sessions[1].number, 2);
+        assert_eq!(sessions[1].lead_out.unwrap().sectors(), 90 * 75);
+        assert_eq!(sessions[1].lead_in.unwrap().sectors(), 60 * 75);
+        assert_eq!(sessions[1].tracks.len(), 1);
+        assert_eq!(sessions[1].tracks[0].number, 2);
+        assert_eq!(sessions[1].tracks[0].stored_pregap(), 150);
+    }
+
+    #[test]
+    fn sheets_without_sessions_have_one() {
+        let cue = parse_cue("FILE a.bin BINARY\nTRACK 1 MODE1/2048\nINDEX 1 00:00:00\n").unwrap();
+
+        assert_eq!(cue.sessions.len(), 1);
+        assert_eq!(cue.sessions[0].number, 1);
+        assert_eq!(cue.sessions[0].tracks[0].file, "a.bin");
+        assert_eq!(cue.sessions[0].tracks[0].sector_size, 0x800);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_syntax_error() {
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:0x:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(3))));
+
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 MODE3/2352\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(2))));
+
+        // A track needs an INDEX 01, which is noticed when the next track starts
+        let cue =
+            "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n  TRACK 02 AUDIO\n    INDEX 01 00:00:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(3))));
+
+        // Tracks need a file to be in
+        let cue = "  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(1))));
+    }
+
+    #[test]
+    fn rejects_indexes_which_go_backwards() {
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n  TRACK 02 AUDIO\n    INDEX 00 00:10:00\n    INDEX 01 00:08:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(6))));
+
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:10:00\n  TRACK 02 AUDIO\n    INDEX 01 00:05:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(5))));
+
+        // Naming the same file again doesn't start it over
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:10:00\nFILE \"a.bin\" BINARY\n  TRACK 02 AUDIO\n    INDEX 01 00:05:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(6))));
+
+        // But a new file does
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:10:00\nFILE \"b.bin\" BINARY\n  TRACK 02 AUDIO\n    INDEX 01 00:00:00\n";
+        assert!(parse_cue(cue).is_ok());
+    }
+
+    #[test]
+    fn rejects_index_0_after_index_1() {
+        let cue = "FILE \"a.bin\" BINARY\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n    INDEX 00 00:02:00\n";
+        assert!(matches!(parse_cue(cue), Err(Error::CueParse(4))));
+    }
+
+    #[test]
+    fn rejects_other_file_types() {
+        let cue = "FILE \"a.wav\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+        assert!(matches!(
+            parse_cue(cue),
+            Err(Error::UnsupportedCueFileType(file_type)) if file_type == "WAVE"
+        ));
+    }
+
+    #[test]
+    fn rejects_sheets_without_tracks() {
+        assert!(matches!(
+            parse_cue("REM nothing here\n"),
+            Err(Error::NoDataTracks)
+        ));
+    }
+
+    #[test]
+    fn tokenizes_quoted_names() {
+        assert_eq!(
+            tokenize(r#"  FILE "My Game (Disc 1).bin"   BINARY "#),
+            ["FILE", "My Game (Disc 1).bin", "BINARY"]
+        );
+    }
+}
@@ -0,0 +1,386 @@
+use crate::{
+    convert::{cue_sheet, track_bin_path},
+    error::{Error, Result},
+    hash::{checksums, hash_tracks, Checksums},
+    loader::{load_mds, LoadOptions},
+    mds::Mds,
+};
+use std::{
+    fs::{read_to_string, write},
+    path::{Path, PathBuf},
+};
+
+// Dump databases like redump.org and No-Intro publish their checksums as Logiqx XML DAT files.
+// Each game in the file lists the roms which make it up, which for discs are the .bin of each
+// track and the .cue sheet, named and laid out the way `mds convert --format cue --split-tracks`
+// writes them.
+
+#[derive(Debug)]
+pub struct Game {
+    pub name: String,
+    pub roms: Vec<Rom>,
+}
+
+#[derive(Debug)]
+pub struct Rom {
+    pub name: String,
+    pub size: u64,
+
+    /// Checksums in lowercase hex, for whichever ones the DAT lists
+    pub crc32: Option<String>,
+    pub md5: Option<String>,
+    pub sha1: Option<String>,
+}
+
+impl Rom {
+    /// Whether this rom is the same data as `checksums`. Every checksum the DAT lists must match,
+    /// and there must be at least one.
+    pub fn matches(&self, checksums: &Checksums) -> bool {
+        let crc32 = format!("{:08x}", checksums.crc32);
+        let expected = [
+            (&self.crc32, crc32),
+            (&self.md5, checksums.md5_hex()),
+            (&self.sha1, checksums.sha1_hex()),
+        ];
+
+        self.size == checksums.size
+            && expected.iter().any(|(hash, _)| hash.is_some())
+            && expected
+                .iter()
+                .all(|(hash, ours)| hash.as_ref().is_none_or(|hash| *hash == *ours))
+    }
+}
+
+/// Read every game out of a Logiqx XML DAT file
+pub fn load_dat<P: AsRef<Path>>(path: P) -> Result<Vec<Game>> {
+    let text = read_to_string(path).map_err(Error::Io)?;
+    parse_dat(&text)
+}
+
+fn parse_dat(text: &str) -> Result<Vec<Game>> {
+    // DATs from redump.org and No-Intro start with a DOCTYPE naming the Logiqx DTD
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let doc = roxmltree::Document::parse_with_options(text, options)
+        .map_err(|err| Error::DatParse(err.to_string()))?;
+
+    // Newer DATs call games machines
+    let games = doc
+        .root_element()
+        .children()
+        .filter(|node| node.has_tag_name("game") || node.has_tag_name("machine"));
+
+    games
+        .map(|game| {
+            let roms = game
+                .children()
+                .filter(|node| node.has_tag_name("rom"))
+                .map(|rom| {
+                    let hash = |name| rom.attribute(name).map(str::to_lowercase);
+                    let size = rom.attribute("size").and_then(|size| size.parse().ok());
+
+                    Ok(Rom {
+                        name: rom.attribute("name").unwrap_or_default().to_owned(),
+                        size: size.ok_or_else(|| Error::DatParse("Rom without a size".into()))?,
+                        crc32: hash("crc"),
+                        md5: hash("md5"),
+                        sha1: hash("sha1"),
+                    })
+                })
+                .collect::<Result<_>>()?;
+
+            Ok(Game {
+                name: game.attribute("name").unwrap_or_default().to_owned(),
+                roms,
+            })
+        })
+        .collect()
+}
+
+/// Check an image against the games in a DAT file, reporting which game it is, any tracks which
+/// don't match, and whether the image needs to be renamed to match the DAT. Fails unless every
+/// track and every .bin in the game match. When `emit_dat` is given, a DAT describing the image
+/// is written there, so that it can be submitted if the game is unknown.
+pub fn verify_dat<P: AsRef<Path>, Q: AsRef<Path>>(
+    mds_file: P,
+    dat_file: Q,
+    emit_dat: Option<PathBuf>,
+    options: &LoadOptions,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let games = load_dat(dat_file)?;
+    let (tracks, _) = hash_tracks(&mds, &mds_file, options)?;
+    let name = mds_file
+        .as_ref()
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if let Some(path) = emit_dat {
+        let game_dat = dat_entry(
+            &mds,
+            &name,
+            &tracks.iter().map(|(_, c)| c.clone()).collect::<Vec<_>>(),
+        )?;
+        write(&path, game_dat).map_err(Error::Io)?;
+        println!("Wrote a DAT entry for the image to {}", path.display());
+    }
+
+    // The game with the most tracks in common with the image is the one it is a dump of
+    let best = games
+        .iter()
+        .map(|game| {
+            let num_matches = tracks
+                .iter()
+                .filter(|(_, checksums)| game.roms.iter().any(|rom| rom.matches(checksums)))
+                .count();
+            (game, num_matches)
+        })
+        .filter(|(_, num_matches)| *num_matches > 0)
+        .max_by_key(|(_, num_matches)| *num_matches);
+
+    let Some((game, _)) = best else {
+        println!("No game in the DAT matches this image");
+        Err(Error::VerificationFailed(tracks.len()))?
+    };
+
+    println!("Game: {}", game.name);
+
+    let mut num_problems = 0;
+    let mut matched_roms = Vec::new();
+    for (track, checksums) in &tracks {
+        match game.roms.iter().position(|rom| rom.matches(checksums)) {
+            Some(i) => {
+                println!("  Track {}: matches {}", track.number(), game.roms[i].name);
+                matched_roms.push(i);
+            }
+            None => {
+                println!("  Track {}: no matching rom", track.number());
+                num_problems += 1;
+            }
+        }
+    }
+
+    let bins = game
+        .roms
+        .iter()
+        .enumerate()
+        .filter(|(_, rom)| rom.name.to_lowercase().ends_with(".bin"));
+    for (i, rom) in bins {
+        if !matched_roms.contains(&i) {
+            println!("  Missing: {}", rom.name);
+            num_problems += 1;
+        }
+    }
+
+    // The cue sheet only matches when the .bin files it refers to are named like the game
+    let cue_name = format!("{}.cue", game.name);
+    if let Some(cue_rom) = game.roms.iter().find(|rom| rom.name == cue_name) {
+        let cue = redump_cue_sheet(&mds, &game.name)?;
+        if cue_rom.matches(&checksums(&cue)) {
+            println!("  Cue sheet: matches {}", cue_rom.name);
+        } else {
+            println!("  Cue sheet: differs from {}", cue_rom.name);
+        }
+    }
+
+    if name != game.name {
+        println!(
+            "Rename needed: {name}.mds should be called {}.mds",
+            game.name
+        );
+    }
+
+    if num_problems > 0 {
+        Err(Error::VerificationFailed(num_problems))?;
+    }
+
+    println!("Every track matches");
+    Ok(())
+}
+
+/// The cue sheet redump.org would list for the image if it were called `name`
+fn redump_cue_sheet(mds: &Mds, name: &str) -> Result<Vec<u8>> {
+    let bin_paths = redump_bin_paths(mds, name);
+    cue_sheet(mds, &bin_paths, true)
+}
+
+fn redump_bin_paths(mds: &Mds, name: &str) -> Vec<PathBuf> {
+    let num_tracks = mds.sessions().flat_map(|s| s.data_tracks()).count();
+    let mds_path = PathBuf::from(format!("{name}.mds"));
+
+    (1..=num_tracks)
+        .map(|number| track_bin_path(&mds_path, number, num_tracks))
+        .collect()
+}
+
+/// A Logiqx XML DAT file holding one game, for the image called `name` whose tracks have the
+/// given checksums
+fn dat_entry(mds: &Mds, name: &str, tracks: &[Checksums]) -> Result<String> {
+    let cue = redump_cue_sheet(mds, name)?;
+    let cue_name = format!("{name}.cue");
+    let bin_paths = redump_bin_paths(mds, name);
+
+    let mut roms: Vec<(String, Checksums)> = bin_paths
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .zip(tracks.iter().cloned())
+        .collect();
+    roms.push((cue_name, checksums(&cue)));
+
+    let mut out = String::new();
+    out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<datafile>\n");
+    out.push_str(&format!("\t<game name=\"{}\">\n", escape(name)));
+    out.push_str(&format!(
+        "\t\t<description>{}</description>\n",
+        escape(name)
+    ));
+
+    for (rom_name, checksums) in roms {
+        out.push_str(&format!(
+            "\t\t<rom name=\"{}\" size=\"{}\" crc=\"{:08x}\" md5=\"{}\" sha1=\"{}\"/>\n",
+            escape(&rom_name),
+            checksums.size,
+            checksums.crc32,
+            checksums.md5_hex(),
+            checksums.sha1_hex(),
+        ));
+    }
+
+    out.push_str("\t</game>\n");
+    out.push_str("</datafile>\n");
+
+    Ok(out)
+}
+
+/// Escape the characters which are special in XML
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+
+    const DAT: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/dtds/datafile.dtd">
+<datafile>
+    <header>
+        <name>Sony - PlayStation</name>
+    </header>
+    <game name="Game (USA)">
+        <description>Game (USA)</description>
+        <rom name="Game (USA) (Track 1).bin" size="2352" crc="0A1B2C3D" md5="0123456789ABCDEF0123456789ABCDEF"/>
+        <rom name="Game (USA).cue" size="80" sha1="0123456789abcdef0123456789abcdef01234567"/>
+    </game>
+    <machine name="Other Game (Europe)">
+        <rom name="Other Game (Europe).bin" size="4704"/>
+    </machine>
+</datafile>
+"#;
+
+    fn rom(crc32: Option<&str>, md5: Option<&str>, sha1: Option<&str>, size: u64) -> Rom {
+        Rom {
+            name: "Game.bin".to_string(),
+            size,
+            crc32: crc32.map(str::to_string),
+            md5: md5.map(str::to_string),
+            sha1: sha1.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn reads_games_and_machines() {
+        let games = parse_dat(DAT).unwrap();
+        assert_eq!(games.len(), 2);
+
+        let game = &games[0];
+        assert_eq!(game.name, "Game (USA)");
+        assert_eq!(game.roms.len(), 2);
+        assert_eq!(game.roms[0].name, "Game (USA) (Track 1).bin");
+        assert_eq!(game.roms[0].size, 2352);
+        assert_eq!(game.roms[0].crc32.as_deref(), Some("0a1b2c3d"));
+        assert_eq!(
+            game.roms[0].md5.as_deref(),
+            Some("0123456789abcdef0123456789abcdef")
+        );
+        assert!(game.roms[0].sha1.is_none());
+        assert!(game.roms[1].crc32.is_none());
+
+        assert_eq!(games[1].name, "Other Game (Europe)");
+        assert_eq!(games[1].roms[0].size, 4704);
+    }
+
+    #[test]
+    fn roms_need_a_size() {
+        let dat = r#"<datafile><game name="Game"><rom name="Game.bin" crc="00000000"/></game></datafile>"#;
+        assert!(matches!(parse_dat(dat), Err(Error::DatParse(_))));
+    }
+
+    #[test]
+    fn roms_match_on_every_checksum_they_list() {
+        let ours = checksums(b"some track data");
+        let crc32 = format!("{:08x}", ours.crc32);
+        let md5 = ours.md5_hex();
+        let sha1 = ours.sha1_hex();
+
+        assert!(rom(Some(&crc32), Some(&md5), Some(&sha1), ours.size).matches(&ours));
+
+        // Checksums the DAT leaves out aren't compared
+        assert!(rom(Some(&crc32), None, None, ours.size).matches(&ours));
+        assert!(rom(None, None, Some(&sha1), ours.size).matches(&ours));
+
+        // But any which are listed have to match
+        let wrong_sha1 = "0".repeat(40);
+        assert!(!rom(Some(&crc32), Some(&md5), Some(&wrong_sha1), ours.size).matches(&ours));
+        assert!(!rom(Some("00000000"), None, None, ours.size).matches(&ours));
+        assert!(!rom(Some(&crc32), None, None, ours.size + 1).matches(&ours));
+    }
+
+    #[test]
+    fn roms_without_checksums_match_nothing() {
+        let ours = checksums(b"some track data");
+        assert!(!rom(None, None, None, ours.size).matches(&ours));
+    }
+
+    #[test]
+    fn escapes_xml_special_characters() {
+        assert_eq!(
+            escape(r#"Tom & Jerry's <"Big"> Adventure"#),
+            "Tom &amp; Jerry&apos;s &lt;&quot;Big&quot;&gt; Adventure"
+        );
+        assert_eq!(escape("Game (USA)"), "Game (USA)");
+    }
+
+    #[test]
+    fn dat_entries_list_each_bin_and_the_cue_sheet() {
+        let disc = TestDisc::new("dat-entry", &[0x5A; 2048 * 4], true);
+        let (tracks, _) = hash_tracks(disc.mds(), disc.mds_path(), disc.options()).unwrap();
+        let tracks: Vec<Checksums> = tracks.into_iter().map(|(_, c)| c).collect();
+        let name = "Tom & Jerry (USA)";
+
+        let dat = dat_entry(disc.mds(), name, &tracks).unwrap();
+        assert!(dat.contains(r#"<game name="Tom &amp; Jerry (USA)">"#));
+
+        let games = parse_dat(&dat).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].name, name);
+
+        let roms = &games[0].roms;
+        assert_eq!(roms.len(), 2);
+        assert_eq!(roms[0].name, "Tom & Jerry (USA).bin");
+        assert!(roms[0].matches(&tracks[0]));
+
+        let cue = redump_cue_sheet(disc.mds(), name).unwrap();
+        assert_eq!(roms[1].name, "Tom & Jerry (USA).cue");
+        assert!(roms[1].matches(&checksums(&cue)));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fmt::Display, io};
+use std::{fmt::Display, io, path::PathBuf};
 
 use crate::mds::TrackMode;
 
@@ -6,14 +6,34 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug)]
 pub enum Error {
+    ChdVerification,
+    CueParse(usize),
+    DatParse(String),
+    FilesystemParse(String),
+    FlacVerification,
     Io(io::Error),
-    MissingInputFile,
+    MissingInputFile(Vec<PathBuf>),
     MultiTrackNotSupported,
     NoDataTracks,
+    NoFilesystem,
+    NoPlaceholderValue(String),
     NoSessions,
-    ParseError,
-    TooManySessions,
+    NoSubchannels,
+    NoSuchFile(String),
+    NoSuchSector(i64),
+    NoSuchSession(usize),
+    NoSuchTrack(usize),
+    NoUserData(TrackMode, usize),
+    NotAudio(usize),
+    NotBootable,
+    Parse,
+    RawSectorsRequired(TrackMode, usize),
+    TruncatedFile(PathBuf, u64, u64),
+    UnknownChdTrackSize(TrackMode, usize),
     UnknownCueTrackSize(TrackMode, usize),
+    UnknownPlaceholder(String),
+    UnsupportedCueFileType(String),
+    VerificationFailed(usize),
 }
 
 impl Display for Error {
@@ -21,16 +41,73 @@ impl Display for Error {
         use Error::*;
 
         match self {
+            ChdVerification => write!(f, "The .chd file failed verification"),
+            CueParse(line) => write!(f, "Error parsing cue sheet on line {line}"),
+            DatParse(err) => write!(f, "Error parsing DAT file: {err}"),
+            FilesystemParse(err) => write!(f, "Error reading filesystem: {err}"),
+            FlacVerification => write!(f, "The .flac file failed verification"),
             Io(err) => write!(f, "{err}"),
-            MissingInputFile => write!(f, "No input file provided to read data from"),
+            MissingInputFile(tried) if tried.is_empty() => {
+                write!(f, "No input file provided to read data from")
+            }
+            MissingInputFile(tried) => {
+                write!(f, "Couldn't find the data file. Tried:")?;
+                for path in tried {
+                    write!(f, "\n  {}", path.display())?;
+                }
+                Ok(())
+            }
             MultiTrackNotSupported => write!(f, "Multi track conversion not yet supported"),
             NoDataTracks => write!(f, "There are no data tracks in this mdf"),
+            NoFilesystem => write!(f, "No filesystem this program can read was found"),
+            NoPlaceholderValue(name) => {
+                write!(
+                    f,
+                    "The disc's {name} couldn't be worked out for the output name"
+                )
+            }
             NoSessions => write!(f, "There are no sessions in the image"),
-            ParseError => write!(f, "Error parsing mds file"),
-            TooManySessions => write!(f, "Cannot convert multi-session images"),
+            NoSubchannels => write!(f, "This track does not contain subchannel data"),
+            NoSuchFile(path) => write!(f, "There is no file {path} in the image"),
+            NoSuchSector(lba) => write!(f, "Sector {lba} isn't stored in the image"),
+            NoSuchSession(n) => write!(f, "There is no session {n} in the image"),
+            NoSuchTrack(n) => write!(f, "There is no track {n} in the image"),
+            NoUserData(mode, data_size) => write!(
+                f,
+                "{mode:?} @ {data_size} sectors don't hold 2048 bytes of user data; try --raw"
+            ),
+            NotAudio(n) => write!(f, "Track {n} is not an audio track"),
+            NotBootable => write!(f, "The image has no El Torito boot catalog"),
+            Parse => write!(f, "Error parsing mds file"),
+            RawSectorsRequired(mode, data_size) => {
+                write!(
+                    f,
+                    "Raw 2352 byte sectors are required, not {mode:?} @ {data_size}"
+                )
+            }
+            TruncatedFile(path, expected, found) => write!(
+                f,
+                "{} is too short: {expected} bytes of track data were expected, but only {found} \
+                 were found",
+                path.display()
+            ),
+            UnknownChdTrackSize(mode, data_size) => {
+                write!(
+                    f,
+                    "Track type can't be stored in a .chd: {mode:?} @ {data_size}"
+                )
+            }
             UnknownCueTrackSize(mode, data_size) => {
                 write!(f, "Unusual track type: {mode:?} @ {data_size}")
             }
+            UnknownPlaceholder(name) => write!(f, "Unknown placeholder in output name: {name}"),
+            UnsupportedCueFileType(file_type) => {
+                write!(
+                    f,
+                    "Only BINARY files are supported in cue sheets, not {file_type}"
+                )
+            }
+            VerificationFailed(n) => write!(f, "{n} mismatches found in the image"),
         }
     }
 }
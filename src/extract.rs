@@ -0,0 +1,193 @@
+use crate::{
+    args::{FilesystemType, ResourceForkFormat},
+    convert::{track_to_bin, track_to_wav},
+    error::{Error, Result},
+    filesystem::{boot_catalog, open_filesystem, DiscReader, Entry, EntryKind, Extent, Filesystem},
+    loader::{load_mds, LoadOptions},
+    macfile::{write_apple_double, write_macbinary},
+    mds::TrackMode,
+    util::pluralize,
+};
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    time::SystemTime,
+};
+
+/// Write a single track of an image to its own file next to the .mds, named like
+/// `Name (Track 02).bin`. When `wav` is true the track is written as a .wav file instead, which
+/// only works for audio tracks.
+pub fn extract_track<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    number: usize,
+    wav: bool,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let track = mds.track(number)?;
+    if wav && !matches!(track.mode, TrackMode::Audio) {
+        Err(Error::NotAudio(number))?;
+    }
+
+    let extension = if wav { "wav" } else { "bin" };
+    let stem = mds_file.as_ref().file_stem().unwrap().to_string_lossy();
+    let path = mds_file
+        .as_ref()
+        .with_file_name(format!("{stem} (Track {number:02}).{extension}"));
+
+    let file = File::create(&path).map_err(Error::Io)?;
+    let mut writer = BufWriter::new(file);
+
+    if wav {
+        track_to_wav(track, &mds_file, options, &mut writer)?;
+    } else {
+        track_to_bin(track, &mds_file, options, &mut writer)?;
+    }
+
+    writer.flush().map_err(Error::Io)?;
+    println!("Wrote track {number} to {}", path.display());
+
+    Ok(())
+}
+
+/// Write each El Torito boot image on the disc to its own file next to the .mds, named like
+/// `Name (Boot 01).img`. Emulated floppy and hard disks are written in full, so they can be
+/// used as disk images.
+pub fn extract_boot<P: AsRef<Path>>(mds_file: P, options: &LoadOptions) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let catalog = boot_catalog(&mds, mds_file.as_ref(), options)?.ok_or(Error::NotBootable)?;
+    let mut disc = DiscReader::new(&mds, mds_file.as_ref(), options);
+    let stem = mds_file.as_ref().file_stem().unwrap().to_string_lossy();
+
+    for (i, image) in catalog.images.iter().enumerate() {
+        let number = i + 1;
+        let path = mds_file
+            .as_ref()
+            .with_file_name(format!("{stem} (Boot {number:02}).img"));
+
+        let extents = [Extent::from_sector(image.start_sector, image.size)];
+        write_file(&path, None, |w| disc.copy_extents(&extents, w))?;
+        println!(
+            "Wrote boot image {number} ({}, {}) to {}",
+            image.platform,
+            image.emulation,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Copy a file or directory out of the image's filesystem into `output_dir`, keeping the
+/// modification times recorded on the disc. Directories are copied along with everything in
+/// them, and the root directory's contents are copied straight into `output_dir`.
+pub fn extract_path<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    path: &str,
+    output_dir: &Path,
+    filesystem: Option<&FilesystemType>,
+    resource_forks: &ResourceForkFormat,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let (mut disc, fs) = open_filesystem(&mds, mds_file.as_ref(), options, filesystem)?;
+    let entry = fs.lookup(&mut disc, path)?;
+
+    fs::create_dir_all(output_dir).map_err(Error::Io)?;
+    let target = if entry.name.is_empty() {
+        output_dir.to_path_buf()
+    } else {
+        output_dir.join(&entry.name)
+    };
+
+    let num_files = extract_entry(fs.as_ref(), &mut disc, &entry, &target, resource_forks)?;
+    println!(
+        "Extracted {num_files} {} to {}",
+        pluralize("file", num_files),
+        output_dir.display()
+    );
+
+    Ok(())
+}
+
+/// Write an entry to `target`, returning how many files were written
+fn extract_entry(
+    fs: &dyn Filesystem,
+    disc: &mut DiscReader,
+    entry: &Entry,
+    target: &Path,
+    resource_forks: &ResourceForkFormat,
+) -> Result<usize> {
+    let mut num_files = 0;
+
+    match &entry.kind {
+        EntryKind::File => {
+            // Files from Mac filesystems which have a resource fork or type and creator codes
+            // need somewhere to put them
+            match (&entry.mac, resource_forks) {
+                (Some(mac), ResourceForkFormat::AppleDouble) if mac.is_significant() => {
+                    write_file(target, entry.modified, |w| fs.read_file(disc, entry, w))?;
+                    let path = target.with_file_name(format!("._{}", entry.name));
+                    write_file(&path, entry.modified, |w| {
+                        write_apple_double(disc, entry, mac, w)
+                    })?;
+                }
+                (Some(mac), ResourceForkFormat::Macbinary) if mac.is_significant() => {
+                    let path = target.with_file_name(format!("{}.bin", entry.name));
+                    write_file(&path, entry.modified, |w| {
+                        write_macbinary(fs, disc, entry, mac, w)
+                    })?;
+                }
+                _ => write_file(target, entry.modified, |w| fs.read_file(disc, entry, w))?,
+            }
+
+            num_files += 1;
+        }
+        EntryKind::Directory => {
+            fs::create_dir_all(target).map_err(Error::Io)?;
+
+            for child in fs.read_dir(disc, entry)? {
+                // Names come from the disc, so make sure they can't point outside of `target`
+                if child.name.is_empty() || child.name == ".." || child.name.contains(['/', '\\']) {
+                    println!("Skipped {:?}, which isn't a safe file name", child.name);
+                    continue;
+                }
+
+                let child_target = target.join(&child.name);
+                num_files += extract_entry(fs, disc, &child, &child_target, resource_forks)?;
+            }
+
+            // Not every platform lets a directory's times be changed, so this is best effort. The
+            // root directory is `output_dir` itself, which is left alone.
+            if let Some(modified) = entry.modified.filter(|_| !entry.name.is_empty()) {
+                let _ = File::open(target).and_then(|dir| dir.set_modified(modified));
+            }
+        }
+        EntryKind::Symlink(link) => {
+            println!("Skipped symbolic link {} -> {link}", target.display());
+        }
+    }
+
+    Ok(num_files)
+}
+
+/// Create the file at `path`, fill it in with `write`, and give it the modification time
+/// recorded on the disc
+fn write_file<F>(path: &Path, modified: Option<SystemTime>, write: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let file = File::create(path).map_err(Error::Io)?;
+    let mut writer = BufWriter::new(file);
+    write(&mut writer)?;
+
+    let file = writer
+        .into_inner()
+        .map_err(|err| Error::Io(err.into_error()))?;
+    if let Some(modified) = modified {
+        file.set_modified(modified).map_err(Error::Io)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,451 @@
+use super::{first_data_sector, DiscReader};
+use crate::{
+    error::{Error, Result},
+    loader::LoadOptions,
+    mds::Mds,
+    sector::USER_DATA_SIZE,
+};
+use std::{fmt::Display, path::Path};
+
+// El Torito (https://pdos.csail.mit.edu/6.828/2017/readings/boot-cdrom.pdf) makes a CD bootable by
+// adding a boot record to the ISO 9660 volume descriptors. It points at a boot catalog, which
+// lists the boot images on the disc: a default one, and then sections of others meant for
+// particular platforms, such as the EFI images on most install discs. Each image either pretends
+// to be a floppy disk or hard disk, or is loaded into memory and run as it is.
+
+/// The sector where ISO 9660 volume descriptors start, relative to the start of the session
+const FIRST_DESCRIPTOR: i64 = 16;
+
+/// The most volume descriptors to look through for the boot record
+const MAX_DESCRIPTORS: i64 = 32;
+
+/// The most sectors a boot catalog is read from
+const MAX_CATALOG_SECTORS: i64 = 8;
+
+const BOOT_RECORD_ID: &[u8] = b"EL TORITO SPECIFICATION";
+
+const ENTRY_SIZE: usize = 32;
+
+/// Boot catalog header IDs
+const VALIDATION_ENTRY: u8 = 0x01;
+const SECTION_HEADER: u8 = 0x90;
+const FINAL_SECTION_HEADER: u8 = 0x91;
+const SECTION_EXTENSION: u8 = 0x44;
+
+const BOOTABLE: u8 = 0x88;
+
+/// Images are measured in the 512-byte sectors of the disk they pretend to be
+const VIRTUAL_SECTOR_SIZE: u64 = 512;
+
+/// Where images are loaded when the catalog doesn't say
+const DEFAULT_LOAD_SEGMENT: u16 = 0x07C0;
+
+/// A disc's boot catalog, and every boot image it lists
+#[derive(Debug)]
+pub struct BootCatalog {
+    /// The LBA of the boot catalog
+    pub sector: u32,
+    pub images: Vec<BootImage>,
+}
+
+#[derive(Debug)]
+pub struct BootImage {
+    /// Whether this is the catalog's default entry, rather than one from a section
+    pub default: bool,
+
+    /// Whether the entry is marked as bootable
+    pub bootable: bool,
+    pub platform: Platform,
+    pub emulation: Emulation,
+
+    /// The segment of memory the image is loaded into
+    pub load_segment: u16,
+
+    /// The partition type of hard disk images
+    pub system_type: u8,
+
+    /// The number of 512-byte sectors the catalog says to load
+    pub sector_count: u16,
+
+    /// The LBA of the first sector of the image
+    pub start_sector: u32,
+
+    /// The size of the image in bytes. Emulated disks are the size of the disk they pretend to
+    /// be, which is more than the catalog says to load.
+    pub size: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    X86,
+    PowerPc,
+    Mac,
+    Efi,
+    Unknown(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Emulation {
+    None,
+    Floppy1200,
+    Floppy1440,
+    Floppy2880,
+    HardDisk,
+    Unknown(u8),
+}
+
+impl From<u8> for Platform {
+    fn from(id: u8) -> Self {
+        match id {
+            0x00 => Self::X86,
+            0x01 => Self::PowerPc,
+            0x02 => Self::Mac,
+            0xEF => Self::Efi,
+            id => Self::Unknown(id),
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::X86 => write!(f, "x86"),
+            Self::PowerPc => write!(f, "PowerPC"),
+            Self::Mac => write!(f, "Mac"),
+            Self::Efi => write!(f, "EFI"),
+            Self::Unknown(id) => write!(f, "platform 0x{id:02X}"),
+        }
+    }
+}
+
+impl From<u8> for Emulation {
+    fn from(media: u8) -> Self {
+        match media & 0x0F {
+            0 => Self::None,
+            1 => Self::Floppy1200,
+            2 => Self::Floppy1440,
+            3 => Self::Floppy2880,
+            4 => Self::HardDisk,
+            media => Self::Unknown(media),
+        }
+    }
+}
+
+impl Display for Emulation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "no emulation"),
+            Self::Floppy1200 => write!(f, "1.2MB floppy"),
+            Self::Floppy1440 => write!(f, "1.44MB floppy"),
+            Self::Floppy2880 => write!(f, "2.88MB floppy"),
+            Self::HardDisk => write!(f, "hard disk"),
+            Self::Unknown(media) => write!(f, "emulation type {media}"),
+        }
+    }
+}
+
+/// Read the boot catalog of the last session on a disc, which is the one a BIOS boots from.
+/// Returns `None` if the disc isn't bootable.
+pub fn boot_catalog(
+    mds: &Mds,
+    mds_path: &Path,
+    options: &LoadOptions,
+) -> Result<Option<BootCatalog>> {
+    let mut disc = DiscReader::new(mds, mds_path, options);
+    let start_lba = first_data_sector(mds)?;
+
+    read_boot_catalog(&mut disc, start_lba)
+}
+
+/// Look for a boot record in the ISO 9660 volume descriptors of the session starting at
+/// `start_lba`, and read the catalog it points to
+fn read_boot_catalog(disc: &mut DiscReader, start_lba: i64) -> Result<Option<BootCatalog>> {
+    let mut buf = [0; USER_DATA_SIZE];
+    let mut catalog_sector = None;
+
+    for i in FIRST_DESCRIPTOR..FIRST_DESCRIPTOR + MAX_DESCRIPTORS {
+        if disc.read_sector(start_lba + i, &mut buf).is_err() || &buf[1..6] != b"CD001" {
+            return Ok(None);
+        }
+
+        match buf[0] {
+            0 if buf[7..].starts_with(BOOT_RECORD_ID) => {
+                catalog_sector = Some(u32::from_le_bytes(buf[71..75].try_into().unwrap()));
+                break;
+            }
+            255 => break,
+            _ => {}
+        }
+    }
+
+    let Some(sector) = catalog_sector else {
+        return Ok(None);
+    };
+
+    let mut catalog = Vec::new();
+    for i in 0..MAX_CATALOG_SECTORS {
+        if disc.read_sector(sector as i64 + i, &mut buf).is_err() {
+            break;
+        }
+        catalog.extend_from_slice(&buf);
+    }
+
+    let images = parse_catalog(disc, &catalog)?;
+    Ok(Some(BootCatalog { sector, images }))
+}
+
+fn parse_catalog(disc: &mut DiscReader, catalog: &[u8]) -> Result<Vec<BootImage>> {
+    let mut entries = catalog.chunks_exact(ENTRY_SIZE);
+
+    // The validation entry's 16-bit words add up to zero, and it ends with a key
+    let validation = entries.next().ok_or_else(|| bad_catalog("it's empty"))?;
+    let checksum = validation
+        .chunks_exact(2)
+        .fold(0u16, |sum, word| sum.wrapping_add(u16_le(word)));
+    if validation[0] != VALIDATION_ENTRY || validation[30..32] != [0x55, 0xAA] || checksum != 0 {
+        return Err(bad_catalog("the validation entry is invalid"));
+    }
+
+    let default = entries
+        .next()
+        .ok_or_else(|| bad_catalog("there's no default entry"))?;
+    let mut images = vec![boot_image(disc, default, validation[1].into(), true)?];
+
+    // Sections follow until one is marked as the last
+    while let Some(header) = entries.next() {
+        if header[0] != SECTION_HEADER && header[0] != FINAL_SECTION_HEADER {
+            break;
+        }
+
+        let platform = header[1].into();
+        let mut num_entries = u16_le(&header[2..4]);
+
+        while num_entries > 0 {
+            let Some(entry) = entries.next() else {
+                break;
+            };
+            if entry[0] == SECTION_EXTENSION {
+                continue;
+            }
+
+            images.push(boot_image(disc, entry, platform, false)?);
+            num_entries -= 1;
+        }
+
+        if header[0] == FINAL_SECTION_HEADER {
+            break;
+        }
+    }
+
+    Ok(images)
+}
+
+/// Parse a default or section entry, and work out how big its image is
+fn boot_image(
+    disc: &mut DiscReader,
+    entry: &[u8],
+    platform: Platform,
+    default: bool,
+) -> Result<BootImage> {
+    let emulation = Emulation::from(entry[1]);
+    let sector_count = u16_le(&entry[6..8]);
+    let start_sector = u32::from_le_bytes(entry[8..12].try_into().unwrap());
+    let loaded = sector_count as u64 * VIRTUAL_SECTOR_SIZE;
+
+    let size = match emulation {
+        Emulation::Floppy1200 => 1_228_800,
+        Emulation::Floppy1440 => 1_474_560,
+        Emulation::Floppy2880 => 2_949_120,
+        Emulation::HardDisk => hard_disk_size(disc, start_sector)?.unwrap_or(loaded),
+        // EFI images are usually FAT filesystems which are too big for the sector count, so
+        // they're given as 0 or 1 and the firmware reads the filesystem's own size instead
+        Emulation::None if platform == Platform::Efi && sector_count <= 1 => {
+            fat_size(disc, start_sector)?.unwrap_or(loaded)
+        }
+        _ => loaded,
+    };
+
+    Ok(BootImage {
+        default,
+        bootable: entry[0] == BOOTABLE,
+        platform,
+        emulation,
+        load_segment: match u16_le(&entry[2..4]) {
+            0 => DEFAULT_LOAD_SEGMENT,
+            segment => segment,
+        },
+        system_type: entry[4],
+        sector_count,
+        start_sector,
+        size,
+    })
+}
+
+/// The size of an emulated hard disk, which runs to the end of the partition in its MBR
+fn hard_disk_size(disc: &mut DiscReader, start_sector: u32) -> Result<Option<u64>> {
+    let mut mbr = [0; USER_DATA_SIZE];
+    disc.read_sector(start_sector as i64, &mut mbr)?;
+    if mbr[510..512] != [0x55, 0xAA] {
+        return Ok(None);
+    }
+
+    let partition = &mbr[446..462];
+    let first = u32::from_le_bytes(partition[8..12].try_into().unwrap()) as u64;
+    let count = u32::from_le_bytes(partition[12..16].try_into().unwrap()) as u64;
+
+    Ok((count > 0).then_some((first + count) * VIRTUAL_SECTOR_SIZE))
+}
+
+/// The size of a FAT filesystem, from the BIOS parameter block in its boot sector
+fn fat_size(disc: &mut DiscReader, start_sector: u32) -> Result<Option<u64>> {
+    let mut boot = [0; USER_DATA_SIZE];
+    disc.read_sector(start_sector as i64, &mut boot)?;
+    if boot[510..512] != [0x55, 0xAA] {
+        return Ok(None);
+    }
+
+    let sector_size = u16_le(&boot[11..13]) as u64;
+    let num_sectors = match u16_le(&boot[19..21]) {
+        0 => u32::from_le_bytes(boot[32..36].try_into().unwrap()) as u64,
+        count => count as u64,
+    };
+
+    Ok((sector_size > 0 && num_sectors > 0).then_some(sector_size * num_sectors))
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn bad_catalog(message: &str) -> Error {
+    Error::FilesystemParse(format!("bad El Torito boot catalog: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{filesystem::tests::write_at, testing::TestDisc};
+
+    /// An ISO 9660 volume descriptor of the given type
+    fn descriptor(descriptor_type: u8) -> Vec<u8> {
+        let mut descriptor = vec![descriptor_type];
+        descriptor.extend_from_slice(b"CD001\x01");
+
+        descriptor
+    }
+
+    fn entry(emulation: u8, sector_count: u16, start_sector: u32) -> Vec<u8> {
+        let mut entry = vec![0; ENTRY_SIZE];
+        entry[0] = BOOTABLE;
+        entry[1] = emulation;
+        entry[6..8].copy_from_slice(&sector_count.to_le_bytes());
+        entry[8..12].copy_from_slice(&start_sector.to_le_bytes());
+
+        entry
+    }
+
+    fn section_header(header: u8, platform: u8, num_entries: u16) -> Vec<u8> {
+        let mut entry = vec![0; ENTRY_SIZE];
+        entry[0] = header;
+        entry[1] = platform;
+        entry[2..4].copy_from_slice(&num_entries.to_le_bytes());
+
+        entry
+    }
+
+    /// A disc with a boot record at sector 17 pointing at a catalog in sector 20. Its default
+    /// image is a no emulation x86 one, followed by a section with a hard disk image whose
+    /// partition ends 100 blocks in, and a final EFI section with a 1.44MB FAT image.
+    fn bootable_disc() -> Vec<u8> {
+        let mut image = Vec::new();
+
+        write_at(&mut image, 16, 0, &descriptor(1));
+        let mut boot_record = descriptor(0);
+        boot_record.extend_from_slice(BOOT_RECORD_ID);
+        write_at(&mut image, 17, 0, &boot_record);
+        write_at(&mut image, 17, 71, &20u32.to_le_bytes());
+        write_at(&mut image, 18, 0, &descriptor(255));
+
+        let mut validation = vec![0; ENTRY_SIZE];
+        validation[0] = VALIDATION_ENTRY;
+        validation[4..12].copy_from_slice(b"TESTDISC");
+        validation[30..32].copy_from_slice(&[0x55, 0xAA]);
+        let sum = validation
+            .chunks_exact(2)
+            .fold(0u16, |sum, word| sum.wrapping_add(u16_le(word)));
+        validation[28..30].copy_from_slice(&0u16.wrapping_sub(sum).to_le_bytes());
+
+        let catalog = [
+            validation,
+            entry(0, 4, 21),
+            section_header(SECTION_HEADER, 0x00, 1),
+            entry(4, 1, 22),
+            section_header(FINAL_SECTION_HEADER, 0xEF, 1),
+            entry(0, 1, 23),
+        ];
+        write_at(&mut image, 20, 0, &catalog.concat());
+
+        let mut mbr = vec![0; 512];
+        mbr[446 + 8..446 + 12].copy_from_slice(&1u32.to_le_bytes());
+        mbr[446 + 12..446 + 16].copy_from_slice(&99u32.to_le_bytes());
+        mbr[510..512].copy_from_slice(&[0x55, 0xAA]);
+        write_at(&mut image, 22, 0, &mbr);
+
+        let mut fat = vec![0; 512];
+        fat[11..13].copy_from_slice(&512u16.to_le_bytes());
+        fat[19..21].copy_from_slice(&2880u16.to_le_bytes());
+        fat[510..512].copy_from_slice(&[0x55, 0xAA]);
+        write_at(&mut image, 23, 0, &fat);
+
+        image
+    }
+
+    #[test]
+    fn reads_every_boot_image() {
+        let disc = TestDisc::new("el-torito", &bootable_disc(), false);
+        let catalog = boot_catalog(disc.mds(), &disc.mds_path(), disc.options())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(catalog.sector, 20);
+        assert_eq!(catalog.images.len(), 3);
+
+        let default = &catalog.images[0];
+        assert!(default.default && default.bootable);
+        assert_eq!(default.platform, Platform::X86);
+        assert_eq!(default.emulation, Emulation::None);
+        assert_eq!(default.load_segment, DEFAULT_LOAD_SEGMENT);
+        assert_eq!((default.start_sector, default.size), (21, 2048));
+
+        let hard_disk = &catalog.images[1];
+        assert!(!hard_disk.default);
+        assert_eq!(hard_disk.emulation, Emulation::HardDisk);
+        assert_eq!(hard_disk.size, 100 * 512);
+
+        let efi = &catalog.images[2];
+        assert_eq!(efi.platform, Platform::Efi);
+        assert_eq!(efi.size, 1_474_560);
+    }
+
+    #[test]
+    fn rejects_a_bad_validation_entry() {
+        let mut image = bootable_disc();
+        image[20 * USER_DATA_SIZE + 28] ^= 0x01;
+
+        let disc = TestDisc::new("el-torito-checksum", &image, false);
+        assert!(matches!(
+            read_boot_catalog(&mut disc.reader(), 0),
+            Err(Error::FilesystemParse(_))
+        ));
+    }
+
+    #[test]
+    fn finds_nothing_without_a_boot_record() {
+        let mut image = Vec::new();
+        write_at(&mut image, 16, 0, &descriptor(1));
+        write_at(&mut image, 17, 0, &descriptor(255));
+
+        let disc = TestDisc::new("el-torito-none", &image, true);
+        assert!(read_boot_catalog(&mut disc.reader(), 0).unwrap().is_none());
+    }
+}
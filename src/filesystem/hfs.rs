@@ -0,0 +1,742 @@
+use super::{
+    from_unix_seconds, truncate_extents, DiscReader, Entry, EntryKind, Extent, Filesystem,
+    MacMetadata,
+};
+use crate::{
+    args::FilesystemType,
+    error::{Error, Result},
+    sector::USER_DATA_SIZE,
+};
+use encoding_rs::MACINTOSH;
+use std::{collections::HashMap, time::SystemTime};
+
+// HFS and HFS+ are described in Inside Macintosh: Files
+// (https://developer.apple.com/library/archive/documentation/mac/Files/Files-2.html) and Technical
+// Note TN1150 (https://developer.apple.com/library/archive/technotes/tn/tn1150.html). Hybrid
+// discs put the volume inside an Apple partition map in the first sectors of the data track,
+// which ISO 9660 leaves unused, or sometimes put it there without a partition map at all. The
+// rest of the disc is shared between the two filesystems.
+//
+// Every file and directory is a record in the catalog B-tree, keyed by the ID of its parent
+// directory and its name. Rather than searching the tree, every leaf record is read when the
+// volume is opened, which is quick for the size of a CD and avoids having to sort names the way
+// the Mac did.
+
+/// Where the volume header is, from the start of the volume
+const VOLUME_HEADER_OFFSET: u64 = 1024;
+
+/// The catalog node ID of the root directory, and of its parent
+const ROOT_ID: u32 = 2;
+const ROOT_PARENT_ID: u32 = 1;
+
+/// The catalog node ID of the catalog file itself, which its overflow extents are filed under
+const CATALOG_FILE_ID: u32 = 4;
+
+/// Catalog record types, which are the same for both versions
+const FOLDER_RECORD: u16 = 1;
+const FILE_RECORD: u16 = 2;
+
+const DATA_FORK: u8 = 0x00;
+const RESOURCE_FORK: u8 = 0xFF;
+
+/// Kinds of B-tree nodes
+const LEAF_NODE: i8 = -1;
+
+/// Seconds from 1904-01-01, the epoch of Mac dates, to 1970-01-01
+const MAC_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// The most partitions to look through in an Apple partition map
+const MAX_PARTITIONS: u32 = 64;
+
+/// Extents in the extents overflow file, keyed by fork type and file ID. Each record gives the
+/// block of the fork it starts at, and the extents from there.
+type OverflowExtents = HashMap<(u8, u32), Vec<(u32, Vec<(u32, u32)>)>>;
+
+/// Where a volume's allocation blocks are
+struct Volume {
+    /// HFS+ rather than HFS
+    plus: bool,
+
+    /// The offset of allocation block 0 from the start of sector 0
+    first_block: u64,
+
+    block_size: u64,
+}
+
+pub struct Hfs {
+    plus: bool,
+    volume_name: String,
+    root: Entry,
+
+    /// The entries in each directory, keyed by the directory's catalog node ID
+    directories: HashMap<u32, Vec<Entry>>,
+}
+
+impl Hfs {
+    /// Look for an HFS or HFS+ volume in the data track starting at `start_lba`, either in an
+    /// Apple partition map or at the start of the track. Returns `None` if there isn't one.
+    pub fn open(disc: &mut DiscReader, start_lba: i64) -> Result<Option<Self>> {
+        let track_offset = start_lba as u64 * USER_DATA_SIZE as u64;
+        let volume_offset = find_partition(disc, track_offset)?.unwrap_or(track_offset);
+
+        let mut header = [0; 512];
+        if disc
+            .read_at(volume_offset + VOLUME_HEADER_OFFSET, &mut header)
+            .is_err()
+        {
+            return Ok(None);
+        }
+
+        match &header[0..2] {
+            // HFS volumes can have an HFS+ volume embedded in them, so that Macs which only
+            // understood HFS would at least show something
+            b"BD" if &header[124..126] == b"H+" => {
+                let block_size = u32_be(&header[20..24]) as u64;
+                let first_block = volume_offset + u16_be(&header[28..30]) as u64 * 512;
+                let embedded = first_block + u16_be(&header[126..128]) as u64 * block_size;
+                disc.read_at(embedded + VOLUME_HEADER_OFFSET, &mut header)?;
+
+                Self::open_plus(disc, embedded, &header).map(Some)
+            }
+            b"BD" => Self::open_hfs(disc, volume_offset, &header).map(Some),
+            b"H+" | b"HX" => Self::open_plus(disc, volume_offset, &header).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Open an HFS volume from its master directory block
+    fn open_hfs(disc: &mut DiscReader, volume_offset: u64, mdb: &[u8]) -> Result<Self> {
+        let volume = Volume {
+            plus: false,
+            first_block: volume_offset + u16_be(&mdb[28..30]) as u64 * 512,
+            block_size: u32_be(&mdb[20..24]) as u64,
+        };
+
+        let name_len = (mdb[36] as usize).min(27);
+        let volume_name = mac_roman(&mdb[37..37 + name_len]);
+
+        let extents_size = u32_be(&mdb[130..134]) as u64;
+        let extents_file = volume.extents(&hfs_extents(&mdb[134..146]), extents_size);
+        let catalog_size = u32_be(&mdb[146..150]) as u64;
+        let catalog_extents = hfs_extents(&mdb[150..162]);
+
+        Self::read_catalog(
+            disc,
+            volume,
+            volume_name,
+            &extents_file,
+            catalog_extents,
+            catalog_size,
+        )
+    }
+
+    /// Open an HFS+ volume from its volume header
+    fn open_plus(disc: &mut DiscReader, volume_offset: u64, header: &[u8]) -> Result<Self> {
+        let volume = Volume {
+            plus: true,
+            first_block: volume_offset,
+            block_size: u32_be(&header[40..44]) as u64,
+        };
+
+        let extents_fork = &header[192..272];
+        let extents_file = volume.extents(&plus_extents(&extents_fork[16..]), u64_be(extents_fork));
+        let catalog_fork = &header[272..352];
+
+        // HFS+ volumes keep their name in the root directory's catalog key, which gets filled in
+        // once the catalog has been read
+        Self::read_catalog(
+            disc,
+            volume,
+            String::new(),
+            &extents_file,
+            plus_extents(&catalog_fork[16..]),
+            u64_be(catalog_fork),
+        )
+    }
+
+    /// Read every file and directory in the catalog
+    fn read_catalog(
+        disc: &mut DiscReader,
+        volume: Volume,
+        mut volume_name: String,
+        extents_file: &[Extent],
+        catalog_extents: Vec<(u32, u32)>,
+        catalog_size: u64,
+    ) -> Result<Self> {
+        if volume.block_size == 0 || !volume.block_size.is_multiple_of(512) {
+            return Err(bad_hfs("the allocation block size is invalid"));
+        }
+
+        let overflow = read_overflow_extents(disc, &volume, extents_file)?;
+        let catalog = volume.fork_extents(
+            catalog_extents,
+            catalog_size,
+            DATA_FORK,
+            CATALOG_FILE_ID,
+            &overflow,
+        );
+
+        let mut directories: HashMap<u32, Vec<Entry>> = HashMap::new();
+        let mut root = None;
+
+        for record in read_leaf_records(disc, &catalog)? {
+            let Some((parent, name, data)) = volume.catalog_key(&record) else {
+                continue;
+            };
+            let Some(entry) = volume.catalog_entry(disc, data, name, &overflow)? else {
+                continue;
+            };
+
+            if parent == ROOT_PARENT_ID {
+                if volume.plus {
+                    volume_name = entry.name.clone();
+                }
+                root = Some(entry);
+            } else if !is_private(&entry.name) {
+                directories.entry(parent).or_default().push(entry);
+            }
+        }
+
+        let mut root = root.ok_or_else(|| bad_hfs("there's no root directory"))?;
+        root.name = String::new();
+
+        Ok(Self {
+            plus: volume.plus,
+            volume_name,
+            root,
+            directories,
+        })
+    }
+}
+
+impl Filesystem for Hfs {
+    fn description(&self) -> String {
+        if self.plus { "HFS+" } else { "HFS" }.to_string()
+    }
+
+    fn kind(&self) -> FilesystemType {
+        FilesystemType::Hfs
+    }
+
+    fn volume_name(&self) -> &str {
+        &self.volume_name
+    }
+
+    fn root(&self) -> Entry {
+        self.root.clone()
+    }
+
+    fn read_dir(&self, _disc: &mut DiscReader, dir: &Entry) -> Result<Vec<Entry>> {
+        let id = dir.mac.as_ref().map_or(ROOT_ID, |mac| mac.catalog_id);
+        Ok(self.directories.get(&id).cloned().unwrap_or_default())
+    }
+}
+
+impl Volume {
+    /// Turn a list of (first block, number of blocks) pairs into extents on the disc, cut down
+    /// to `size` bytes
+    fn extents(&self, blocks: &[(u32, u32)], size: u64) -> Vec<Extent> {
+        let extents = blocks
+            .iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|&(start, count)| Extent::Stored {
+                offset: self.first_block + start as u64 * self.block_size,
+                length: count as u64 * self.block_size,
+            })
+            .collect();
+
+        truncate_extents(extents, size)
+    }
+
+    /// The extents of a fork, including any which didn't fit in its catalog record and were put
+    /// in the extents overflow file
+    fn fork_extents(
+        &self,
+        mut blocks: Vec<(u32, u32)>,
+        size: u64,
+        fork: u8,
+        id: u32,
+        overflow: &OverflowExtents,
+    ) -> Vec<Extent> {
+        let mut num_blocks: u64 = blocks.iter().map(|(_, count)| *count as u64).sum();
+
+        for (start, extents) in overflow.get(&(fork, id)).into_iter().flatten() {
+            if num_blocks * self.block_size >= size {
+                break;
+            }
+            if *start as u64 == num_blocks {
+                blocks.extend(extents);
+                num_blocks += extents.iter().map(|(_, count)| *count as u64).sum::<u64>();
+            }
+        }
+
+        self.extents(&blocks, size)
+    }
+
+    /// Split a catalog record into its parent directory's ID, its name, and its data
+    fn catalog_key<'a>(&self, record: &'a [u8]) -> Option<(u32, String, &'a [u8])> {
+        if self.plus {
+            let key_len = u16_be(record.get(0..2)?) as usize;
+            let parent = u32_be(record.get(2..6)?);
+            let name_len = u16_be(record.get(6..8)?) as usize;
+            let name: Vec<u16> = record
+                .get(8..8 + name_len * 2)?
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            let name = String::from_utf16_lossy(&name);
+
+            Some((parent, name, record.get(2 + key_len..)?))
+        } else {
+            let key_len = *record.first()? as usize;
+            let parent = u32_be(record.get(2..6)?);
+            let name_len = *record.get(6)? as usize;
+            let name = mac_roman(record.get(7..7 + name_len)?);
+
+            // Record data starts on an even byte
+            Some((parent, name, record.get((1 + key_len + 1) & !1..)?))
+        }
+    }
+
+    /// Turn a catalog record into an entry. Thread records, which lead from an ID back to the
+    /// entry's name, are skipped.
+    fn catalog_entry(
+        &self,
+        disc: &mut DiscReader,
+        data: &[u8],
+        name: String,
+        overflow: &OverflowExtents,
+    ) -> Result<Option<Entry>> {
+        let record_type = match (data, self.plus) {
+            ([high, low, ..], true) => u16::from_be_bytes([*high, *low]),
+            ([record_type, ..], false) => *record_type as u16,
+            _ => return Ok(None),
+        };
+
+        // The offsets of the fields each record type has, which differ between the two versions
+        let (id, created, modified, user_info, extra_info, len) = match (record_type, self.plus) {
+            (FOLDER_RECORD, false) => (6, 10, 14, 22, 38, 70),
+            (FILE_RECORD, false) => (20, 44, 48, 4, 56, 102),
+            (FOLDER_RECORD, true) => (8, 12, 16, 48, 64, 88),
+            (FILE_RECORD, true) => (8, 12, 16, 48, 64, 248),
+            _ => return Ok(None),
+        };
+
+        if data.len() < len {
+            return Err(bad_hfs("a catalog record is too short"));
+        }
+
+        let mut finder_info = [0; 32];
+        finder_info[0..16].copy_from_slice(&data[user_info..user_info + 16]);
+        finder_info[16..32].copy_from_slice(&data[extra_info..extra_info + 16]);
+        let catalog_id = u32_be(&data[id..id + 4]);
+
+        let mut entry = Entry {
+            // The Mac used colons to separate paths, so names can have slashes in them
+            name: name.replace('/', ":"),
+            kind: EntryKind::Directory,
+            size: 0,
+            modified: mac_date(u32_be(&data[modified..modified + 4])),
+            extents: Vec::new(),
+            mac: Some(MacMetadata {
+                catalog_id,
+                finder_info,
+                created: mac_date(u32_be(&data[created..created + 4])),
+                resource_fork: Vec::new(),
+            }),
+        };
+
+        if record_type == FILE_RECORD {
+            let (data_size, data_extents, resource_size, resource_extents) = if self.plus {
+                let (data_fork, resource_fork) = (&data[88..168], &data[168..248]);
+                (
+                    u64_be(data_fork),
+                    plus_extents(&data_fork[16..]),
+                    u64_be(resource_fork),
+                    plus_extents(&resource_fork[16..]),
+                )
+            } else {
+                (
+                    u32_be(&data[26..30]) as u64,
+                    hfs_extents(&data[74..86]),
+                    u32_be(&data[36..40]) as u64,
+                    hfs_extents(&data[86..98]),
+                )
+            };
+
+            entry.kind = EntryKind::File;
+            entry.size = data_size;
+            entry.extents =
+                self.fork_extents(data_extents, data_size, DATA_FORK, catalog_id, overflow);
+            if let Some(mac) = &mut entry.mac {
+                mac.resource_fork = self.fork_extents(
+                    resource_extents,
+                    resource_size,
+                    RESOURCE_FORK,
+                    catalog_id,
+                    overflow,
+                );
+            }
+
+            // HFS+ keeps Unix permissions, and symbolic links keep their target in the data fork
+            let mode = u16_be(&data[42..44]);
+            if self.plus && mode & 0xF000 == 0xA000 {
+                let target = disc.read_extents(&entry.extents)?;
+                entry.kind = EntryKind::Symlink(String::from_utf8_lossy(&target).into_owned());
+            }
+        }
+
+        Ok(Some(entry))
+    }
+}
+
+/// Find the HFS partition in an Apple partition map at the start of a data track. Partition map
+/// entries are spaced by the block size given in the driver descriptor in the first block, which
+/// is usually 512 bytes but can be 2048 on CDs.
+fn find_partition(disc: &mut DiscReader, track_offset: u64) -> Result<Option<u64>> {
+    let mut block = [0; 512];
+    disc.read_at(track_offset, &mut block)?;
+    if &block[0..2] != b"ER" {
+        return Ok(None);
+    }
+
+    let block_size = match u16_be(&block[2..4]) as u64 {
+        0 => 512,
+        size => size,
+    };
+
+    let mut num_partitions = 1;
+    let mut i = 1;
+    while i <= num_partitions.min(MAX_PARTITIONS) {
+        if disc
+            .read_at(track_offset + i as u64 * block_size, &mut block)
+            .is_err()
+            || &block[0..2] != b"PM"
+        {
+            break;
+        }
+
+        num_partitions = u32_be(&block[4..8]);
+        if block[48..].starts_with(b"Apple_HFS") {
+            let start = u32_be(&block[8..12]) as u64;
+            return Ok(Some(track_offset + start * block_size));
+        }
+
+        i += 1;
+    }
+
+    Ok(None)
+}
+
+/// Read every record from the leaf nodes of a B-tree, in order
+fn read_leaf_records(disc: &mut DiscReader, extents: &[Extent]) -> Result<Vec<Vec<u8>>> {
+    let tree = disc.read_extents(extents)?;
+    if tree.len() < 512 {
+        return Ok(Vec::new());
+    }
+
+    // The header node is always first, and says how big every node is
+    let node_size = u16_be(&tree[32..34]) as usize;
+    let mut node_number = u32_be(&tree[24..28]) as usize;
+    if node_size < 512 {
+        return Err(bad_hfs("a B-tree's node size is invalid"));
+    }
+
+    let mut records = Vec::new();
+    let mut nodes_read = 0;
+
+    while node_number != 0 {
+        let node = tree
+            .get(node_number * node_size..(node_number + 1) * node_size)
+            .ok_or_else(|| bad_hfs("a B-tree node is past the end of the tree"))?;
+        if node[8] as i8 != LEAF_NODE {
+            return Err(bad_hfs("a B-tree leaf node is missing"));
+        }
+
+        // Record offsets are stored backwards from the end of the node, with one more giving
+        // where the free space starts
+        let num_records = u16_be(&node[10..12]) as usize;
+        let offset = |i: usize| {
+            let pos = node_size.checked_sub(2 * (i + 1))?;
+            Some(u16_be(&node[pos..pos + 2]) as usize)
+        };
+
+        for i in 0..num_records {
+            let (Some(start), Some(end)) = (offset(i), offset(i + 1)) else {
+                break;
+            };
+            if let Some(record) = node.get(start..end) {
+                records.push(record.to_vec());
+            }
+        }
+
+        // Nodes link to the next one, which could loop forever on a broken disc
+        nodes_read += 1;
+        if nodes_read > tree.len() / node_size {
+            return Err(bad_hfs("a B-tree's leaf nodes loop"));
+        }
+        node_number = u32_be(&node[0..4]) as usize;
+    }
+
+    Ok(records)
+}
+
+/// Read the extents overflow file, which holds the extents of fragmented files which didn't fit
+/// in their catalog records
+fn read_overflow_extents(
+    disc: &mut DiscReader,
+    volume: &Volume,
+    extents_file: &[Extent],
+) -> Result<OverflowExtents> {
+    let mut overflow: OverflowExtents = HashMap::new();
+
+    for record in read_leaf_records(disc, extents_file)? {
+        let (fork, id, start, extents) = if volume.plus {
+            if record.len() < 76 {
+                continue;
+            }
+            let start = u32_be(&record[8..12]);
+            (
+                record[2],
+                u32_be(&record[4..8]),
+                start,
+                plus_extents(&record[12..76]),
+            )
+        } else {
+            if record.len() < 20 {
+                continue;
+            }
+            let start = u16_be(&record[6..8]) as u32;
+            (
+                record[1],
+                u32_be(&record[2..6]),
+                start,
+                hfs_extents(&record[8..20]),
+            )
+        };
+
+        overflow
+            .entry((fork, id))
+            .or_default()
+            .push((start, extents));
+    }
+
+    for records in overflow.values_mut() {
+        records.sort_by_key(|(start, _)| *start);
+    }
+
+    Ok(overflow)
+}
+
+/// Whether an entry is one of the hidden directories HFS+ keeps hard links in
+fn is_private(name: &str) -> bool {
+    name.starts_with('\0') || name == ".HFS+ Private Directory Data\r"
+}
+
+/// An HFS extent record, which holds three extents of 16-bit block numbers and counts
+fn hfs_extents(bytes: &[u8]) -> Vec<(u32, u32)> {
+    bytes
+        .chunks_exact(4)
+        .map(|extent| (u16_be(&extent[0..2]) as u32, u16_be(&extent[2..4]) as u32))
+        .collect()
+}
+
+/// An HFS+ extent record, which holds eight extents of 32-bit block numbers and counts
+fn plus_extents(bytes: &[u8]) -> Vec<(u32, u32)> {
+    bytes[..64]
+        .chunks_exact(8)
+        .map(|extent| (u32_be(&extent[0..4]), u32_be(&extent[4..8])))
+        .collect()
+}
+
+/// Convert a Mac date, in seconds since 1904. Zero means the date wasn't recorded.
+fn mac_date(seconds: u32) -> Option<SystemTime> {
+    match seconds {
+        0 => None,
+        seconds => from_unix_seconds(seconds as i64 - MAC_EPOCH_OFFSET),
+    }
+}
+
+fn mac_roman(bytes: &[u8]) -> String {
+    MACINTOSH.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+fn u16_be(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn u32_be(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes(bytes[0..4].try_into().unwrap())
+}
+
+fn u64_be(bytes: &[u8]) -> u64 {
+    u64::from_be_bytes(bytes[0..8].try_into().unwrap())
+}
+
+fn bad_hfs(message: &str) -> Error {
+    Error::FilesystemParse(format!("bad HFS filesystem: {message}"))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::{
+        filesystem::{format_time, tests::write_at},
+        testing::TestDisc,
+    };
+
+    const NODE_SIZE: usize = 512;
+
+    /// 2000-01-01 00:00:00 as a Mac date
+    const MAC_DATE: u32 = 3_029_529_600;
+
+    /// An HFS catalog record: its key, then its data starting on an even byte
+    fn catalog_record(parent: u32, name: &str, data: &[u8]) -> Vec<u8> {
+        let key_len = 6 + name.len();
+        let mut record = vec![key_len as u8, 0];
+        record.extend_from_slice(&parent.to_be_bytes());
+        record.push(name.len() as u8);
+        record.extend_from_slice(name.as_bytes());
+        record.resize((key_len + 2) & !1, 0);
+        record.extend_from_slice(data);
+
+        record
+    }
+
+    fn folder(id: u32) -> Vec<u8> {
+        let mut data = vec![0; 70];
+        data[0] = FOLDER_RECORD as u8;
+        data[6..10].copy_from_slice(&id.to_be_bytes());
+        data[10..14].copy_from_slice(&MAC_DATE.to_be_bytes());
+        data[14..18].copy_from_slice(&MAC_DATE.to_be_bytes());
+
+        data
+    }
+
+    /// A file whose data fork is `data_size` bytes in block 1, and whose resource fork is
+    /// `resource_size` bytes in block 2
+    fn file(id: u32, data_size: u32, resource_size: u32) -> Vec<u8> {
+        let mut data = vec![0; 102];
+        data[0] = FILE_RECORD as u8;
+        data[4..12].copy_from_slice(b"TEXTttxt");
+        data[20..24].copy_from_slice(&id.to_be_bytes());
+        data[26..30].copy_from_slice(&data_size.to_be_bytes());
+        data[36..40].copy_from_slice(&resource_size.to_be_bytes());
+        data[44..48].copy_from_slice(&MAC_DATE.to_be_bytes());
+        data[48..52].copy_from_slice(&(MAC_DATE + 61).to_be_bytes());
+        data[74..78].copy_from_slice(&[0, 1, 0, 1]);
+        data[86..90].copy_from_slice(&[0, 2, 0, 1]);
+
+        data
+    }
+
+    /// A B-tree holding a header node and one leaf node with `records` in it
+    fn catalog(records: &[Vec<u8>]) -> Vec<u8> {
+        let mut tree = vec![0; NODE_SIZE * 4];
+        tree[8] = 1;
+        tree[24..28].copy_from_slice(&1u32.to_be_bytes());
+        tree[32..34].copy_from_slice(&(NODE_SIZE as u16).to_be_bytes());
+
+        let leaf = &mut tree[NODE_SIZE..NODE_SIZE * 2];
+        leaf[8] = LEAF_NODE as u8;
+        leaf[10..12].copy_from_slice(&(records.len() as u16).to_be_bytes());
+
+        let mut offset = 14;
+        for (i, record) in records.iter().enumerate() {
+            leaf[offset..offset + record.len()].copy_from_slice(record);
+            leaf[NODE_SIZE - 2 * (i + 1)..][..2].copy_from_slice(&(offset as u16).to_be_bytes());
+            offset += record.len();
+        }
+        leaf[NODE_SIZE - 2 * (records.len() + 1)..][..2]
+            .copy_from_slice(&(offset as u16).to_be_bytes());
+
+        tree
+    }
+
+    /// An HFS volume with 2048 byte allocation blocks starting at sector 1. The catalog is in
+    /// block 0, and ReadMe's forks are in blocks 1 and 2.
+    pub fn volume() -> Vec<u8> {
+        let mut image = Vec::new();
+
+        let mut mdb = vec![0; 512];
+        mdb[0..2].copy_from_slice(b"BD");
+        mdb[20..24].copy_from_slice(&2048u32.to_be_bytes());
+        mdb[28..30].copy_from_slice(&4u16.to_be_bytes());
+        mdb[36] = 9;
+        mdb[37..46].copy_from_slice(b"Test Disk");
+        mdb[146..150].copy_from_slice(&2048u32.to_be_bytes());
+        mdb[150..154].copy_from_slice(&[0, 0, 0, 1]);
+        write_at(&mut image, 0, VOLUME_HEADER_OFFSET as usize, &mdb);
+
+        // Thread records, which are type 3, are skipped
+        let records = [
+            catalog_record(ROOT_PARENT_ID, "Test Disk", &folder(ROOT_ID)),
+            catalog_record(ROOT_ID, "", &[3, 0]),
+            catalog_record(ROOT_ID, "Docs", &folder(17)),
+            catalog_record(ROOT_ID, "Read/Me", &file(16, 13, 5)),
+        ];
+        write_at(&mut image, 1, 0, &catalog(&records));
+        write_at(&mut image, 2, 0, b"Hello, world!");
+        write_at(&mut image, 3, 0, b"rsrc!");
+
+        image
+    }
+
+    #[test]
+    fn reads_a_volume() {
+        let disc = TestDisc::new("hfs", &volume(), false);
+        let mut reader = disc.reader();
+        let hfs = Hfs::open(&mut reader, 0).unwrap().unwrap();
+
+        assert_eq!(hfs.description(), "HFS");
+        assert_eq!(hfs.volume_name(), "Test Disk");
+
+        let root = hfs.read_dir(&mut reader, &hfs.root()).unwrap();
+        let names: Vec<&str> = root.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["Docs", "Read:Me"]);
+        assert_eq!(root[0].kind, EntryKind::Directory);
+
+        let readme = &root[1];
+        let mac = readme.mac.as_ref().unwrap();
+        assert_eq!(readme.kind, EntryKind::File);
+        assert_eq!(readme.size, 13);
+        assert_eq!(&mac.finder_info[0..8], b"TEXTttxt");
+        assert_eq!(mac.catalog_id, 16);
+        assert_eq!(format_time(mac.created.unwrap()), "2000-01-01 00:00:00");
+        assert_eq!(format_time(readme.modified.unwrap()), "2000-01-01 00:01:01");
+
+        let mut data = Vec::new();
+        hfs.read_file(&mut reader, readme, &mut data).unwrap();
+        assert_eq!(data, b"Hello, world!");
+        assert_eq!(reader.read_extents(&mac.resource_fork).unwrap(), b"rsrc!");
+    }
+
+    #[test]
+    fn finds_volumes_in_a_partition_map() {
+        // The volume is moved along by four 512 byte blocks, which a partition map points at
+        let mut image = vec![0; USER_DATA_SIZE];
+        image[0..2].copy_from_slice(b"ER");
+        image[2..4].copy_from_slice(&512u16.to_be_bytes());
+        image[512..514].copy_from_slice(b"PM");
+        image[516..520].copy_from_slice(&1u32.to_be_bytes());
+        image[520..524].copy_from_slice(&4u32.to_be_bytes());
+        image[560..569].copy_from_slice(b"Apple_HFS");
+        image.extend(volume());
+
+        let disc = TestDisc::new("hfs-partition", &image, true);
+        let mut reader = disc.reader();
+        let hfs = Hfs::open(&mut reader, 0).unwrap().unwrap();
+
+        let entry = hfs.lookup(&mut reader, "read:me").unwrap();
+        assert_eq!(
+            reader.read_extents(&entry.extents).unwrap(),
+            b"Hello, world!"
+        );
+    }
+
+    #[test]
+    fn finds_nothing_without_a_volume() {
+        let disc = TestDisc::new("hfs-none", &[0; USER_DATA_SIZE * 2], false);
+        assert!(Hfs::open(&mut disc.reader(), 0).unwrap().is_none());
+    }
+}
@@ -0,0 +1,770 @@
+use super::{
+    find_entry, local_to_utc, lookup_by_walking, path_components, system_time, DiscReader, Entry,
+    EntryKind, Extent, Filesystem,
+};
+use crate::{
+    args::FilesystemType,
+    error::{Error, Result},
+    sector::USER_DATA_SIZE,
+};
+use std::time::SystemTime;
+
+// ISO 9660 is described in ECMA-119 (https://ecma-international.org/publications-and-standards/standards/ecma-119/).
+// A disc starts with a set of volume descriptors at sector 16, the first of which describes the
+// filesystem and points at its root directory and path table. Windows discs usually also carry a
+// Joliet descriptor, which describes a second directory tree with Unicode names, and discs made
+// on Unix usually put Rock Ridge entries in the system use area of each directory record, which
+// give files their full names, permissions, and timestamps. See
+// https://en.wikipedia.org/wiki/Rock_Ridge for links to the Rock Ridge and SUSP specifications.
+
+/// The sector holding the first volume descriptor, relative to the start of the session
+const VOLUME_DESCRIPTOR_START: i64 = 16;
+
+/// The most volume descriptors to look through before giving up on finding a terminator
+const MAX_VOLUME_DESCRIPTORS: i64 = 64;
+
+const PRIMARY_VOLUME_DESCRIPTOR: u8 = 1;
+const SUPPLEMENTARY_VOLUME_DESCRIPTOR: u8 = 2;
+const VOLUME_DESCRIPTOR_TERMINATOR: u8 = 255;
+
+/// The escape sequences which mark a supplementary volume descriptor as Joliet, for UCS-2 levels
+/// 1, 2, and 3
+const JOLIET_ESCAPES: [&[u8]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+/// Directory record flags
+const FLAG_DIRECTORY: u8 = 0x02;
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+/// The size of the System Use Sharing Protocol data that some XA discs put ahead of any Rock
+/// Ridge entries
+const XA_SYSTEM_USE_SIZE: usize = 14;
+
+/// How many continuation areas to follow in one directory record, to avoid looping forever on a
+/// broken disc
+const MAX_CONTINUATIONS: usize = 16;
+
+/// Which of the directory trees on the disc is being read
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Extension {
+    None,
+    Joliet,
+
+    /// Rock Ridge, along with the number of bytes to skip at the start of each system use area
+    RockRidge(usize),
+}
+
+/// An entry in a path table, which lists every directory on the disc along with its parent
+#[derive(Debug)]
+struct PathTableEntry {
+    name: String,
+    lba: u32,
+
+    /// The 1-based index of the parent directory's entry. The root is its own parent.
+    parent: usize,
+}
+
+pub struct Iso9660 {
+    volume_name: String,
+    extension: Extension,
+    root: Entry,
+    path_table: Vec<PathTableEntry>,
+}
+
+impl Iso9660 {
+    /// Read the volume descriptors of a session starting at `start_lba`. Returns `None` if there
+    /// isn't an ISO 9660 filesystem there.
+    pub fn open(disc: &mut DiscReader, start_lba: i64) -> Result<Option<Self>> {
+        let mut primary = None;
+        let mut joliet = None;
+        let mut buf = [0; USER_DATA_SIZE];
+
+        for i in 0..MAX_VOLUME_DESCRIPTORS {
+            let lba = start_lba + VOLUME_DESCRIPTOR_START + i;
+            if disc.read_sector(lba, &mut buf).is_err() || &buf[1..6] != b"CD001" {
+                break;
+            }
+
+            match buf[0] {
+                PRIMARY_VOLUME_DESCRIPTOR if primary.is_none() => primary = Some(buf),
+                SUPPLEMENTARY_VOLUME_DESCRIPTOR if JOLIET_ESCAPES.contains(&&buf[88..91]) => {
+                    joliet = Some(buf)
+                }
+                VOLUME_DESCRIPTOR_TERMINATOR => break,
+                _ => {}
+            }
+        }
+
+        let Some(primary) = primary else {
+            return Ok(None);
+        };
+
+        // Rock Ridge names are the most complete, then Joliet's, then the plain 8.3 names
+        let root = parse_record(&primary[156..190], Extension::None)?;
+        let (descriptor, extension) = match rock_ridge_skip(disc, &root)? {
+            Some(skip) => (primary, Extension::RockRidge(skip)),
+            None => match joliet {
+                Some(joliet) => (joliet, Extension::Joliet),
+                None => (primary, Extension::None),
+            },
+        };
+
+        let mut root = parse_record(&descriptor[156..190], extension)?;
+        root.entry.name = String::new();
+
+        let volume_name = match extension {
+            Extension::Joliet => ucs2(&descriptor[40..72]),
+            _ => String::from_utf8_lossy(&descriptor[40..72]).into_owned(),
+        };
+
+        let path_table_size = u32_le(&descriptor[132..136]) as u64;
+        let path_table_lba = u32_le(&descriptor[140..144]);
+        let path_table =
+            disc.read_extents(&[Extent::from_sector(path_table_lba, path_table_size)])?;
+
+        Ok(Some(Self {
+            volume_name: volume_name.trim_end().to_string(),
+            extension,
+            root: root.entry,
+            path_table: parse_path_table(&path_table, extension)?,
+        }))
+    }
+
+    /// Find the directory at `path` using the path table, without reading any of the directories
+    /// leading up to it. Returns the sector holding the directory's records.
+    fn lookup_dir(&self, path: &[&str]) -> Option<u32> {
+        let mut index = 1;
+
+        for name in path {
+            let children: Vec<(usize, &PathTableEntry)> = self
+                .path_table
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| (i + 1, entry))
+                .filter(|(i, entry)| entry.parent == index && *i != 1)
+                .collect();
+
+            let exact = children.iter().find(|(_, entry)| entry.name == *name);
+            let child = exact.or_else(|| {
+                children
+                    .iter()
+                    .find(|(_, entry)| entry.name.eq_ignore_ascii_case(name))
+            })?;
+
+            index = child.0;
+        }
+
+        self.path_table.get(index - 1).map(|entry| entry.lba)
+    }
+
+    /// The directory whose records start at `lba`, named `name`. Its size and timestamp come
+    /// from its own "." record.
+    fn dir_at(&self, disc: &mut DiscReader, lba: u32, name: &str) -> Result<Entry> {
+        let mut buf = [0; USER_DATA_SIZE];
+        disc.read_sector(lba as i64, &mut buf)?;
+
+        let len = buf[0] as usize;
+        let mut dir = parse_record(&buf[..len.max(34)], self.extension)?.entry;
+        dir.name = name.to_string();
+
+        Ok(dir)
+    }
+}
+
+impl Filesystem for Iso9660 {
+    fn description(&self) -> String {
+        match self.extension {
+            Extension::None => "ISO 9660".to_string(),
+            Extension::Joliet => "ISO 9660 with Joliet".to_string(),
+            Extension::RockRidge(_) => "ISO 9660 with Rock Ridge".to_string(),
+        }
+    }
+
+    fn kind(&self) -> FilesystemType {
+        FilesystemType::Iso9660
+    }
+
+    fn volume_name(&self) -> &str {
+        &self.volume_name
+    }
+
+    fn root(&self) -> Entry {
+        self.root.clone()
+    }
+
+    fn read_dir(&self, disc: &mut DiscReader, dir: &Entry) -> Result<Vec<Entry>> {
+        let data = disc.read_extents(&dir.extents)?;
+        let mut entries: Vec<Entry> = Vec::new();
+        let mut continues_previous = false;
+
+        // Records never cross a sector boundary; the rest of a sector is zero-filled instead
+        for sector in data.chunks(USER_DATA_SIZE) {
+            let mut pos = 0;
+
+            while pos < sector.len() && sector[pos] != 0 {
+                let len = sector[pos] as usize;
+                let bytes = sector.get(pos..pos + len).ok_or_else(|| bad_record(pos))?;
+                pos += len;
+
+                let mut record = parse_record(bytes, self.extension)?;
+                if record.is_self_or_parent {
+                    continue;
+                }
+
+                if record.has_continuation {
+                    let system_use = read_system_use(disc, bytes, self.extension)?;
+                    record.apply(system_use);
+                }
+
+                if let Some(lba) = record.child_link {
+                    record.entry = self.dir_at(disc, lba, &record.entry.name)?;
+                }
+
+                if record.relocated {
+                    continue;
+                }
+
+                // Files over 4 GiB are split across several records with the same name
+                match entries.last_mut() {
+                    Some(previous) if continues_previous => {
+                        previous.size += record.entry.size;
+                        previous.extents.extend(record.entry.extents);
+                    }
+                    _ => entries.push(record.entry),
+                }
+
+                continues_previous = record.multi_extent;
+            }
+        }
+
+        Ok(entries)
+    }
+
+    fn lookup(&self, disc: &mut DiscReader, path: &str) -> Result<Entry> {
+        let components: Vec<&str> = path_components(path).collect();
+        let Some((name, parents)) = components.split_last() else {
+            return Ok(self.root());
+        };
+
+        // Rock Ridge names can differ from the ones in the path table, so the tree has to be
+        // walked instead
+        if let Extension::RockRidge(_) = self.extension {
+            return lookup_by_walking(self, disc, path);
+        }
+
+        let parent_lba = self
+            .lookup_dir(parents)
+            .ok_or_else(|| Error::NoSuchFile(path.to_string()))?;
+        let parent = self.dir_at(disc, parent_lba, "")?;
+
+        let entries = self.read_dir(disc, &parent)?;
+        find_entry(entries, name).ok_or_else(|| Error::NoSuchFile(path.to_string()))
+    }
+}
+
+/// A directory record, along with anything learned from its Rock Ridge entries
+struct Record {
+    entry: Entry,
+    is_self_or_parent: bool,
+    multi_extent: bool,
+
+    /// Rock Ridge moves directories nested too deeply for ISO 9660 elsewhere. The directory is
+    /// left with a placeholder file pointing at where it went, and the moved directory is marked
+    /// so that it can be hidden from its new parent.
+    child_link: Option<u32>,
+    relocated: bool,
+
+    /// Whether some of the record's Rock Ridge entries are stored in a continuation area
+    has_continuation: bool,
+}
+
+impl Record {
+    /// Use the Rock Ridge entries in a record's system use area to fill in its details
+    fn apply(&mut self, system_use: SystemUse) {
+        if let Some(name) = &system_use.name {
+            self.entry.name = name.clone();
+        }
+
+        if let Some(modified) = system_use.modified {
+            self.entry.modified = Some(modified);
+        }
+
+        if let Some(target) = &system_use.symlink {
+            self.entry.kind = EntryKind::Symlink(target.clone());
+        }
+
+        if system_use.child_link.is_some() {
+            self.entry.kind = EntryKind::Directory;
+        }
+
+        self.child_link = system_use.child_link;
+        self.relocated = system_use.relocated;
+        self.has_continuation = system_use.continuation.is_some();
+    }
+}
+
+/// The Rock Ridge entries found in a record's system use area
+#[derive(Debug, Default)]
+struct SystemUse {
+    name: Option<String>,
+    modified: Option<SystemTime>,
+    symlink: Option<String>,
+    child_link: Option<u32>,
+    relocated: bool,
+    sharing_protocol_skip: Option<usize>,
+
+    /// Where the rest of the entries are stored, when they didn't fit in the record: the sector,
+    /// the offset within it, and the length
+    continuation: Option<(u32, u32, u32)>,
+}
+
+/// Parse a directory record. Rock Ridge entries are only read from the record itself; any
+/// continuation areas are read separately by `read_system_use`.
+fn parse_record(bytes: &[u8], extension: Extension) -> Result<Record> {
+    if bytes.len() < 34 {
+        return Err(bad_record(bytes.len()));
+    }
+
+    let lba = u32_le(&bytes[2..6]);
+    let size = u32_le(&bytes[10..14]) as u64;
+    let flags = bytes[25];
+    let name_len = bytes[32] as usize;
+    let raw_name = bytes.get(33..33 + name_len).ok_or_else(|| bad_record(33))?;
+
+    let is_self_or_parent = name_len == 1 && raw_name[0] <= 1;
+    let name = match extension {
+        _ if is_self_or_parent => String::new(),
+        Extension::Joliet => strip_version(&ucs2(raw_name)),
+        _ => strip_version(&String::from_utf8_lossy(raw_name)),
+    };
+
+    let kind = if flags & FLAG_DIRECTORY != 0 {
+        EntryKind::Directory
+    } else {
+        EntryKind::File
+    };
+
+    let mut record = Record {
+        entry: Entry {
+            name,
+            kind,
+            size,
+            modified: record_time(&bytes[18..25]),
+            extents: vec![Extent::from_sector(lba, size)],
+            mac: None,
+        },
+        is_self_or_parent,
+        multi_extent: flags & FLAG_MULTI_EXTENT != 0,
+        child_link: None,
+        relocated: false,
+        has_continuation: false,
+    };
+
+    if let Extension::RockRidge(skip) = extension {
+        let area = system_use_area(bytes).get(skip..).unwrap_or_default();
+        let mut system_use = SystemUse::default();
+        parse_system_use(area, &mut system_use);
+        record.apply(system_use);
+    }
+
+    Ok(record)
+}
+
+/// The system use area at the end of a directory record, after its name and any padding
+fn system_use_area(bytes: &[u8]) -> &[u8] {
+    let name_len = bytes[32] as usize;
+    let start = 33 + name_len + (name_len + 1) % 2;
+    let area = bytes.get(start..).unwrap_or_default();
+
+    // Some XA discs put their own data ahead of the SUSP entries
+    match area.get(6..8) {
+        Some(b"XA") if area.len() >= XA_SYSTEM_USE_SIZE => &area[XA_SYSTEM_USE_SIZE..],
+        _ => area,
+    }
+}
+
+/// Read all of a record's Rock Ridge entries, following any continuation areas
+fn read_system_use(disc: &mut DiscReader, bytes: &[u8], extension: Extension) -> Result<SystemUse> {
+    let Extension::RockRidge(skip) = extension else {
+        return Ok(SystemUse::default());
+    };
+
+    let mut system_use = SystemUse::default();
+    let mut area = system_use_area(bytes)
+        .get(skip..)
+        .unwrap_or_default()
+        .to_vec();
+
+    for _ in 0..MAX_CONTINUATIONS {
+        parse_system_use(&area, &mut system_use);
+
+        let Some((lba, offset, length)) = system_use.continuation.take() else {
+            break;
+        };
+
+        area = vec![0; length as usize];
+        let offset = lba as u64 * USER_DATA_SIZE as u64 + offset as u64;
+        disc.read_at(offset, &mut area)?;
+    }
+
+    Ok(system_use)
+}
+
+/// Parse the SUSP entries in a system use area, adding what they say to `system_use`
+fn parse_system_use(mut area: &[u8], system_use: &mut SystemUse) {
+    while area.len() >= 4 {
+        let len = area[2] as usize;
+        if len < 4 || len > area.len() {
+            break;
+        }
+
+        let (entry, rest) = area.split_at(len);
+        let data = &entry[4..];
+        area = rest;
+
+        match &entry[0..2] {
+            b"SP" if data.len() >= 3 && data[0..2] == [0xBE, 0xEF] => {
+                system_use.sharing_protocol_skip = Some(data[2] as usize);
+            }
+            b"CE" if data.len() >= 24 => {
+                let lba = u32_le(&data[0..4]);
+                let offset = u32_le(&data[8..12]);
+                let length = u32_le(&data[16..20]);
+                system_use.continuation = Some((lba, offset, length));
+            }
+            // Flags for the current and parent directory only appear on "." and ".."
+            b"NM" if !data.is_empty() && data[0] & 0x06 == 0 => {
+                let part = String::from_utf8_lossy(&data[1..]);
+                system_use
+                    .name
+                    .get_or_insert_with(String::new)
+                    .push_str(&part);
+            }
+            b"TF" if !data.is_empty() => system_use.modified = rock_ridge_time(data),
+            b"SL" if !data.is_empty() => {
+                let target = symlink_target(&data[1..]);
+                let symlink = system_use.symlink.get_or_insert_with(String::new);
+                if !symlink.is_empty() && !symlink.ends_with('/') {
+                    symlink.push('/');
+                }
+                symlink.push_str(&target);
+            }
+            b"CL" if data.len() >= 4 => system_use.child_link = Some(u32_le(&data[0..4])),
+            b"RE" => system_use.relocated = true,
+            b"ST" => break,
+            _ => {}
+        }
+    }
+}
+
+/// Whether the root directory has Rock Ridge entries, and if so how many bytes to skip at the
+/// start of each system use area. This is given by the SP entry in the root's "." record.
+fn rock_ridge_skip(disc: &mut DiscReader, root: &Record) -> Result<Option<usize>> {
+    let mut buf = [0; USER_DATA_SIZE];
+    let Some(Extent::Stored { offset, .. }) = root.entry.extents.first() else {
+        return Ok(None);
+    };
+    disc.read_sector((offset / USER_DATA_SIZE as u64) as i64, &mut buf)?;
+
+    let len = buf[0] as usize;
+    if len < 34 {
+        return Ok(None);
+    }
+
+    let mut system_use = SystemUse::default();
+    parse_system_use(system_use_area(&buf[..len]), &mut system_use);
+
+    Ok(system_use.sharing_protocol_skip)
+}
+
+/// Parse the components of an SL entry into a path
+fn symlink_target(mut components: &[u8]) -> String {
+    let mut parts = Vec::new();
+
+    while components.len() >= 2 {
+        let flags = components[0];
+        let len = (components[1] as usize).min(components.len() - 2);
+        let content = &components[2..2 + len];
+        components = &components[2 + len..];
+
+        let part = match flags {
+            f if f & 0x02 != 0 => ".".to_string(),
+            f if f & 0x04 != 0 => "..".to_string(),
+            f if f & 0x08 != 0 => String::new(),
+            _ => String::from_utf8_lossy(content).into_owned(),
+        };
+
+        parts.push(part);
+    }
+
+    match parts.as_slice() {
+        [root] if root.is_empty() => "/".to_string(),
+        _ => parts.join("/"),
+    }
+}
+
+/// The modification time in a TF entry. Each time the entry holds is flagged, in the order
+/// creation, modification, access, and attribute change. Bit 7 means they are in the 17-byte
+/// format used by volume descriptors rather than the 7-byte one used by directory records.
+fn rock_ridge_time(data: &[u8]) -> Option<SystemTime> {
+    let flags = data[0];
+    let size = if flags & 0x80 != 0 { 17 } else { 7 };
+    let index = (flags & 0x01) as usize;
+
+    if flags & 0x02 == 0 {
+        return None;
+    }
+
+    let bytes = data.get(1 + index * size..1 + (index + 1) * size)?;
+    if size == 7 {
+        record_time(bytes)
+    } else {
+        descriptor_time(bytes)
+    }
+}
+
+/// The 7-byte time used in directory records: years since 1900, month, day, hour, minute,
+/// second, and the offset from UTC in 15 minute units
+fn record_time(bytes: &[u8]) -> Option<SystemTime> {
+    let time = system_time(
+        1900 + bytes[0] as i64,
+        bytes[1].into(),
+        bytes[2].into(),
+        bytes[3].into(),
+        bytes[4].into(),
+        bytes[5].into(),
+    )?;
+
+    local_to_utc(time, bytes[6] as i8 as i64 * 15)
+}
+
+/// The 17-byte time used in volume descriptors: ASCII digits for the year, month, day, hour,
+/// minute, second, and hundredths, then the offset from UTC in 15 minute units
+fn descriptor_time(bytes: &[u8]) -> Option<SystemTime> {
+    let digits = std::str::from_utf8(&bytes[0..16]).ok()?;
+    let field = |range: std::ops::Range<usize>| digits.get(range)?.parse::<u32>().ok();
+
+    let time = system_time(
+        field(0..4)?.into(),
+        field(4..6)?,
+        field(6..8)?,
+        field(8..10)?,
+        field(10..12)?,
+        field(12..14)?,
+    )?;
+
+    local_to_utc(time, bytes[16] as i8 as i64 * 15)
+}
+
+/// Parse a path table, stored least significant byte first
+fn parse_path_table(mut table: &[u8], extension: Extension) -> Result<Vec<PathTableEntry>> {
+    let mut entries = Vec::new();
+
+    while table.len() >= 8 && table[0] != 0 {
+        let name_len = table[0] as usize;
+        let len = 8 + name_len + name_len % 2;
+        let bytes = table.get(..len).ok_or_else(|| bad_record(entries.len()))?;
+        table = &table[len..];
+
+        let raw_name = &bytes[8..8 + name_len];
+        let name = match extension {
+            Extension::Joliet => ucs2(raw_name),
+            _ => String::from_utf8_lossy(raw_name).into_owned(),
+        };
+
+        entries.push(PathTableEntry {
+            name,
+            lba: u32_le(&bytes[2..6]),
+            parent: u16::from_le_bytes([bytes[6], bytes[7]]) as usize,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Remove the version number from the end of a file name, along with the dot separating an
+/// empty extension
+fn strip_version(name: &str) -> String {
+    let name = name.split(';').next().unwrap_or_default();
+    name.strip_suffix('.').unwrap_or(name).to_string()
+}
+
+/// Decode a big endian UCS-2 string, as used by Joliet
+fn ucs2(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+
+    String::from_utf16_lossy(&units)
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[0..4].try_into().unwrap())
+}
+
+fn bad_record(offset: usize) -> Error {
+    Error::FilesystemParse(format!("bad ISO 9660 directory record at offset {offset}"))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::{
+        filesystem::{format_time, tests::write_at},
+        testing::TestDisc,
+    };
+
+    /// 2000-01-02 03:04:05 UTC, written in the 7-byte format as the local time an hour ahead
+    const RECORD_TIME: [u8; 7] = [100, 1, 2, 4, 4, 5, 4];
+
+    fn record(name: &[u8], lba: u32, size: u32, flags: u8, system_use: &[u8]) -> Vec<u8> {
+        let padding = (name.len() + 1) % 2;
+        let len = 33 + name.len() + padding + system_use.len();
+
+        let mut record = vec![0; len];
+        record[0] = len as u8;
+        record[2..6].copy_from_slice(&lba.to_le_bytes());
+        record[6..10].copy_from_slice(&lba.to_be_bytes());
+        record[10..14].copy_from_slice(&size.to_le_bytes());
+        record[14..18].copy_from_slice(&size.to_be_bytes());
+        record[18..25].copy_from_slice(&RECORD_TIME);
+        record[25] = flags;
+        record[28] = 1;
+        record[32] = name.len() as u8;
+        record[33..33 + name.len()].copy_from_slice(name);
+        record[33 + name.len() + padding..].copy_from_slice(system_use);
+
+        record
+    }
+
+    /// A SUSP entry, with its length and version filled in
+    fn susp(signature: &[u8; 2], data: &[u8]) -> Vec<u8> {
+        let mut entry = signature.to_vec();
+        entry.extend_from_slice(&[4 + data.len() as u8, 1]);
+        entry.extend_from_slice(data);
+
+        entry
+    }
+
+    /// A volume holding README.TXT and DOCS/GUIDE.HTM. With Rock Ridge, the root directory
+    /// carries an SP entry and README.TXT is given a lowercase name.
+    pub fn volume(rock_ridge: bool) -> Vec<u8> {
+        let mut image = Vec::new();
+
+        let mut primary = vec![0; USER_DATA_SIZE];
+        primary[0] = PRIMARY_VOLUME_DESCRIPTOR;
+        primary[1..6].copy_from_slice(b"CD001");
+        primary[6] = 1;
+        primary[40..72].copy_from_slice(&[b' '; 32]);
+        primary[40..48].copy_from_slice(b"TEST_ISO");
+        primary[80..84].copy_from_slice(&23u32.to_le_bytes());
+        primary[128..130].copy_from_slice(&2048u16.to_le_bytes());
+        primary[132..136].copy_from_slice(&22u32.to_le_bytes());
+        primary[140..144].copy_from_slice(&18u32.to_le_bytes());
+        primary[156..190].copy_from_slice(&record(&[0], 19, 2048, FLAG_DIRECTORY, &[]));
+        write_at(&mut image, 16, 0, &primary);
+
+        write_at(&mut image, 17, 0, &[VOLUME_DESCRIPTOR_TERMINATOR]);
+        write_at(&mut image, 17, 1, b"CD001\x01");
+
+        let path_table = [
+            &[1, 0, 19, 0, 0, 0, 1, 0, 0, 0][..],
+            &[4, 0, 20, 0, 0, 0, 1, 0],
+            b"DOCS",
+        ];
+        write_at(&mut image, 18, 0, &path_table.concat());
+
+        let (root_system_use, readme_system_use) = if rock_ridge {
+            (susp(b"SP", &[0xBE, 0xEF, 0]), susp(b"NM", b"\0readme.txt"))
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let root = [
+            record(&[0], 19, 2048, FLAG_DIRECTORY, &root_system_use),
+            record(&[1], 19, 2048, FLAG_DIRECTORY, &[]),
+            record(b"DOCS", 20, 2048, FLAG_DIRECTORY, &[]),
+            record(b"README.TXT;1", 21, 13, 0, &readme_system_use),
+        ];
+        write_at(&mut image, 19, 0, &root.concat());
+
+        let docs = [
+            record(&[0], 20, 2048, FLAG_DIRECTORY, &[]),
+            record(&[1], 19, 2048, FLAG_DIRECTORY, &[]),
+            record(b"GUIDE.HTM;1", 22, 20, 0, &[]),
+        ];
+        write_at(&mut image, 20, 0, &docs.concat());
+
+        write_at(&mut image, 21, 0, b"Hello, world!");
+        write_at(&mut image, 22, 0, b"<html>guide</html>\r\n");
+
+        image
+    }
+
+    fn names(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.name.as_str()).collect()
+    }
+
+    #[test]
+    fn reads_a_plain_volume() {
+        let disc = TestDisc::new("iso9660", &volume(false), false);
+        let mut reader = disc.reader();
+        let iso = Iso9660::open(&mut reader, 0).unwrap().unwrap();
+
+        assert_eq!(iso.description(), "ISO 9660");
+        assert_eq!(iso.volume_name(), "TEST_ISO");
+
+        let root = iso.read_dir(&mut reader, &iso.root()).unwrap();
+        assert_eq!(names(&root), ["DOCS", "README.TXT"]);
+        assert_eq!(root[0].kind, EntryKind::Directory);
+        assert_eq!(root[1].kind, EntryKind::File);
+        assert_eq!(root[1].size, 13);
+        assert_eq!(
+            format_time(root[1].modified.unwrap()),
+            "2000-01-02 03:04:05"
+        );
+
+        let mut data = Vec::new();
+        iso.read_file(&mut reader, &root[1], &mut data).unwrap();
+        assert_eq!(data, b"Hello, world!");
+    }
+
+    #[test]
+    fn looks_up_paths_through_the_path_table() {
+        let disc = TestDisc::new("iso9660-lookup", &volume(false), true);
+        let mut reader = disc.reader();
+        let iso = Iso9660::open(&mut reader, 0).unwrap().unwrap();
+
+        let guide = iso.lookup(&mut reader, "docs/guide.htm").unwrap();
+        assert_eq!(guide.name, "GUIDE.HTM");
+        assert_eq!(
+            reader.read_extents(&guide.extents).unwrap(),
+            b"<html>guide</html>\r\n"
+        );
+
+        assert!(matches!(
+            iso.lookup(&mut reader, "DOCS/MISSING"),
+            Err(Error::NoSuchFile(_))
+        ));
+    }
+
+    #[test]
+    fn prefers_rock_ridge_names() {
+        let disc = TestDisc::new("rock-ridge", &volume(true), false);
+        let mut reader = disc.reader();
+        let iso = Iso9660::open(&mut reader, 0).unwrap().unwrap();
+
+        assert_eq!(iso.description(), "ISO 9660 with Rock Ridge");
+
+        let root = iso.read_dir(&mut reader, &iso.root()).unwrap();
+        assert_eq!(names(&root), ["DOCS", "readme.txt"]);
+        assert_eq!(iso.lookup(&mut reader, "readme.txt").unwrap().size, 13);
+    }
+
+    #[test]
+    fn finds_nothing_without_volume_descriptors() {
+        let disc = TestDisc::new("iso9660-none", &[0; USER_DATA_SIZE * 20], false);
+        let mut reader = disc.reader();
+
+        assert!(Iso9660::open(&mut reader, 0).unwrap().is_none());
+    }
+}
@@ -0,0 +1,618 @@
+mod el_torito;
+mod hfs;
+mod iso9660;
+mod udf;
+
+use crate::{
+    args::FilesystemType,
+    error::{Error, Result},
+    loader::LoadOptions,
+    mds::{Mds, Track},
+    sector::{user_data_offset, USER_DATA_SIZE},
+    util::{reader_for_track, SplitFile},
+};
+pub use el_torito::{boot_catalog, BootCatalog, BootImage, Emulation, Platform};
+use hfs::Hfs;
+use iso9660::Iso9660;
+use std::{
+    io::{BufReader, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use udf::Udf;
+
+// Filesystems are read straight from the user data of the image's sectors, the same 2048 bytes
+// per sector that would end up in an .iso. Each filesystem describes where its files are stored
+// as byte ranges from the start of the disc, which `DiscReader` turns into reads from the right
+// track and data file.
+
+/// A file, directory, or symbolic link in a filesystem
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+
+    /// Where the entry's contents are stored. For directories, this is the filesystem's own
+    /// record of what the directory holds.
+    pub extents: Vec<Extent>,
+
+    /// The Finder information and resource fork of entries on Mac filesystems
+    pub mac: Option<MacMetadata>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntryKind {
+    File,
+    Directory,
+
+    /// A symbolic link, along with the path it points to
+    Symlink(String),
+}
+
+/// The parts of a file on a Mac filesystem which other systems have nowhere to keep
+#[derive(Clone, Debug)]
+pub struct MacMetadata {
+    /// The entry's catalog node ID, which the contents of directories are filed under
+    pub catalog_id: u32,
+
+    /// The Finder's information about the entry, starting with a file's type and creator codes
+    pub finder_info: [u8; 32],
+
+    pub created: Option<SystemTime>,
+
+    /// Where the resource fork is stored. Most files on the Mac had one, even if it was empty.
+    pub resource_fork: Vec<Extent>,
+}
+
+impl MacMetadata {
+    pub fn resource_fork_size(&self) -> u64 {
+        self.resource_fork.iter().map(Extent::length).sum()
+    }
+
+    /// Whether there is anything here which would be lost by copying just the data fork
+    pub fn is_significant(&self) -> bool {
+        self.resource_fork_size() > 0 || self.finder_info[0..8].iter().any(|&b| b != 0)
+    }
+}
+
+/// A run of bytes making up part of an entry's contents
+#[derive(Clone, Debug)]
+pub enum Extent {
+    /// `length` bytes stored `offset` bytes from the start of sector 0
+    Stored { offset: u64, length: u64 },
+
+    /// `length` bytes of zeros, which take up no space on the disc
+    Sparse { length: u64 },
+
+    /// Bytes kept in the filesystem's own records, rather than in sectors of their own
+    Inline(Vec<u8>),
+}
+
+impl Extent {
+    /// An extent starting at the beginning of a 2048-byte sector
+    pub fn from_sector(lba: u32, length: u64) -> Self {
+        Self::Stored {
+            offset: lba as u64 * USER_DATA_SIZE as u64,
+            length,
+        }
+    }
+
+    pub fn length(&self) -> u64 {
+        match self {
+            Self::Stored { length, .. } | Self::Sparse { length } => *length,
+            Self::Inline(data) => data.len() as u64,
+        }
+    }
+}
+
+/// A filesystem which can be browsed, and have files read out of it
+pub trait Filesystem {
+    /// A short description of the filesystem, like "ISO 9660 with Joliet"
+    fn description(&self) -> String;
+
+    /// Which kind of filesystem this is
+    fn kind(&self) -> FilesystemType;
+
+    /// The name the volume was given when the disc was made
+    fn volume_name(&self) -> &str;
+
+    /// The filesystem's root directory
+    fn root(&self) -> Entry;
+
+    /// Every entry in a directory, not including the directory itself or its parent
+    fn read_dir(&self, disc: &mut DiscReader, dir: &Entry) -> Result<Vec<Entry>>;
+
+    /// Find the entry at `path`, which is made up of names separated by slashes. Names are
+    /// matched exactly when possible, and otherwise without regard to case.
+    fn lookup(&self, disc: &mut DiscReader, path: &str) -> Result<Entry> {
+        lookup_by_walking(self, disc, path)
+    }
+
+    /// Write the contents of a file to `writer`
+    fn read_file(
+        &self,
+        disc: &mut DiscReader,
+        entry: &Entry,
+        writer: &mut dyn Write,
+    ) -> Result<()> {
+        disc.copy_extents(&entry.extents, writer)
+    }
+}
+
+/// Open the filesystem of the last session on a disc, which is the one a drive would mount.
+/// Hybrid discs can have more than one filesystem, in which case `filesystem` picks which one to
+/// open. Otherwise, UDF is preferred, then ISO 9660, then HFS.
+pub fn open_filesystem<'a>(
+    mds: &'a Mds,
+    mds_path: &Path,
+    options: &'a LoadOptions,
+    filesystem: Option<&FilesystemType>,
+) -> Result<(DiscReader<'a>, Box<dyn Filesystem>)> {
+    let mut disc = DiscReader::new(mds, mds_path, options);
+    let start_lba = first_data_sector(mds)?;
+    let wanted = |kind| filesystem.is_none_or(|filesystem| *filesystem == kind);
+
+    // Discs with both usually have a UDF/ISO 9660 bridge filesystem, where UDF is the one which
+    // can describe everything on the disc. A UDF volume which can't be read is only an error when
+    // it was asked for, since the ISO 9660 side of a bridge disc is usually still fine.
+    if wanted(FilesystemType::Udf) {
+        match Udf::open(&mut disc, start_lba) {
+            Ok(Some(udf)) => return Ok((disc, Box::new(udf))),
+            Ok(None) => {}
+            Err(Error::FilesystemParse(_)) if filesystem.is_none() => {}
+            Err(err) => Err(err)?,
+        }
+    }
+
+    if wanted(FilesystemType::Iso9660) {
+        if let Some(iso) = Iso9660::open(&mut disc, start_lba)? {
+            return Ok((disc, Box::new(iso)));
+        }
+    }
+
+    if wanted(FilesystemType::Hfs) {
+        if let Some(hfs) = Hfs::open(&mut disc, start_lba)? {
+            return Ok((disc, Box::new(hfs)));
+        }
+    }
+
+    Err(Error::NoFilesystem)
+}
+
+/// The LBA of the first sector of user data in the last session, which is where filesystems start
+pub fn first_data_sector(mds: &Mds) -> Result<i64> {
+    let session = mds.session(None)?;
+    let first_track = session
+        .data_tracks()
+        .find(|track| has_user_data(track))
+        .ok_or(Error::NoDataTracks)?;
+
+    Ok(first_track.track_start_sector as i64)
+}
+
+/// The LBA of the first sector of user data on the disc, in the earliest session which has any.
+/// This is where consoles look for their boot headers, whichever session a drive would mount.
+pub fn first_disc_data_sector(mds: &Mds) -> Result<i64> {
+    let first_track = mds
+        .sessions()
+        .flat_map(|session| session.data_tracks())
+        .find(|track| has_user_data(track))
+        .ok_or(Error::NoDataTracks)?;
+
+    Ok(first_track.track_start_sector as i64)
+}
+
+/// Reads the user data of sectors from anywhere on a disc, whichever track they belong to
+pub struct DiscReader<'a> {
+    mds_path: PathBuf,
+    options: &'a LoadOptions,
+    tracks: Vec<(&'a Track, Option<BufReader<SplitFile>>)>,
+
+    /// The track holding the last sector read, and the sector its reader is now positioned at.
+    /// Filesystems mostly read runs of sectors, which can then be read without seeking.
+    position: Option<(usize, i64)>,
+}
+
+impl<'a> DiscReader<'a> {
+    pub fn new(mds: &'a Mds, mds_path: &Path, options: &'a LoadOptions) -> Self {
+        let tracks = mds
+            .sessions()
+            .flat_map(|session| session.data_tracks())
+            .filter(|track| has_user_data(track))
+            .map(|track| (track, None))
+            .collect();
+
+        Self {
+            mds_path: mds_path.to_path_buf(),
+            options,
+            tracks,
+            position: None,
+        }
+    }
+
+    /// Read the 2048 bytes of user data in the sector numbered `lba`
+    pub fn read_sector(&mut self, lba: i64, buf: &mut [u8]) -> Result<()> {
+        let i = match self.position {
+            Some((i, _)) if track_contains(self.tracks[i].0, lba) => i,
+            _ => self
+                .tracks
+                .iter()
+                .position(|(track, _)| track_contains(track, lba))
+                .ok_or(Error::NoSuchSector(lba))?,
+        };
+        let track = self.tracks[i].0;
+        let contiguous = self.position == Some((i, lba));
+        self.position = None;
+
+        let reader = match &mut self.tracks[i].1 {
+            Some(reader) => reader,
+            reader => reader.insert(reader_for_track(&self.mds_path, track, self.options)?),
+        };
+
+        if contiguous {
+            // The reader is just past the previous sector's user data
+            let skip = track.sector_size() - USER_DATA_SIZE;
+            reader.seek_relative(skip as i64).map_err(Error::Io)?;
+        } else {
+            let user_data = user_data_offset(track.mode, track.sector_data_size()).unwrap_or(0);
+            let sector = (lba - track.track_start_sector as i64) as i128;
+            let offset = track.index1_offset() as i128 + sector * track.sector_size() as i128;
+
+            reader
+                .seek(SeekFrom::Start(offset as u64 + user_data as u64))
+                .map_err(Error::Io)?;
+        }
+
+        reader
+            .read_exact(&mut buf[..USER_DATA_SIZE])
+            .map_err(Error::Io)?;
+        self.position = Some((i, lba + 1));
+
+        Ok(())
+    }
+
+    /// Fill `buf` with the user data starting `offset` bytes from the start of sector 0
+    pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let mut sector = [0; USER_DATA_SIZE];
+        let mut pos = 0;
+
+        while pos < buf.len() {
+            let absolute = offset + pos as u64;
+            let lba = absolute / USER_DATA_SIZE as u64;
+            let start = (absolute % USER_DATA_SIZE as u64) as usize;
+            let len = (USER_DATA_SIZE - start).min(buf.len() - pos);
+
+            self.read_sector(lba as i64, &mut sector)?;
+            buf[pos..pos + len].copy_from_slice(&sector[start..start + len]);
+            pos += len;
+        }
+
+        Ok(())
+    }
+
+    /// Read every byte of the given extents, one after another
+    pub fn read_extents(&mut self, extents: &[Extent]) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.copy_extents(extents, &mut data)?;
+
+        Ok(data)
+    }
+
+    /// Write every byte of the given extents to `writer`, one after another
+    pub fn copy_extents(&mut self, extents: &[Extent], writer: &mut dyn Write) -> Result<()> {
+        let mut buf = vec![0; USER_DATA_SIZE * 32];
+
+        for extent in extents {
+            if let Extent::Inline(data) = extent {
+                writer.write_all(data).map_err(Error::Io)?;
+                continue;
+            }
+
+            let mut pos = 0;
+            while pos < extent.length() {
+                let len = (extent.length() - pos).min(buf.len() as u64) as usize;
+                match extent {
+                    Extent::Stored { offset, .. } => self.read_at(offset + pos, &mut buf[..len])?,
+                    _ => buf[..len].fill(0),
+                }
+
+                writer.write_all(&buf[..len]).map_err(Error::Io)?;
+                pos += len as u64;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `lba` is one of a track's sectors, including those of its pregap
+fn track_contains(track: &Track, lba: i64) -> bool {
+    let first = track.track_start_sector as i64 - track.pregap_sectors() as i64;
+    let end = track.track_start_sector as i64 + track.num_sectors() as i64;
+
+    (first..end).contains(&lba)
+}
+
+/// Whether a track's sectors hold 2048 bytes of user data, which is where filesystems are kept
+fn has_user_data(track: &Track) -> bool {
+    user_data_offset(track.mode, track.sector_data_size()).is_some()
+}
+
+/// Cut a list of extents down to the size of the file they hold, since the last one is usually
+/// rounded up to a whole number of blocks
+pub fn truncate_extents(extents: Vec<Extent>, size: u64) -> Vec<Extent> {
+    let mut remaining = size;
+
+    extents
+        .into_iter()
+        .filter_map(|extent| {
+            let length = extent.length().min(remaining);
+            remaining -= length;
+
+            match extent {
+                _ if length == 0 => None,
+                Extent::Stored { offset, .. } => Some(Extent::Stored { offset, length }),
+                Extent::Sparse { .. } => Some(Extent::Sparse { length }),
+                Extent::Inline(mut data) => {
+                    data.truncate(length as usize);
+                    Some(Extent::Inline(data))
+                }
+            }
+        })
+        .collect()
+}
+
+/// The names making up a path, ignoring empty names and references to the current directory
+pub fn path_components(path: &str) -> impl Iterator<Item = &str> {
+    path.split(['/', '\\'])
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// Find the entry at `path` by reading each directory along it in turn
+pub fn lookup_by_walking<F>(fs: &F, disc: &mut DiscReader, path: &str) -> Result<Entry>
+where
+    F: Filesystem + ?Sized,
+{
+    let mut entry = fs.root();
+
+    for name in path_components(path) {
+        if entry.kind != EntryKind::Directory {
+            Err(Error::NoSuchFile(path.to_string()))?;
+        }
+
+        let entries = fs.read_dir(disc, &entry)?;
+        entry = find_entry(entries, name).ok_or_else(|| Error::NoSuchFile(path.to_string()))?;
+    }
+
+    Ok(entry)
+}
+
+/// Find the entry called `name`, preferring an exact match over one which differs in case
+pub fn find_entry(entries: Vec<Entry>, name: &str) -> Option<Entry> {
+    let exact = entries.iter().position(|entry| entry.name == name);
+    let index = exact.or_else(|| {
+        entries
+            .iter()
+            .position(|entry| entry.name.eq_ignore_ascii_case(name))
+    })?;
+
+    entries.into_iter().nth(index)
+}
+
+/// Convert a calendar date and time in UTC into a `SystemTime`. Returns `None` for dates which
+/// don't exist, which filesystems use when a time wasn't recorded.
+pub fn system_time(
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+) -> Option<SystemTime> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // Count days from 1970-01-01 using the proleptic Gregorian calendar, with years starting in
+    // March so that leap days fall at the end. See
+    // https://howardhinnant.github.io/date_algorithms.html#days_from_civil.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let seconds = days * 86_400 + (hour * 3600 + minute * 60 + second.min(59)) as i64;
+    from_unix_seconds(seconds)
+}
+
+/// Convert a local time into UTC, given how many minutes the local time zone is ahead of UTC
+pub fn local_to_utc(time: SystemTime, offset_minutes: i64) -> Option<SystemTime> {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+
+    from_unix_seconds(seconds - offset_minutes * 60)
+}
+
+/// Convert a number of seconds since the Unix epoch into a `SystemTime`
+pub fn from_unix_seconds(seconds: i64) -> Option<SystemTime> {
+    if seconds >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(seconds as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(seconds.unsigned_abs()))
+    }
+}
+
+/// Format a time as a UTC date and time, like `2001-02-03 04:05:06`
+pub fn format_time(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    };
+
+    // The inverse of `system_time`. See
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days.
+    let days = seconds.div_euclid(86_400);
+    let time_of_day = seconds.rem_euclid(86_400);
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+    pub use hfs::tests::volume as hfs_volume;
+
+    /// Copy `bytes` into an image, `offset` bytes into sector `lba`, growing it if need be
+    pub fn write_at(image: &mut Vec<u8>, lba: u32, offset: usize, bytes: &[u8]) {
+        let start = lba as usize * USER_DATA_SIZE + offset;
+        if image.len() < start + bytes.len() {
+            image.resize((start + bytes.len()).next_multiple_of(USER_DATA_SIZE), 0);
+        }
+
+        image[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    /// An image whose sectors are each filled with their own number
+    fn numbered_sectors(count: u8) -> Vec<u8> {
+        (0..count).flat_map(|lba| [lba; USER_DATA_SIZE]).collect()
+    }
+
+    #[test]
+    fn reads_raw_sectors_in_any_order() {
+        let disc = TestDisc::new("any-order", &numbered_sectors(20), true);
+        let mut reader = disc.reader();
+        let mut buf = [0; USER_DATA_SIZE];
+
+        let order = (0..20).chain((0..20).rev()).chain([3, 17, 4, 5, 0, 19, 19]);
+        for lba in order {
+            reader.read_sector(lba, &mut buf).unwrap();
+            assert!(buf.iter().all(|&b| b == lba as u8), "sector {lba}");
+        }
+    }
+
+    #[test]
+    fn reads_across_sector_boundaries() {
+        let disc = TestDisc::new("boundaries", &numbered_sectors(4), true);
+        let mut reader = disc.reader();
+
+        let mut buf = vec![0; USER_DATA_SIZE + 10];
+        reader.read_at(USER_DATA_SIZE as u64 - 5, &mut buf).unwrap();
+
+        assert_eq!(buf[..5], [0; 5]);
+        assert!(buf[5..USER_DATA_SIZE + 5].iter().all(|&b| b == 1));
+        assert_eq!(buf[USER_DATA_SIZE + 5..], [2; 5]);
+    }
+
+    #[test]
+    fn sectors_past_the_end_are_missing() {
+        let disc = TestDisc::new("past-end", &numbered_sectors(2), false);
+        let mut reader = disc.reader();
+        let mut buf = [0; USER_DATA_SIZE];
+
+        reader.read_sector(1, &mut buf).unwrap();
+        assert!(matches!(
+            reader.read_sector(2, &mut buf),
+            Err(Error::NoSuchSector(2))
+        ));
+
+        // A failed read doesn't leave the reader thinking it's in the right place
+        reader.read_sector(0, &mut buf).unwrap();
+        assert_eq!(buf, [0; USER_DATA_SIZE]);
+        reader.read_sector(1, &mut buf).unwrap();
+        assert_eq!(buf, [1; USER_DATA_SIZE]);
+    }
+
+    #[test]
+    fn copies_sparse_and_inline_extents() {
+        let disc = TestDisc::new("extents", &numbered_sectors(3), false);
+        let mut reader = disc.reader();
+
+        let extents = [
+            Extent::Inline(b"abc".to_vec()),
+            Extent::Sparse { length: 4 },
+            Extent::Stored {
+                offset: 2 * USER_DATA_SIZE as u64 + 1,
+                length: 3,
+            },
+        ];
+
+        let data = reader.read_extents(&extents).unwrap();
+        assert_eq!(data, b"abc\0\0\0\0\x02\x02\x02");
+    }
+
+    /// A UDF/ISO 9660 bridge disc whose UDF anchor points at volume descriptors which aren't
+    /// there
+    fn broken_bridge_disc() -> TestDisc {
+        let mut image = iso9660::tests::volume(false);
+
+        let mut anchor = vec![2, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        anchor.extend_from_slice(&256u32.to_le_bytes());
+        anchor[4] = anchor.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        anchor.extend_from_slice(&(USER_DATA_SIZE as u32).to_le_bytes());
+        anchor.extend_from_slice(&257u32.to_le_bytes());
+        write_at(&mut image, 256, 0, &anchor);
+        write_at(&mut image, 257, 0, &[0; USER_DATA_SIZE]);
+
+        TestDisc::new("broken-bridge", &image, false)
+    }
+
+    fn description(disc: &TestDisc, filesystem: Option<&FilesystemType>) -> Result<String> {
+        open_filesystem(disc.mds(), &disc.mds_path(), disc.options(), filesystem)
+            .map(|(_, fs)| fs.description())
+    }
+
+    #[test]
+    fn broken_udf_falls_back_to_iso9660() {
+        let disc = broken_bridge_disc();
+
+        assert_eq!(description(&disc, None).unwrap(), "ISO 9660");
+        assert_eq!(
+            description(&disc, Some(&FilesystemType::Iso9660)).unwrap(),
+            "ISO 9660"
+        );
+    }
+
+    #[test]
+    fn broken_udf_is_reported_when_asked_for() {
+        let disc = broken_bridge_disc();
+
+        assert!(matches!(
+            description(&disc, Some(&FilesystemType::Udf)),
+            Err(Error::FilesystemParse(_))
+        ));
+    }
+
+    #[test]
+    fn discs_without_a_filesystem_have_none() {
+        let disc = TestDisc::new("no-filesystem", &[0; USER_DATA_SIZE * 20], false);
+
+        assert!(matches!(description(&disc, None), Err(Error::NoFilesystem)));
+    }
+}
@@ -0,0 +1,786 @@
+use super::{
+    local_to_utc, system_time, truncate_extents, DiscReader, Entry, EntryKind, Extent, Filesystem,
+};
+use crate::{
+    args::FilesystemType,
+    error::{Error, Result},
+    sector::USER_DATA_SIZE,
+};
+use std::time::SystemTime;
+
+// UDF is built on ECMA-167 (https://ecma-international.org/publications-and-standards/standards/ecma-167/),
+// narrowed down by OSTA's UDF specification (http://www.osta.org/specs/). A volume is found through
+// an anchor at sector 256, which points at a sequence of volume descriptors describing the
+// partitions on the disc and where the file set is. Everything after that is addressed by a block
+// number within a partition. UDF 2.50 and later keep directories and file entries in a metadata
+// partition, whose blocks are stored in the extents of a metadata file in a physical partition.
+
+/// The sector holding the anchor volume descriptor pointer, relative to the start of the session
+const ANCHOR_SECTOR: i64 = 256;
+
+/// The only logical block size that makes sense for optical discs
+const BLOCK_SIZE: u64 = USER_DATA_SIZE as u64;
+
+/// The most sectors of volume descriptors to read, in case the sequence isn't terminated
+const MAX_VOLUME_DESCRIPTORS: u32 = 64;
+
+/// How many allocation extent descriptors to follow for one file, to avoid looping forever on a
+/// broken disc
+const MAX_ALLOCATION_EXTENTS: usize = 1024;
+
+const TAG_ANCHOR: u16 = 2;
+const TAG_PARTITION: u16 = 5;
+const TAG_LOGICAL_VOLUME: u16 = 6;
+const TAG_TERMINATING: u16 = 8;
+const TAG_FILE_SET: u16 = 256;
+const TAG_FILE_IDENTIFIER: u16 = 257;
+const TAG_ALLOCATION_EXTENT: u16 = 258;
+const TAG_FILE_ENTRY: u16 = 261;
+const TAG_EXTENDED_FILE_ENTRY: u16 = 266;
+
+/// File types in an ICB tag
+const FILE_TYPE_DIRECTORY: u8 = 4;
+const FILE_TYPE_SYMLINK: u8 = 12;
+
+/// File characteristics in a file identifier descriptor
+const FILE_DELETED: u8 = 0x04;
+const FILE_PARENT: u8 = 0x08;
+
+/// The ways a file entry can describe where its data is
+const SHORT_ALLOCATION: u16 = 0;
+const LONG_ALLOCATION: u16 = 1;
+const EXTENDED_ALLOCATION: u16 = 2;
+const INLINE_DATA: u16 = 3;
+
+/// Extent types, held in the top two bits of an allocation descriptor's length
+const EXTENT_RECORDED: u32 = 0;
+const EXTENT_CONTINUATION: u32 = 3;
+
+/// How a partition reference number maps to sectors on the disc
+#[derive(Debug)]
+enum PartitionMap {
+    /// Blocks are stored one after another from `start`. Sparable partitions, which are used on
+    /// rewritable discs, are read this way too, without applying their sparing tables.
+    Physical { start: u64 },
+
+    /// Blocks are stored in the extents of a metadata file, which is in the physical partition
+    /// referred to by `physical`
+    Metadata { physical: u16, extents: Vec<Extent> },
+}
+
+/// The parts of a file entry this program cares about
+struct FileEntry {
+    file_type: u8,
+    size: u64,
+    modified: Option<SystemTime>,
+    extents: Vec<Extent>,
+}
+
+pub struct Udf {
+    volume_name: String,
+    revision: u16,
+    maps: Vec<PartitionMap>,
+    root: Entry,
+}
+
+impl Udf {
+    /// Read the volume descriptors of a session starting at `start_lba`. Returns `None` if there
+    /// isn't a UDF filesystem there.
+    pub fn open(disc: &mut DiscReader, start_lba: i64) -> Result<Option<Self>> {
+        let mut buf = [0; USER_DATA_SIZE];
+        let anchor = disc.read_sector(start_lba + ANCHOR_SECTOR, &mut buf);
+        if anchor.is_err() || tag_id(&buf) != Some(TAG_ANCHOR) {
+            return Ok(None);
+        }
+
+        let sequence_length = u32_le(&buf[16..20]) / BLOCK_SIZE as u32;
+        let sequence_start = u32_le(&buf[20..24]);
+        let mut partitions = Vec::new();
+        let mut logical_volume = None;
+
+        for i in 0..sequence_length.min(MAX_VOLUME_DESCRIPTORS) {
+            disc.read_sector((sequence_start + i) as i64, &mut buf)?;
+
+            match tag_id(&buf) {
+                Some(TAG_PARTITION) => {
+                    let number = u16_le(&buf[22..24]);
+                    partitions.push((number, u32_le(&buf[188..192]) as u64));
+                }
+                Some(TAG_LOGICAL_VOLUME) => logical_volume = Some(buf),
+                Some(TAG_TERMINATING) | None => break,
+                _ => {}
+            }
+        }
+
+        let lvd = logical_volume.ok_or_else(|| bad_udf("there's no logical volume descriptor"))?;
+        if u32_le(&lvd[212..216]) as u64 != BLOCK_SIZE {
+            return Err(bad_udf("only 2048 byte blocks are supported"));
+        }
+
+        let mut udf = Self {
+            volume_name: dstring(&lvd[84..212]),
+            revision: u16_le(&lvd[240..242]),
+            maps: parse_partition_maps(&lvd, &partitions)?,
+            root: Entry {
+                name: String::new(),
+                kind: EntryKind::Directory,
+                size: 0,
+                modified: None,
+                extents: Vec::new(),
+                mac: None,
+            },
+        };
+
+        // The metadata file is found through the physical partition, so it can only be read once
+        // the partition maps are known
+        for i in 0..udf.maps.len() {
+            if let PartitionMap::Metadata { physical, .. } = udf.maps[i] {
+                let location = metadata_file_location(&lvd, i)?;
+                let extents = udf.read_file_entry(disc, physical, location)?.extents;
+                udf.maps[i] = PartitionMap::Metadata { physical, extents };
+            }
+        }
+
+        let (fsd_block, fsd_partition) = long_ad(&lvd[248..264]);
+        let fsd = udf.read_block(disc, fsd_partition, fsd_block)?;
+        if tag_id(&fsd) != Some(TAG_FILE_SET) {
+            return Err(bad_udf("there's no file set descriptor"));
+        }
+
+        let (root_block, root_partition) = long_ad(&fsd[400..416]);
+        let root = udf.read_file_entry(disc, root_partition, root_block)?;
+        udf.root = Entry {
+            name: String::new(),
+            kind: EntryKind::Directory,
+            size: root.size,
+            modified: root.modified,
+            extents: root.extents,
+            mac: None,
+        };
+
+        Ok(Some(udf))
+    }
+
+    /// Map `length` bytes starting at a block in a partition to where they're stored on the disc
+    fn map_extent(&self, partition: u16, block: u32, length: u64) -> Result<Vec<Extent>> {
+        let map = self
+            .maps
+            .get(partition as usize)
+            .ok_or_else(|| bad_udf("a file refers to a partition which doesn't exist"))?;
+
+        match map {
+            PartitionMap::Physical { start } => Ok(vec![Extent::Stored {
+                offset: (start + block as u64) * BLOCK_SIZE,
+                length,
+            }]),
+            PartitionMap::Metadata { extents, .. } => {
+                let mut wanted = block as u64 * BLOCK_SIZE..block as u64 * BLOCK_SIZE + length;
+                let mut mapped = Vec::new();
+                let mut position = 0;
+
+                for extent in extents {
+                    let range = position..position + extent.length();
+                    position = range.end;
+
+                    let Extent::Stored { offset, .. } = extent else {
+                        continue;
+                    };
+                    if wanted.start >= range.end || wanted.is_empty() {
+                        continue;
+                    }
+
+                    let len = wanted.end.min(range.end) - wanted.start;
+                    mapped.push(Extent::Stored {
+                        offset: offset + wanted.start - range.start,
+                        length: len,
+                    });
+                    wanted.start += len;
+                }
+
+                if !wanted.is_empty() {
+                    return Err(bad_udf("a block is past the end of the metadata partition"));
+                }
+
+                Ok(mapped)
+            }
+        }
+    }
+
+    fn read_block(&self, disc: &mut DiscReader, partition: u16, block: u32) -> Result<Vec<u8>> {
+        let extents = self.map_extent(partition, block, BLOCK_SIZE)?;
+        disc.read_extents(&extents)
+    }
+
+    /// Read a file entry or extended file entry, and work out where the file's data is
+    fn read_file_entry(
+        &self,
+        disc: &mut DiscReader,
+        partition: u16,
+        block: u32,
+    ) -> Result<FileEntry> {
+        let buf = self.read_block(disc, partition, block)?;
+
+        // Extended file entries have a few more fields, which push the rest along
+        let (modified, ea_length, ad_length, ad_start) = match tag_id(&buf) {
+            Some(TAG_FILE_ENTRY) => (84, 168, 172, 176),
+            Some(TAG_EXTENDED_FILE_ENTRY) => (92, 208, 212, 216),
+            _ => return Err(bad_udf("a file entry is missing")),
+        };
+
+        let file_type = buf[27];
+        let flags = u16_le(&buf[34..36]);
+        let size = u64::from_le_bytes(buf[56..64].try_into().unwrap());
+        let ad_start = ad_start + u32_le(&buf[ea_length..ea_length + 4]) as usize;
+        let ad_end = ad_start + u32_le(&buf[ad_length..ad_length + 4]) as usize;
+        let descriptors = buf
+            .get(ad_start..ad_end)
+            .ok_or_else(|| bad_udf("a file entry's allocation descriptors don't fit in it"))?;
+
+        // File data is never stored in the metadata partition, even when the file's entry is
+        let data_partition = match self.maps.get(partition as usize) {
+            Some(PartitionMap::Metadata { physical, .. }) if file_type != FILE_TYPE_DIRECTORY => {
+                *physical
+            }
+            _ => partition,
+        };
+
+        let extents = match flags & 0x7 {
+            INLINE_DATA => vec![Extent::Inline(descriptors.to_vec())],
+            ad_type => self.allocation_descriptors(disc, ad_type, data_partition, descriptors)?,
+        };
+
+        Ok(FileEntry {
+            file_type,
+            size,
+            modified: timestamp(&buf[modified..modified + 12]),
+            extents: truncate_extents(extents, size),
+        })
+    }
+
+    /// Parse a list of allocation descriptors, following any continuations into allocation
+    /// extent descriptors
+    fn allocation_descriptors(
+        &self,
+        disc: &mut DiscReader,
+        ad_type: u16,
+        partition: u16,
+        descriptors: &[u8],
+    ) -> Result<Vec<Extent>> {
+        let size = match ad_type {
+            SHORT_ALLOCATION => 8,
+            LONG_ALLOCATION => 16,
+            EXTENDED_ALLOCATION => 20,
+            _ => return Err(bad_udf("unknown allocation descriptor type")),
+        };
+
+        let mut extents = Vec::new();
+        let mut descriptors = descriptors.to_vec();
+
+        for _ in 0..MAX_ALLOCATION_EXTENTS {
+            let mut continuation = None;
+
+            for ad in descriptors.chunks_exact(size) {
+                let raw_length = u32_le(&ad[0..4]);
+                let length = (raw_length & 0x3FFF_FFFF) as u64;
+                let (block, ad_partition) = match ad_type {
+                    SHORT_ALLOCATION => (u32_le(&ad[4..8]), partition),
+                    LONG_ALLOCATION => long_ad(ad),
+                    _ => (u32_le(&ad[12..16]), u16_le(&ad[16..18])),
+                };
+
+                if length == 0 {
+                    break;
+                }
+
+                match raw_length >> 30 {
+                    EXTENT_RECORDED => {
+                        extents.extend(self.map_extent(ad_partition, block, length)?)
+                    }
+                    EXTENT_CONTINUATION => continuation = Some((ad_partition, block)),
+                    _ => extents.push(Extent::Sparse { length }),
+                }
+            }
+
+            let Some((ad_partition, block)) = continuation else {
+                return Ok(extents);
+            };
+
+            let buf = self.read_block(disc, ad_partition, block)?;
+            if tag_id(&buf) != Some(TAG_ALLOCATION_EXTENT) {
+                return Err(bad_udf("an allocation extent descriptor is missing"));
+            }
+
+            let length = u32_le(&buf[20..24]) as usize;
+            descriptors = buf.get(24..24 + length).unwrap_or_default().to_vec();
+        }
+
+        Err(bad_udf("a file has too many allocation extents"))
+    }
+
+    /// The target of a symbolic link, which is stored as a list of path components
+    fn symlink_target(&self, disc: &mut DiscReader, extents: &[Extent]) -> Result<String> {
+        let data = disc.read_extents(extents)?;
+        let mut components = Vec::new();
+        let mut pos = 0;
+
+        while pos + 4 <= data.len() {
+            let component_type = data[pos];
+            let len = data[pos + 1] as usize;
+            let identifier = data.get(pos + 4..pos + 4 + len).unwrap_or_default();
+            pos += 4 + len;
+
+            components.push(match component_type {
+                1 | 2 => String::new(),
+                3 => "..".to_string(),
+                4 => ".".to_string(),
+                _ => cs0(identifier),
+            });
+        }
+
+        Ok(match components.as_slice() {
+            [root] if root.is_empty() => "/".to_string(),
+            _ => components.join("/"),
+        })
+    }
+}
+
+impl Filesystem for Udf {
+    fn description(&self) -> String {
+        format!("UDF {:x}.{:02x}", self.revision >> 8, self.revision & 0xFF)
+    }
+
+    fn kind(&self) -> FilesystemType {
+        FilesystemType::Udf
+    }
+
+    fn volume_name(&self) -> &str {
+        &self.volume_name
+    }
+
+    fn root(&self) -> Entry {
+        self.root.clone()
+    }
+
+    fn read_dir(&self, disc: &mut DiscReader, dir: &Entry) -> Result<Vec<Entry>> {
+        let data = disc.read_extents(&dir.extents)?;
+        let mut entries = Vec::new();
+        let mut pos = 0;
+
+        while pos + 38 <= data.len() && tag_id(&data[pos..]) == Some(TAG_FILE_IDENTIFIER) {
+            let characteristics = data[pos + 18];
+            let name_len = data[pos + 19] as usize;
+            let (block, partition) = long_ad(&data[pos + 20..pos + 36]);
+            let impl_use_len = u16_le(&data[pos + 36..pos + 38]) as usize;
+            let name_start = pos + 38 + impl_use_len;
+            let name = data
+                .get(name_start..name_start + name_len)
+                .unwrap_or_default();
+
+            // Descriptors are padded to a multiple of four bytes
+            pos += (38 + impl_use_len + name_len + 3) & !3;
+
+            if characteristics & (FILE_DELETED | FILE_PARENT) != 0 {
+                continue;
+            }
+
+            let file = self.read_file_entry(disc, partition, block)?;
+            let kind = match file.file_type {
+                FILE_TYPE_DIRECTORY => EntryKind::Directory,
+                FILE_TYPE_SYMLINK => EntryKind::Symlink(self.symlink_target(disc, &file.extents)?),
+                _ => EntryKind::File,
+            };
+
+            entries.push(Entry {
+                name: cs0(name),
+                kind,
+                size: file.size,
+                modified: file.modified,
+                extents: file.extents,
+                mac: None,
+            });
+        }
+
+        Ok(entries)
+    }
+}
+
+/// Parse the partition maps in a logical volume descriptor, given the number and starting sector
+/// of each partition descriptor
+fn parse_partition_maps(lvd: &[u8], partitions: &[(u16, u64)]) -> Result<Vec<PartitionMap>> {
+    let num_maps = u32_le(&lvd[268..272]) as usize;
+    let mut maps = Vec::new();
+    let mut physical_maps = Vec::new();
+    let mut pos = 440;
+
+    for i in 0..num_maps {
+        let map = lvd
+            .get(pos..pos + 2)
+            .ok_or_else(|| bad_udf("the partition maps don't fit in the volume descriptor"))?;
+        let (map_type, len) = (map[0], map[1] as usize);
+        let map = lvd.get(pos..pos + len).unwrap_or_default();
+        pos += len.max(2);
+
+        let (number, metadata) = match (map_type, map.len()) {
+            (1, 6) => (u16_le(&map[4..6]), false),
+            (2, 64) if map[5..].starts_with(b"*UDF Metadata Partition") => {
+                (u16_le(&map[38..40]), true)
+            }
+            (2, 64) if map[5..].starts_with(b"*UDF Sparable Partition") => {
+                (u16_le(&map[38..40]), false)
+            }
+            _ => {
+                return Err(bad_udf(
+                    "only physical, sparable, and metadata partitions are supported",
+                ))
+            }
+        };
+
+        let start = partitions
+            .iter()
+            .find(|(n, _)| *n == number)
+            .map(|(_, start)| *start)
+            .ok_or_else(|| bad_udf("a partition map refers to a partition which doesn't exist"))?;
+
+        if metadata {
+            maps.push(PartitionMap::Metadata {
+                physical: number,
+                extents: Vec::new(),
+            });
+        } else {
+            physical_maps.push((number, i as u16));
+            maps.push(PartitionMap::Physical { start });
+        }
+    }
+
+    // Metadata maps give the number of the partition they're in, which needs turning into the
+    // reference number of that partition's physical map
+    for map in &mut maps {
+        if let PartitionMap::Metadata { physical, .. } = map {
+            *physical = physical_maps
+                .iter()
+                .find(|(number, _)| number == physical)
+                .map(|(_, reference)| *reference)
+                .ok_or_else(|| bad_udf("a metadata partition has no physical partition"))?;
+        }
+    }
+
+    Ok(maps)
+}
+
+/// The block of the metadata file for the partition map with reference number `reference`
+fn metadata_file_location(lvd: &[u8], reference: usize) -> Result<u32> {
+    let mut pos = 440;
+
+    for _ in 0..reference {
+        pos += (lvd[pos + 1] as usize).max(2);
+    }
+
+    lvd.get(pos + 40..pos + 44)
+        .map(u32_le)
+        .ok_or_else(|| bad_udf("the metadata partition map is too short"))
+}
+
+/// The identifier of a descriptor's tag, if its checksum is valid
+fn tag_id(buf: &[u8]) -> Option<u16> {
+    let tag = buf.get(0..16)?;
+    let checksum = tag
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != 4)
+        .fold(0u8, |sum, (_, byte)| sum.wrapping_add(*byte));
+
+    (checksum == tag[4]).then(|| u16_le(&tag[0..2]))
+}
+
+/// The block number and partition reference number in a long allocation descriptor
+fn long_ad(ad: &[u8]) -> (u32, u16) {
+    (u32_le(&ad[4..8]), u16_le(&ad[8..10]))
+}
+
+/// Decode a dstring, a fixed-size field whose last byte gives the length of the string in it
+fn dstring(field: &[u8]) -> String {
+    let len = *field.last().unwrap_or(&0) as usize;
+    cs0(&field[..len.min(field.len() - 1)])
+}
+
+/// Decode a string in OSTA's CS0 character set. The first byte says whether each character is
+/// stored in 8 bits or 16.
+fn cs0(bytes: &[u8]) -> String {
+    match bytes.split_first() {
+        Some((8 | 254, chars)) => chars.iter().map(|&c| char::from(c)).collect(),
+        Some((16 | 255, chars)) => {
+            let units: Vec<u16> = chars
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Parse a 12-byte timestamp. The time zone is given in minutes from UTC, in the bottom 12 bits
+/// of the first field, and applies when the top four bits are 1.
+fn timestamp(bytes: &[u8]) -> Option<SystemTime> {
+    let type_and_zone = u16_le(&bytes[0..2]);
+    let time = system_time(
+        i16::from_le_bytes([bytes[2], bytes[3]]).into(),
+        bytes[4].into(),
+        bytes[5].into(),
+        bytes[6].into(),
+        bytes[7].into(),
+        bytes[8].into(),
+    )?;
+
+    // Sign extend the 12-bit offset. -2047 means the time zone wasn't recorded.
+    let offset = ((type_and_zone << 4) as i16 >> 4) as i64;
+    if type_and_zone >> 12 == 1 && offset != -2047 {
+        local_to_utc(time, offset)
+    } else {
+        Some(time)
+    }
+}
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn u32_le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes(bytes[0..4].try_into().unwrap())
+}
+
+fn bad_udf(message: &str) -> Error {
+    Error::FilesystemParse(format!("bad UDF filesystem: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        filesystem::{format_time, tests::write_at},
+        testing::TestDisc,
+    };
+
+    /// Where the test volume's partition starts
+    const PARTITION_START: u32 = 270;
+
+    const FILE_TYPE_FILE: u8 = 5;
+    const FILE_DIRECTORY: u8 = 0x02;
+
+    /// A descriptor tag, with its checksum filled in
+    fn tag(id: u16, location: u32) -> [u8; 16] {
+        let mut tag = [0; 16];
+        tag[0..2].copy_from_slice(&id.to_le_bytes());
+        tag[2] = 2;
+        tag[12..16].copy_from_slice(&location.to_le_bytes());
+        tag[4] = tag.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+
+        tag
+    }
+
+    fn long_ad(length: u32, block: u32) -> [u8; 16] {
+        let mut ad = [0; 16];
+        ad[0..4].copy_from_slice(&length.to_le_bytes());
+        ad[4..8].copy_from_slice(&block.to_le_bytes());
+
+        ad
+    }
+
+    /// A file entry, with `allocation` holding either short allocation descriptors or the
+    /// file's data, depending on `ad_type`
+    fn file_entry(
+        block: u32,
+        file_type: u8,
+        size: u64,
+        ad_type: u16,
+        allocation: &[u8],
+    ) -> Vec<u8> {
+        let mut entry = vec![0; USER_DATA_SIZE];
+        entry[0..16].copy_from_slice(&tag(TAG_FILE_ENTRY, block));
+        entry[27] = file_type;
+        entry[34..36].copy_from_slice(&ad_type.to_le_bytes());
+        entry[56..64].copy_from_slice(&size.to_le_bytes());
+
+        // 2001-02-03 05:05:06, an hour ahead of UTC
+        let zone = 0x1000u16 | 60;
+        entry[84..86].copy_from_slice(&zone.to_le_bytes());
+        entry[86..88].copy_from_slice(&2001u16.to_le_bytes());
+        entry[88..93].copy_from_slice(&[2, 3, 5, 5, 6]);
+
+        entry[172..176].copy_from_slice(&(allocation.len() as u32).to_le_bytes());
+        entry[176..176 + allocation.len()].copy_from_slice(allocation);
+
+        entry
+    }
+
+    fn short_ad(length: u32, block: u32) -> Vec<u8> {
+        [length.to_le_bytes(), block.to_le_bytes()].concat()
+    }
+
+    /// A file identifier descriptor, padded to a multiple of four bytes
+    fn file_identifier(characteristics: u8, name: &str, icb: u32) -> Vec<u8> {
+        let name = match name {
+            "" => Vec::new(),
+            name => [&[8], name.as_bytes()].concat(),
+        };
+
+        let mut fid = tag(TAG_FILE_IDENTIFIER, 0).to_vec();
+        fid.extend_from_slice(&[1, 0, characteristics, name.len() as u8]);
+        fid.extend_from_slice(&long_ad(USER_DATA_SIZE as u32, icb));
+        fid.extend_from_slice(&[0, 0]);
+        fid.extend_from_slice(&name);
+        fid.resize((fid.len() + 3) & !3, 0);
+
+        fid
+    }
+
+    /// A UDF 1.02 volume with one physical partition, holding README.TXT, a directory, and a
+    /// small file stored inside its own file entry
+    fn volume() -> Vec<u8> {
+        let mut image = vec![0; USER_DATA_SIZE * 256];
+
+        let mut anchor = tag(TAG_ANCHOR, 256).to_vec();
+        anchor.extend_from_slice(&(4 * USER_DATA_SIZE as u32).to_le_bytes());
+        anchor.extend_from_slice(&257u32.to_le_bytes());
+        write_at(&mut image, 256, 0, &anchor);
+
+        let mut partition = vec![0; USER_DATA_SIZE];
+        partition[0..16].copy_from_slice(&tag(TAG_PARTITION, 257));
+        partition[188..192].copy_from_slice(&PARTITION_START.to_le_bytes());
+        write_at(&mut image, 257, 0, &partition);
+
+        let mut lvd = vec![0; USER_DATA_SIZE];
+        lvd[0..16].copy_from_slice(&tag(TAG_LOGICAL_VOLUME, 258));
+        lvd[84..93].copy_from_slice(b"\x08TEST_UDF");
+        lvd[211] = 9;
+        lvd[212..216].copy_from_slice(&2048u32.to_le_bytes());
+        lvd[240..242].copy_from_slice(&0x0102u16.to_le_bytes());
+        lvd[248..264].copy_from_slice(&long_ad(USER_DATA_SIZE as u32, 0));
+        lvd[264..268].copy_from_slice(&6u32.to_le_bytes());
+        lvd[268..272].copy_from_slice(&1u32.to_le_bytes());
+        lvd[440..446].copy_from_slice(&[1, 6, 1, 0, 0, 0]);
+        write_at(&mut image, 258, 0, &lvd);
+
+        write_at(&mut image, 259, 0, &tag(TAG_TERMINATING, 259));
+
+        let block = |n: u32| PARTITION_START + n;
+
+        let mut fsd = vec![0; USER_DATA_SIZE];
+        fsd[0..16].copy_from_slice(&tag(TAG_FILE_SET, 0));
+        fsd[400..416].copy_from_slice(&long_ad(USER_DATA_SIZE as u32, 1));
+        write_at(&mut image, block(0), 0, &fsd);
+
+        let root = [
+            file_identifier(FILE_PARENT | FILE_DIRECTORY, "", 1),
+            file_identifier(0, "README.TXT", 3),
+            file_identifier(FILE_DIRECTORY, "Docs", 4),
+            file_identifier(FILE_DELETED, "OLD.TXT", 3),
+            file_identifier(0, "inline.txt", 5),
+        ]
+        .concat();
+        let root_size = root.len() as u32;
+        write_at(&mut image, block(2), 0, &root);
+        write_at(
+            &mut image,
+            block(1),
+            0,
+            &file_entry(
+                1,
+                FILE_TYPE_DIRECTORY,
+                root_size.into(),
+                SHORT_ALLOCATION,
+                &short_ad(root_size, 2),
+            ),
+        );
+
+        let readme = file_entry(3, FILE_TYPE_FILE, 13, SHORT_ALLOCATION, &short_ad(13, 6));
+        write_at(&mut image, block(3), 0, &readme);
+        write_at(&mut image, block(6), 0, b"Hello, world!");
+
+        let docs = file_identifier(FILE_PARENT | FILE_DIRECTORY, "", 1);
+        let docs_size = docs.len() as u32;
+        write_at(&mut image, block(7), 0, &docs);
+        write_at(
+            &mut image,
+            block(4),
+            0,
+            &file_entry(
+                4,
+                FILE_TYPE_DIRECTORY,
+                docs_size.into(),
+                SHORT_ALLOCATION,
+                &short_ad(docs_size, 7),
+            ),
+        );
+
+        let inline = file_entry(5, FILE_TYPE_FILE, 7, INLINE_DATA, b"inline!");
+        write_at(&mut image, block(5), 0, &inline);
+
+        image
+    }
+
+    #[test]
+    fn reads_a_volume() {
+        let disc = TestDisc::new("udf", &volume(), false);
+        let mut reader = disc.reader();
+        let udf = Udf::open(&mut reader, 0).unwrap().unwrap();
+
+        assert_eq!(udf.description(), "UDF 1.02");
+        assert_eq!(udf.volume_name(), "TEST_UDF");
+
+        let root = udf.read_dir(&mut reader, &udf.root()).unwrap();
+        let names: Vec<&str> = root.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["README.TXT", "Docs", "inline.txt"]);
+        assert_eq!(root[0].kind, EntryKind::File);
+        assert_eq!(root[1].kind, EntryKind::Directory);
+        assert_eq!(
+            format_time(root[0].modified.unwrap()),
+            "2001-02-03 04:05:06"
+        );
+
+        let mut data = Vec::new();
+        udf.read_file(&mut reader, &root[0], &mut data).unwrap();
+        assert_eq!(data, b"Hello, world!");
+
+        assert!(udf.read_dir(&mut reader, &root[1]).unwrap().is_empty());
+        assert_eq!(reader.read_extents(&root[2].extents).unwrap(), b"inline!");
+    }
+
+    #[test]
+    fn looks_up_paths() {
+        let disc = TestDisc::new("udf-lookup", &volume(), true);
+        let mut reader = disc.reader();
+        let udf = Udf::open(&mut reader, 0).unwrap().unwrap();
+
+        assert_eq!(udf.lookup(&mut reader, "readme.txt").unwrap().size, 13);
+        assert_eq!(
+            udf.lookup(&mut reader, "/Docs").unwrap().kind,
+            EntryKind::Directory
+        );
+        assert!(matches!(
+            udf.lookup(&mut reader, "OLD.TXT"),
+            Err(Error::NoSuchFile(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_bad_checksum() {
+        let mut image = volume();
+        image[256 * USER_DATA_SIZE + 4] ^= 0xFF;
+
+        let disc = TestDisc::new("udf-checksum", &image, false);
+        assert!(Udf::open(&mut disc.reader(), 0).unwrap().is_none());
+    }
+
+    #[test]
+    fn reports_a_missing_logical_volume() {
+        let mut image = volume();
+        write_at(&mut image, 258, 0, &tag(TAG_TERMINATING, 258));
+
+        let disc = TestDisc::new("udf-no-lvd", &image, false);
+        assert!(matches!(
+            Udf::open(&mut disc.reader(), 0),
+            Err(Error::FilesystemParse(_))
+        ));
+    }
+}
@@ -0,0 +1,632 @@
+use crate::bits::BitWriter;
+use md5::{Digest, Md5};
+use std::io::{self, Seek, SeekFrom, Write};
+
+// A small FLAC encoder for 16-bit stereo audio, which is all a CD can hold. It only uses FLAC's
+// fixed predictors rather than LPC, which keeps it simple at the cost of a few percent of
+// compression. The format is documented at https://xiph.org/flac/format.html.
+
+/// Encode interleaved 16-bit stereo samples into a series of FLAC frames of `block_size` samples
+/// each, with no stream header in front of them. The last frame may be shorter than the rest.
+/// Frames are numbered starting at `first_frame`.
+pub fn encode_frames(
+    samples: &[i16],
+    block_size: usize,
+    sample_rate: u32,
+    first_frame: u32,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    let blocks = samples.chunks(block_size * 2);
+
+    for (frame_number, block) in (first_frame..).zip(blocks) {
+        encode_frame(&mut out, block, sample_rate, frame_number);
+    }
+
+    out
+}
+
+/// The number of samples in each frame of the FLAC files written by `FlacWriter`
+const BLOCK_SIZE: usize = 4608;
+
+/// The type of a STREAMINFO metadata block
+const STREAMINFO: u8 = 0;
+
+/// The size of a STREAMINFO block, not including its header
+const STREAMINFO_SIZE: usize = 34;
+
+/// Writes a complete FLAC file of 44.1 kHz 16-bit stereo audio. Raw little endian samples, the
+/// way they are stored on a CD, are written to it, and the stream header is filled in once
+/// `finish` is called.
+pub struct FlacWriter<W: Write + Seek> {
+    writer: W,
+
+    /// Bytes of samples which don't yet make up a whole frame
+    pending: Vec<u8>,
+    num_frames: u32,
+    num_samples: u64,
+    min_frame_size: usize,
+    max_frame_size: usize,
+    md5: Md5,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    /// Start a FLAC file, with the given metadata blocks following the STREAMINFO block. Each is
+    /// given as its type and contents.
+    pub fn new(mut writer: W, metadata: &[(u8, Vec<u8>)]) -> io::Result<Self> {
+        writer.write_all(b"fLaC")?;
+
+        // STREAMINFO is written properly once all the audio has been seen
+        let blocks = [(STREAMINFO, vec![0; STREAMINFO_SIZE])];
+        let blocks = blocks.iter().chain(metadata);
+        let num_blocks = metadata.len() + 1;
+
+        for (i, (block_type, data)) in blocks.enumerate() {
+            let last = if i == num_blocks - 1 { 0x80 } else { 0 };
+            writer.write_all(&[last | block_type])?;
+            writer.write_all(&(data.len() as u32).to_be_bytes()[1..])?;
+            writer.write_all(data)?;
+        }
+
+        Ok(Self {
+            writer,
+            pending: Vec::with_capacity(BLOCK_SIZE * 4),
+            num_frames: 0,
+            num_samples: 0,
+            min_frame_size: usize::MAX,
+            max_frame_size: 0,
+            md5: Md5::new(),
+        })
+    }
+
+    /// Encode any remaining audio and fill in the STREAMINFO block
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            let pending = std::mem::take(&mut self.pending);
+            self.encode(&pending)?;
+        }
+
+        let mut w = BitWriter::default();
+        w.write(BLOCK_SIZE as u32, 16); // min block size
+        w.write(BLOCK_SIZE as u32, 16); // max block size
+        w.write(self.min_frame_size.min(self.max_frame_size) as u32, 24);
+        w.write(self.max_frame_size as u32, 24);
+        w.write(44_100, 20);
+        w.write(1, 3); // 2 channels
+        w.write(15, 5); // 16 bits per sample
+        w.write((self.num_samples >> 32) as u32, 4);
+        w.write(self.num_samples as u32, 32);
+
+        let mut stream_info = w.finish();
+        stream_info.extend_from_slice(&self.md5.finalize());
+
+        // Skip the "fLaC" marker and the block header
+        self.writer.seek(SeekFrom::Start(8))?;
+        self.writer.write_all(&stream_info)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn encode(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let samples: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]))
+            .collect();
+        let frame = encode_frames(&samples, BLOCK_SIZE, 44_100, self.num_frames);
+
+        self.min_frame_size = self.min_frame_size.min(frame.len());
+        self.max_frame_size = self.max_frame_size.max(frame.len());
+        self.num_frames += 1;
+        self.num_samples += samples.len() as u64 / 2;
+
+        self.writer.write_all(&frame)
+    }
+}
+
+impl<W: Write + Seek> Write for FlacWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.md5.update(buf);
+        self.pending.extend_from_slice(buf);
+
+        let frame_bytes = BLOCK_SIZE * 4;
+        while self.pending.len() >= frame_bytes {
+            let frame: Vec<u8> = self.pending.drain(..frame_bytes).collect();
+            self.encode(&frame)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The ways a FLAC frame can store a pair of channels
+#[derive(Clone, Copy)]
+enum Stereo {
+    Independent,
+    LeftSide,
+    SideRight,
+    MidSide,
+}
+
+fn encode_frame(out: &mut Vec<u8>, block: &[i16], sample_rate: u32, frame_number: u32) {
+    let left: Vec<i32> = block.iter().step_by(2).map(|&x| x.into()).collect();
+    let right: Vec<i32> = block.iter().skip(1).step_by(2).map(|&x| x.into()).collect();
+    let side: Vec<i32> = left.iter().zip(&right).map(|(l, r)| l - r).collect();
+    let mid: Vec<i32> = left.iter().zip(&right).map(|(l, r)| (l + r) >> 1).collect();
+
+    let left_sub = Subframe::best(&left, 16);
+    let right_sub = Subframe::best(&right, 16);
+    let side_sub = Subframe::best(&side, 17);
+    let mid_sub = Subframe::best(&mid, 16);
+
+    let (stereo, first, second) = [
+        (Stereo::Independent, &left_sub, &right_sub),
+        (Stereo::LeftSide, &left_sub, &side_sub),
+        (Stereo::SideRight, &side_sub, &right_sub),
+        (Stereo::MidSide, &mid_sub, &side_sub),
+    ]
+    .into_iter()
+    .min_by_key(|(_, a, b)| a.bits + b.bits)
+    .unwrap();
+
+    let block_size = left.len();
+    let mut w = BitWriter::default();
+
+    // Frame header
+    w.write(0b11_1111_1111_1110, 14); // sync code
+    w.write(0, 1); // reserved
+    w.write(0, 1); // fixed block size
+
+    let (size_code, size_extra) = match block_size {
+        192 => (1, None),
+        576 | 1152 | 2304 | 4608 => (2 + (block_size / 576).trailing_zeros(), None),
+        256 | 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768 => {
+            (8 + (block_size / 256).trailing_zeros(), None)
+        }
+        n if n <= 256 => (6, Some((n - 1, 8))),
+        n => (7, Some((n - 1, 16))),
+    };
+    w.write(size_code, 4);
+    w.write(sample_rate_code(sample_rate), 4);
+
+    let channel_code = match stereo {
+        Stereo::Independent => 0b0001,
+        Stereo::LeftSide => 0b1000,
+        Stereo::SideRight => 0b1001,
+        Stereo::MidSide => 0b1010,
+    };
+    w.write(channel_code, 4);
+    w.write(0b100, 3); // 16 bits per sample
+    w.write(0, 1); // reserved
+    write_utf8(&mut w, frame_number);
+
+    if let Some((value, bits)) = size_extra {
+        w.write(value as u32, bits);
+    }
+
+    let crc = crc8(w.bytes());
+    w.write(crc.into(), 8);
+
+    // Subframes
+    let (first_samples, first_bps) = match stereo {
+        Stereo::Independent | Stereo::LeftSide => (&left, 16),
+        Stereo::SideRight => (&side, 17),
+        Stereo::MidSide => (&mid, 16),
+    };
+    let (second_samples, second_bps) = match stereo {
+        Stereo::Independent | Stereo::SideRight => (&right, 16),
+        Stereo::LeftSide | Stereo::MidSide => (&side, 17),
+    };
+    first.write(&mut w, first_samples, first_bps);
+    second.write(&mut w, second_samples, second_bps);
+
+    // Frame footer
+    w.align();
+    let crc = crc16(w.bytes());
+    w.write(crc.into(), 16);
+
+    out.extend_from_slice(w.bytes());
+}
+
+/// The encoding chosen for a single channel of a frame, along with how many bits it will take
+struct Subframe {
+    kind: SubframeKind,
+    bits: usize,
+}
+
+enum SubframeKind {
+    Constant,
+    Verbatim,
+    Fixed {
+        order: usize,
+        partition_order: u32,
+        rice_params: Vec<u32>,
+    },
+}
+
+impl Subframe {
+    /// Find the smallest way to encode `samples`, which are `bps` bits wide
+    fn best(samples: &[i32], bps: usize) -> Self {
+        // Subframe header
+        let header_bits = 8;
+
+        if samples.iter().all(|&x| x == samples[0]) {
+            return Self {
+                kind: SubframeKind::Constant,
+                bits: header_bits + bps,
+            };
+        }
+
+        let mut best = Self {
+            kind: SubframeKind::Verbatim,
+            bits: header_bits + bps * samples.len(),
+        };
+
+        for order in 0..=4.min(samples.len() - 1) {
+            let residual = fixed_residual(samples, order);
+            let (partition_order, rice_params, residual_bits) =
+                best_partitioning(&residual, samples.len(), order);
+            let bits = header_bits + bps * order + residual_bits;
+
+            if bits < best.bits {
+                best = Self {
+                    kind: SubframeKind::Fixed {
+                        order,
+                        partition_order,
+                        rice_params,
+                    },
+                    bits,
+                };
+            }
+        }
+
+        best
+    }
+
+    fn write(&self, w: &mut BitWriter, samples: &[i32], bps: usize) {
+        match &self.kind {
+            SubframeKind::Constant => {
+                w.write(0, 8);
+                write_signed(w, samples[0], bps);
+            }
+            SubframeKind::Verbatim => {
+                w.write(0b0000_0010, 8);
+                for &x in samples {
+                    write_signed(w, x, bps);
+                }
+            }
+            SubframeKind::Fixed {
+                order,
+                partition_order,
+                rice_params,
+            } => {
+                w.write((0b00_1000 | *order as u32) << 1, 8);
+                for &x in &samples[0..*order] {
+                    write_signed(w, x, bps);
+                }
+
+                let residual = fixed_residual(samples, *order);
+                let partition_size = samples.len() >> partition_order;
+
+                w.write(0, 2); // 4-bit rice parameters
+                w.write(*partition_order, 4);
+
+                let mut start = 0;
+                for (i, &param) in rice_params.iter().enumerate() {
+                    let end = (i + 1) * partition_size - order;
+                    w.write(param, 4);
+
+                    for &r in &residual[start..end] {
+                        write_rice(w, zigzag(r), param);
+                    }
+
+                    start = end;
+                }
+            }
+        }
+    }
+}
+
+/// The residual left over after applying one of FLAC's fixed polynomial predictors. The first
+/// `order` samples are used to warm up the predictor, so there are no residuals for them.
+fn fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    let s = samples;
+
+    (order..samples.len())
+        .map(|i| match order {
+            0 => s[i],
+            1 => s[i] - s[i - 1],
+            2 => s[i] - 2 * s[i - 1] + s[i - 2],
+            3 => s[i] - 3 * s[i - 1] + 3 * s[i - 2] - s[i - 3],
+            _ => s[i] - 4 * s[i - 1] + 6 * s[i - 2] - 4 * s[i - 3] + s[i - 4],
+        })
+        .collect()
+}
+
+/// Choose the partition order and rice parameters which encode `residual` in the fewest bits.
+/// Returns the partition order, the parameter of each partition, and the total number of bits.
+fn best_partitioning(
+    residual: &[i32],
+    block_size: usize,
+    predictor_order: usize,
+) -> (u32, Vec<u32>, usize) {
+    let mut best: Option<(u32, Vec<u32>, usize)> = None;
+
+    for partition_order in 0..=8 {
+        let partitions = 1 << partition_order;
+        if !block_size.is_multiple_of(partitions) || block_size / partitions <= predictor_order {
+            break;
+        }
+
+        let partition_size = block_size / partitions;
+        let mut params = Vec::with_capacity(partitions);
+        let mut bits = 6; // coding method & partition order
+
+        let mut start = 0;
+        for i in 0..partitions {
+            let end = (i + 1) * partition_size - predictor_order;
+            let (param, partition_bits) = best_rice_param(&residual[start..end]);
+            params.push(param);
+            bits += 4 + partition_bits;
+            start = end;
+        }
+
+        if best.as_ref().is_none_or(|(_, _, b)| bits < *b) {
+            best = Some((partition_order, params, bits));
+        }
+    }
+
+    best.unwrap()
+}
+
+/// Find the rice parameter which encodes `residual` in the fewest bits
+fn best_rice_param(residual: &[i32]) -> (u32, usize) {
+    (0..15)
+        .map(|param| {
+            let bits = residual
+                .iter()
+                .map(|&r| (zigzag(r) >> param) as usize + 1 + param as usize)
+                .sum();
+            (param, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+}
+
+/// Fold signed values into unsigned ones, so that small magnitudes stay small
+fn zigzag(x: i32) -> u32 {
+    ((x << 1) ^ (x >> 31)) as u32
+}
+
+fn sample_rate_code(sample_rate: u32) -> u32 {
+    match sample_rate {
+        88_200 => 0b0001,
+        176_400 => 0b0010,
+        192_000 => 0b0011,
+        8_000 => 0b0100,
+        16_000 => 0b0101,
+        22_050 => 0b0110,
+        24_000 => 0b0111,
+        32_000 => 0b1000,
+        44_100 => 0b1001,
+        48_000 => 0b1010,
+        96_000 => 0b1011,
+        _ => 0b0000, // take it from the STREAMINFO block
+    }
+}
+
+fn write_signed(w: &mut BitWriter, value: i32, num_bits: usize) {
+    w.write(value as u32, num_bits);
+}
+
+fn write_rice(w: &mut BitWriter, value: u32, param: u32) {
+    let mut quotient = value >> param;
+    while quotient >= 32 {
+        w.write(0, 32);
+        quotient -= 32;
+    }
+
+    w.write(1, quotient as usize + 1);
+    w.write(value, param as usize);
+}
+
+/// Write a value in the UTF-8-like variable length encoding FLAC uses for frame numbers
+fn write_utf8(w: &mut BitWriter, value: u32) {
+    if value < 0x80 {
+        w.write(value, 8);
+        return;
+    }
+
+    let extra_bytes = match value {
+        0..=0x7FF => 1,
+        0x800..=0xFFFF => 2,
+        0x1_0000..=0x1F_FFFF => 3,
+        0x20_0000..=0x3FF_FFFF => 4,
+        _ => 5,
+    };
+
+    let lead_mask = !(0xFFu32 >> (extra_bytes + 1)) & 0xFF;
+    w.write(lead_mask | (value >> (6 * extra_bytes)), 8);
+
+    for i in (0..extra_bytes).rev() {
+        w.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Pseudo-random samples from a xorshift generator
+    fn noise(len: usize, mut seed: u32) -> Vec<i16> {
+        (0..len)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as i16
+            })
+            .collect()
+    }
+
+    /// Interleaved stereo test signals of `frames` samples per channel
+    fn signals(frames: usize) -> Vec<(&'static str, Vec<i16>)> {
+        let tone = (0..frames)
+            .flat_map(|i| {
+                let t = i as f64 / 44_100.0;
+                let left = (t * 440.0 * std::f64::consts::TAU).sin() * 20_000.0;
+                let right = (t * 660.0 * std::f64::consts::TAU).sin() * 12_000.0;
+                [left as i16, right as i16]
+            })
+            .collect();
+        let extremes = (0..frames)
+            .flat_map(|i| match i % 3 {
+                0 => [i16::MAX, i16::MIN],
+                1 => [i16::MIN, i16::MAX],
+                _ => [i16::MIN, i16::MIN],
+            })
+            .collect();
+
+        vec![
+            ("tone", tone),
+            ("noise", noise(frames * 2, 0xC0FF_EE11)),
+            ("silence", vec![0; frames * 2]),
+            ("constant", vec![-1234; frames * 2]),
+            ("extremes", extremes),
+        ]
+    }
+
+    /// Decode frames written by `encode_frames` with claxon, after putting a stream header on
+    /// the front of them
+    fn decode(frames: &[u8], block_size: usize) -> Vec<i16> {
+        let mut w = BitWriter::default();
+        w.write(block_size as u32, 16);
+        w.write(block_size as u32, 16);
+        w.write(0, 24);
+        w.write(0, 24);
+        w.write(44_100, 20);
+        w.write(1, 3);
+        w.write(15, 5);
+        w.write(0, 36);
+
+        let mut stream = b"fLaC".to_vec();
+        stream.extend_from_slice(&[0x80, 0, 0, STREAMINFO_SIZE as u8]);
+        stream.extend(w.finish());
+        stream.extend_from_slice(&[0; 16]);
+        stream.extend_from_slice(frames);
+
+        let mut reader = claxon::FlacReader::new(stream.as_slice()).unwrap();
+        let mut blocks = reader.blocks();
+        let mut samples = Vec::new();
+        let mut buffer = Vec::new();
+
+        while let Some(block) = blocks.read_next_or_eof(buffer).unwrap() {
+            for (left, right) in block.channel(0).iter().zip(block.channel(1)) {
+                samples.push(*left as i16);
+                samples.push(*right as i16);
+            }
+            buffer = block.into_buffer();
+        }
+
+        samples
+    }
+
+    #[test]
+    fn claxon_decodes_encoded_frames() {
+        for block_size in [192, 588, 4608] {
+            // Three whole blocks and a short one at the end
+            for last_block in [1, 100, block_size - 1] {
+                for (name, samples) in signals(block_size * 3 + last_block) {
+                    let frames = encode_frames(&samples, block_size, 44_100, 0);
+
+                    assert!(
+                        decode(&frames, block_size) == samples,
+                        "{name} didn't round trip with a block size of {block_size} and a last \
+                         block of {last_block}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frame_numbers_can_start_anywhere() {
+        let samples = noise(192 * 2 * 4, 7);
+
+        for first_frame in [0x7F, 0x80, 0x7FF, 0x800, 0xFFFF, 0x1_0000] {
+            let frames = encode_frames(&samples, 192, 44_100, first_frame);
+            assert!(decode(&frames, 192) == samples, "frame {first_frame}");
+        }
+    }
+
+    fn utf8(value: u32) -> Vec<u8> {
+        let mut w = BitWriter::default();
+        write_utf8(&mut w, value);
+        w.finish()
+    }
+
+    #[test]
+    fn write_utf8_matches_utf8() {
+        // Values up to 0x10FFFF are encoded the same way as characters
+        for value in [0, 0x7F, 0x80, 0x7FF, 0x800, 0xFFFF, 0x1_0000, 0x10_FFFF] {
+            let c = char::from_u32(value).unwrap();
+            let mut buf = [0; 4];
+            assert_eq!(
+                utf8(value),
+                c.encode_utf8(&mut buf).as_bytes(),
+                "{value:#X}"
+            );
+        }
+    }
+
+    #[test]
+    fn write_utf8_extends_past_utf8() {
+        let cases: [(u32, &[u8]); 7] = [
+            (0x1F_FFFF, &[0xF7, 0xBF, 0xBF, 0xBF]),
+            (0x20_0000, &[0xF8, 0x88, 0x80, 0x80, 0x80]),
+            (0x3FF_FFFF, &[0xFB, 0xBF, 0xBF, 0xBF, 0xBF]),
+            (0x400_0000, &[0xFC, 0x84, 0x80, 0x80, 0x80, 0x80]),
+            (0x7FFF_FFFF, &[0xFD, 0xBF, 0xBF, 0xBF, 0xBF, 0xBF]),
+            (0xD800, &[0xED, 0xA0, 0x80]),
+            (0x11_0000, &[0xF4, 0x90, 0x80, 0x80]),
+        ];
+
+        for (value, expected) in cases {
+            assert_eq!(utf8(value), expected, "{value:#X}");
+        }
+    }
+
+    #[test]
+    fn crcs_match_check_values() {
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc16(b"123456789"), 0xFEE8);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{
+    convert::track_to_bin,
+    error::Result,
+    loader::{load_mds, LoadOptions},
+    mds::{Mds, Track},
+};
+use crc32fast::Hasher as Crc32;
+use md5::Md5;
+use sha1::{Digest, Sha1};
+use std::{io::Write, path::Path};
+
+/// The checksums of some data, in the forms dump databases list them
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checksums {
+    pub size: u64,
+    pub crc32: u32,
+    pub md5: [u8; 16],
+    pub sha1: [u8; 20],
+}
+
+impl Checksums {
+    pub fn md5_hex(&self) -> String {
+        hex(&self.md5)
+    }
+
+    pub fn sha1_hex(&self) -> String {
+        hex(&self.sha1)
+    }
+}
+
+/// Computes every checksum at once as data is written to it
+#[derive(Default)]
+struct Hasher {
+    size: u64,
+    crc32: Crc32,
+    md5: Md5,
+    sha1: Sha1,
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.size += data.len() as u64;
+        self.crc32.update(data);
+        self.md5.update(data);
+        self.sha1.update(data);
+    }
+
+    fn finish(self) -> Checksums {
+        Checksums {
+            size: self.size,
+            crc32: self.crc32.finalize(),
+            md5: self.md5.finalize().into(),
+            sha1: self.sha1.finalize().into(),
+        }
+    }
+}
+
+/// Feeds everything written to it into the hashers of both a track and the whole image
+struct TrackAndImage<'a> {
+    track: &'a mut Hasher,
+    image: &'a mut Hasher,
+}
+
+impl Write for TrackAndImage<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.track.update(buf);
+        self.image.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Print the CRC32, MD5, and SHA-1 of every track, and of the whole image
+pub fn hash<P: AsRef<Path>>(mds_file: P, options: &LoadOptions) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let (tracks, image) = hash_tracks(&mds, &mds_file, options)?;
+
+    println!("{}", mds_file.as_ref().to_str().unwrap_or("--none--"));
+
+    for (track, checksums) in &tracks {
+        println!("Track {} ({:?})", track.number(), track.mode);
+        print_checksums(checksums);
+    }
+
+    println!("Image");
+    print_checksums(&image);
+
+    Ok(())
+}
+
+/// Hash the data of every track, as it would be written to a .bin, along with the data of all
+/// the tracks one after another. Everything is read only once.
+pub fn hash_tracks<'a, P: AsRef<Path>>(
+    mds: &'a Mds,
+    mds_file: P,
+    options: &LoadOptions,
+) -> Result<(Vec<(&'a Track, Checksums)>, Checksums)> {
+    let mut image = Hasher::default();
+    let mut tracks = Vec::new();
+
+    for track in mds.sessions().flat_map(|session| session.data_tracks()) {
+        let mut track_hasher = Hasher::default();
+        let writer = TrackAndImage {
+            track: &mut track_hasher,
+            image: &mut image,
+        };
+
+        track_to_bin(track, &mds_file, options, writer)?;
+        tracks.push((track, track_hasher.finish()));
+    }
+
+    Ok((tracks, image.finish()))
+}
+
+/// The checksums of some data which is already in memory
+pub fn checksums(data: &[u8]) -> Checksums {
+    let mut hasher = Hasher::default();
+    hasher.update(data);
+    hasher.finish()
+}
+
+fn print_checksums(checksums: &Checksums) {
+    println!("  Size:  {}", checksums.size);
+    println!("  CRC32: {:08x}", checksums.crc32);
+    println!("  MD5:   {}", checksums.md5_hex());
+    println!("  SHA-1: {}", checksums.sha1_hex());
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+
+    #[test]
+    fn checksums_match_the_tracks_written_as_bins() {
+        let cue = "FILE disc.bin BINARY\n\
+                   TRACK 01 MODE1/2352\n\
+                   INDEX 01 00:00:00\n\
+                   TRACK 02 AUDIO\n\
+                   INDEX 00 00:00:04\n\
+                   INDEX 01 00:00:06\n";
+        let data: Vec<u8> = (0..2352 * 10).map(|i| (i % 251) as u8).collect();
+        let disc = TestDisc::from_cue("hash", cue, &[("disc.bin", &data)]);
+
+        let (tracks, image) = hash_tracks(disc.mds(), disc.mds_path(), disc.options()).unwrap();
+        assert_eq!(tracks.len(), 2);
+
+        let mut all_bins = Vec::new();
+        for (track, track_checksums) in &tracks {
+            let mut bin = Vec::new();
+            track_to_bin(track, disc.mds_path(), disc.options(), &mut bin).unwrap();
+
+            assert_eq!(*track_checksums, checksums(&bin));
+            all_bins.extend(bin);
+        }
+
+        assert_eq!(image, checksums(&all_bins));
+        assert_eq!(image.size, data.len() as u64);
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    error::{Error, Result},
+    filesystem::{BootCatalog, BootImage, Emulation, Platform},
+    loader::LoadOptions,
+    locate::track_file_paths,
+    mds::{Mds, SubChannels, Track, TrackMode},
+    platform::DiscIdentity,
+};
+use serde::Serialize;
+use std::{io::stdout, path::Path};
+
+// The JSON printed by `mds info --format json`. These structs are the schema documented in the
+// README, so fields must only ever be added to them. Anything which changes the meaning of an
+// existing field needs a new SCHEMA_VERSION.
+
+/// The version of the schema, which is bumped whenever a change isn't backwards compatible.
+/// Version 2 added `expected_data_file`, as `data_file` is `null` for files that can't be found.
+const SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct MdsInfo {
+    schema_version: u32,
+    file: String,
+    version: String,
+    media_type: &'static str,
+    size: usize,
+    platform: Option<&'static str>,
+    serial: Option<String>,
+    sessions: Vec<SessionInfo>,
+    boot_catalog: Option<BootCatalogInfo>,
+}
+
+#[derive(Serialize)]
+struct SessionInfo {
+    number: usize,
+    first_sector: i32,
+    last_sector: i32,
+    num_sectors: i32,
+    tracks: Vec<TrackInfo>,
+}
+
+#[derive(Serialize)]
+struct TrackInfo {
+    number: usize,
+    mode: &'static str,
+    control: u8,
+    subchannels: &'static str,
+    sector_size: usize,
+    start_sector: i32,
+    start_offset: u64,
+    num_sectors: usize,
+    pregap_sectors: usize,
+    data_file: Option<String>,
+    expected_data_file: Option<String>,
+    data_files: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct BootCatalogInfo {
+    sector: u32,
+    images: Vec<BootImageInfo>,
+}
+
+#[derive(Serialize)]
+struct BootImageInfo {
+    default: bool,
+    bootable: bool,
+    platform: String,
+    emulation: String,
+    load_segment: u16,
+    system_type: u8,
+    sector_count: u16,
+    start_sector: u32,
+    size: u64,
+}
+
+/// Print an .mds file's sessions and tracks as a JSON document
+pub fn print_json<P: AsRef<Path>>(
+    mds: &Mds,
+    mds_file: P,
+    options: &LoadOptions,
+    identity: Option<&DiscIdentity>,
+    boot: Option<&BootCatalog>,
+) -> Result<()> {
+    let info = mds_info(mds, mds_file, options, identity, boot);
+
+    let mut out = stdout().lock();
+    serde_json::to_writer_pretty(&mut out, &info).map_err(|err| Error::Io(err.into()))?;
+    println!();
+
+    Ok(())
+}
+
+fn mds_info<P: AsRef<Path>>(
+    mds: &Mds,
+    mds_file: P,
+    options: &LoadOptions,
+    identity: Option<&DiscIdentity>,
+    boot: Option<&BootCatalog>,
+) -> MdsInfo {
+    MdsInfo {
+        schema_version: SCHEMA_VERSION,
+        file: mds_file.as_ref().to_string_lossy().to_string(),
+        version: mds.version().to_string(),
+        media_type: media_type_name(mds),
+        size: mds.byte_len(),
+        platform: identity.map(|identity| identity.platform.id()),
+        serial: identity.and_then(|identity| identity.serial.clone()),
+        sessions: mds
+            .sessions()
+            .map(|session| SessionInfo {
+                number: session.number(),
+                first_sector: session.start_sector,
+                last_sector: session.end_sector,
+                num_sectors: session.end_sector - session.start_sector,
+                tracks: session
+                    .data_tracks()
+                    .map(|track| track_info(track, &mds_file, options))
+                    .collect(),
+            })
+            .collect(),
+        boot_catalog: boot.map(|catalog| BootCatalogInfo {
+            sector: catalog.sector,
+            images: catalog.images.iter().map(boot_image_info).collect(),
+        }),
+    }
+}
+
+fn track_info<P: AsRef<Path>>(track: &Track, mds_file: P, options: &LoadOptions) -> TrackInfo {
+    let paths = track_file_paths(&mds_file, track, options);
+    let data_files: Vec<String> = paths
+        .iter()
+        .map(|(path, _)| path.to_string_lossy().to_string())
+        .collect();
+
+    // `data_file` has always been `null` for files which can't be found, so where they were
+    // looked for is given separately
+    let data_file = paths
+        .first()
+        .filter(|(_, found)| *found)
+        .map(|(path, _)| path.to_string_lossy().to_string());
+
+    TrackInfo {
+        number: track.number(),
+        mode: track_mode_name(track.mode),
+        control: track.control(),
+        subchannels: match track.num_subchannels {
+            SubChannels::None => "none",
+            SubChannels::Eight => "pw96",
+        },
+        sector_size: track.sector_size(),
+        start_sector: track.track_start_sector,
+        start_offset: track.track_start_offset,
+        num_sectors: track.num_sectors(),
+        pregap_sectors: track.pregap_sectors(),
+        data_file,
+        expected_data_file: data_files.first().cloned(),
+        data_files,
+    }
+}
+
+fn boot_image_info(image: &BootImage) -> BootImageInfo {
+    BootImageInfo {
+        default: image.default,
+        bootable: image.bootable,
+        platform: match image.platform {
+            Platform::X86 => "x86".to_string(),
+            Platform::PowerPc => "powerpc".to_string(),
+            Platform::Mac => "mac".to_string(),
+            Platform::Efi => "efi".to_string(),
+            Platform::Unknown(id) => format!("0x{id:02x}"),
+        },
+        emulation: match image.emulation {
+            Emulation::None => "none".to_string(),
+            Emulation::Floppy1200 => "floppy_1200k".to_string(),
+            Emulation::Floppy1440 => "floppy_1440k".to_string(),
+            Emulation::Floppy2880 => "floppy_2880k".to_string(),
+            Emulation::HardDisk => "hard_disk".to_string(),
+            Emulation::Unknown(media) => media.to_string(),
+        },
+        load_segment: image.load_segment,
+        system_type: image.system_type,
+        sector_count: image.sector_count,
+        start_sector: image.start_sector,
+        size: image.size,
+    }
+}
+
+fn track_mode_name(mode: TrackMode) -> &'static str {
+    match mode {
+        TrackMode::None => "none",
+        TrackMode::Audio => "audio",
+        TrackMode::Mode1 => "mode1",
+        TrackMode::Mode2 => "mode2",
+        TrackMode::Mode2Form1 => "mode2_form1",
+        TrackMode::Mode2Form2 => "mode2_form2",
+    }
+}
+
+fn media_type_name(mds: &Mds) -> &'static str {
+    use crate::mds::MediaType::*;
+
+    match mds.media_type() {
+        CdRom => "cd_rom",
+        CdR => "cd_r",
+        CdRw => "cd_rw",
+        DvdRom => "dvd_rom",
+        DvdR => "dvd_r",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+    use serde_json::{json, Value};
+
+    fn info_json(disc: &TestDisc) -> Value {
+        let info = mds_info(disc.mds(), disc.mds_path(), disc.options(), None, None);
+        serde_json::to_value(info).unwrap()
+    }
+
+    #[test]
+    fn tracks_give_their_data_files() {
+        let disc = TestDisc::new("json-found", &[0; 2048], false);
+        let info = info_json(&disc);
+        let bin = disc.dir().join("disc.bin").to_string_lossy().to_string();
+
+        assert_eq!(info["schema_version"], 2);
+        let track = &info["sessions"][0]["tracks"][0];
+        assert_eq!(track["data_file"], json!(bin));
+        assert_eq!(track["expected_data_file"], json!(bin));
+        assert_eq!(track["data_files"], json!([bin]));
+    }
+
+    #[test]
+    fn missing_data_files_are_still_expected() {
+        let disc = TestDisc::new("json-missing", &[0; 2048], false);
+        std::fs::remove_file(disc.dir().join("disc.bin")).unwrap();
+        let info = info_json(&disc);
+        let bin = disc.dir().join("disc.bin").to_string_lossy().to_string();
+
+        let track = &info["sessions"][0]["tracks"][0];
+        assert_eq!(track["data_file"], Value::Null);
+        assert_eq!(track["expected_data_file"], json!(bin));
+    }
+}
@@ -0,0 +1,151 @@
+mod json;
+
+use crate::{
+    args::InfoFormat,
+    error::{Error, Result},
+    filesystem::{boot_catalog, BootCatalog},
+    loader::{load_mds, LoadOptions},
+    locate::track_file_paths,
+    mds::Mds,
+    platform::{identify, DiscIdentity},
+    util::pluralize,
+};
+use std::path::Path;
+
+/// Print information about an .mds file and the tracks it describes, in the given format
+pub fn info<P: AsRef<Path>>(mds_file: P, options: &LoadOptions, format: &InfoFormat) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+
+    // The rest of the information comes from the .mds, so a disc whose data files can't be read
+    // is shown as unidentified and not bootable rather than failing. A broken boot catalog or
+    // filesystem is noted, but the rest of the information is still printed.
+    let boot = match boot_catalog(&mds, mds_file.as_ref(), options) {
+        Ok(catalog) => catalog,
+        Err(err) => {
+            if let Error::FilesystemParse(_) = err {
+                eprintln!("Couldn't read the El Torito boot catalog: {err}");
+            }
+            None
+        }
+    };
+    let identity = match identify(&mds, mds_file.as_ref(), options) {
+        Ok(identity) => identity,
+        Err(err) => {
+            if let Error::FilesystemParse(_) = err {
+                eprintln!("Couldn't work out the disc's platform: {err}");
+            }
+            None
+        }
+    };
+
+    match format {
+        InfoFormat::Text => print_text(&mds, mds_file, options, identity.as_ref(), boot.as_ref()),
+        InfoFormat::Json => {
+            json::print_json(&mds, mds_file, options, identity.as_ref(), boot.as_ref())
+        }
+    }
+}
+
+fn print_text<P: AsRef<Path>>(
+    mds: &Mds,
+    mds_file: P,
+    options: &LoadOptions,
+    identity: Option<&DiscIdentity>,
+    boot: Option<&BootCatalog>,
+) -> Result<()> {
+    let file_size = mds.byte_len();
+    let num_sessions = mds.sessions().count();
+    let num_tracks = mds.sessions().map(|sess| sess.data_tracks().count()).sum();
+    let version = mds.version();
+    let media_type = mds.media_type();
+
+    println!("{}", mds_file.as_ref().to_str().unwrap_or("--none--"));
+    println!(
+        "MDS v{version} | {media_type}, {file_size} {}, {num_sessions} {}, {num_tracks} {}",
+        pluralize("byte", file_size),
+        pluralize("session", num_sessions),
+        pluralize("track", num_tracks),
+    );
+
+    if let Some(identity) = identity {
+        println!("Platform: {}", identity.platform);
+        if let Some(serial) = &identity.serial {
+            println!("Serial:   {serial}");
+        }
+    }
+
+    for (i, session) in mds.sessions().enumerate() {
+        let first_sector = session.start_sector;
+        let last_sector = session.end_sector;
+        let total_sectors = last_sector - first_sector;
+
+        println!("Session {}", i + 1);
+        println!("  First sector:   {first_sector:<9} (0x{first_sector:X})");
+        println!("  Last sector:    {last_sector:<9} (0x{last_sector:X})");
+        println!("  Total sectors:  {total_sectors:<9} (0x{total_sectors:X})");
+
+        for (i, track) in session.data_tracks().enumerate() {
+            let filenames: Vec<String> = track_file_paths(&mds_file, track, options)
+                .into_iter()
+                .map(|(path, found)| match found {
+                    true => path.display().to_string(),
+                    false => format!("{} (missing)", path.display()),
+                })
+                .collect();
+            let filename = match filenames.as_slice() {
+                [] => "--none--".to_owned(),
+                names => names.join(&format!("\n{:18}", "")),
+            };
+
+            let num_sectors = track.num_sectors();
+            let sector_size = track.sector_size();
+            let track_offset = track.track_start_offset;
+            let sector_offset = track.track_start_sector;
+            let timecode = track.time_str();
+            let total_size = sector_size * num_sectors / 1_000_000;
+
+            println!("  Track {}", i + 1);
+            println!("    Mode:         {:?}", track.mode);
+            println!("    Subchannels:  {:?}", track.num_subchannels);
+            println!("    Data file:    {filename}");
+            println!("    Time offset:  {timecode}");
+            println!("    First byte:   {track_offset:<9} (0x{track_offset:X?})");
+            println!("    First sector: {sector_offset:<9} (0x{sector_offset:X?})");
+            println!("    Sectors:      {num_sectors:<9} (0x{num_sectors:X?})");
+            println!("    Sector size:  {sector_size:<9} (0x{sector_size:X?})");
+            println!("    Approx Size:  {total_size}MB");
+        }
+    }
+
+    if let Some(catalog) = boot {
+        print_boot_catalog(catalog);
+    }
+
+    Ok(())
+}
+
+fn print_boot_catalog(catalog: &BootCatalog) {
+    let sector = catalog.sector;
+    println!("El Torito boot catalog");
+    println!("  Sector:         {sector:<9} (0x{sector:X})");
+
+    for (i, image) in catalog.images.iter().enumerate() {
+        let start = image.start_sector;
+        let count = image.sector_count;
+        let size = image.size;
+        let default = if image.default { " (default)" } else { "" };
+
+        println!("  Boot image {}{default}", i + 1);
+        println!("    Platform:     {}", image.platform);
+        println!("    Emulation:    {}", image.emulation);
+        println!(
+            "    Bootable:     {}",
+            if image.bootable { "yes" } else { "no" }
+        );
+        println!("    Load segment: 0x{:04X}", image.load_segment);
+        println!("    System type:  0x{:02X}", image.system_type);
+        println!("    First sector: {start:<9} (0x{start:X})");
+        println!("    Load sectors: {count:<9} (512 bytes each)");
+        println!("    Image size:   {size:<9} (0x{size:X})");
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{
+    error::{Error, Result},
+    loader::{load_mds, LoadOptions},
+    subchannel::track_subchannels,
+    timecode::Timecode,
+    util::writer_with_extension,
+};
+use std::{io::Write, ops::RangeInclusive, path::Path};
+
+// LibCrypt is a copy protection scheme used by PAL PlayStation games. The game reads the Q
+// subchannel of a handful of sectors and expects their CRCs to be wrong, which a copy burned
+// without subchannel data won't reproduce. Emulators need to be told which frames were modified,
+// which is done with either an .sbi or an .lsd file holding those frames. Both formats are
+// described at http://redump.org/faq/#libcrypt and in the sources of emulators which read them.
+
+/// The absolute MSF addresses between which LibCrypt modifies sectors. The modified sectors come
+/// in pairs five sectors apart, and every known LibCrypt disc stores them twice: once in minutes
+/// 03 and 04, and again in minute 09.
+const LIBCRYPT_RANGES: [RangeInclusive<(u8, u8, u8)>; 2] =
+    [(3, 0, 0)..=(4, 59, 74), (9, 0, 0)..=(9, 59, 74)];
+
+/// The number of sectors ahead of LBA 0, which absolute MSF addresses count from
+const LEAD_IN_SECTORS: i32 = 150;
+
+/// A sector whose Q subchannel failed its CRC check
+struct ModifiedSector {
+    lba: i32,
+    q: [u8; 12],
+}
+
+impl ModifiedSector {
+    /// The sector's absolute address, as BCD minutes, seconds, and frames
+    fn bcd_msf(&self) -> [u8; 3] {
+        let (m, s, f) = Timecode::from_sectors(self.lba + LEAD_IN_SECTORS).msf();
+        [bcd(m as u8), bcd(s as u8), bcd(f as u8)]
+    }
+
+    fn in_libcrypt_range(&self) -> bool {
+        let (m, s, f) = Timecode::from_sectors(self.lba + LEAD_IN_SECTORS).msf();
+        let msf = (m as u8, s as u8, f as u8);
+        LIBCRYPT_RANGES.iter().any(|range| range.contains(&msf))
+    }
+}
+
+/// Scan the Q subchannel of every sector for frames with bad CRCs, and report whether the disc is
+/// protected by LibCrypt. If it is, the modified frames are written to .sbi and .lsd files next
+/// to the .mds, where a converted .cue/.bin would be written.
+pub fn libcrypt<P: AsRef<Path>>(mds_file: P, options: &LoadOptions, output: &Path) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks = mds
+        .sessions()
+        .flat_map(|session| session.data_tracks())
+        .filter(|track| track.sector_subchannel_size() > 0);
+
+    let mut num_tracks = 0;
+    let mut modified = Vec::new();
+
+    for track in tracks {
+        num_tracks += 1;
+
+        for sector in track_subchannels(track, &mds_file, options)? {
+            let (lba, subchannel) = sector?;
+            let q = subchannel.q();
+
+            if !q.crc_valid {
+                let q = *subchannel.channel(1);
+                modified.push(ModifiedSector { lba, q });
+            }
+        }
+    }
+
+    if num_tracks == 0 {
+        Err(Error::NoSubchannels)?;
+    }
+
+    let (libcrypt, other): (Vec<_>, Vec<_>) = modified
+        .into_iter()
+        .partition(ModifiedSector::in_libcrypt_range);
+
+    println!("{}", mds_file.as_ref().to_str().unwrap_or("--none--"));
+
+    if !other.is_empty() {
+        println!(
+            "{} other Q subchannel frames have bad CRCs, which may just be damage",
+            other.len()
+        );
+    }
+
+    if libcrypt.is_empty() {
+        println!("Not protected by LibCrypt");
+        return Ok(());
+    }
+
+    println!(
+        "Protected by LibCrypt: {} sectors have modified Q subchannel frames",
+        libcrypt.len()
+    );
+
+    for sector in &libcrypt {
+        let [m, s, f] = sector.bcd_msf();
+        println!("  {m:02x}:{s:02x}:{f:02x}  LBA {}", sector.lba);
+    }
+
+    let mut sbi_writer = writer_with_extension(output, "sbi")?;
+    write_sbi(&libcrypt, &mut sbi_writer).map_err(Error::Io)?;
+    sbi_writer.flush().map_err(Error::Io)?;
+
+    let mut lsd_writer = writer_with_extension(output, "lsd")?;
+    write_lsd(&libcrypt, &mut lsd_writer).map_err(Error::Io)?;
+    lsd_writer.flush().map_err(Error::Io)?;
+
+    Ok(())
+}
+
+/// An .sbi file starts with a magic number, followed by each sector's address and the first 10
+/// bytes of its Q frame. The byte between the two says how much of the frame is stored; 1 means
+/// all of it, apart from the CRC.
+fn write_sbi<W: Write>(sectors: &[ModifiedSector], writer: &mut W) -> std::io::Result<()> {
+    writer.write_all(b"SBI\0")?;
+
+    for sector in sectors {
+        writer.write_all(&sector.bcd_msf())?;
+        writer.write_all(&[1])?;
+        writer.write_all(&sector.q[0..10])?;
+    }
+
+    Ok(())
+}
+
+/// An .lsd file holds each sector's address followed by its whole Q frame, including the CRC
+fn write_lsd<W: Write>(sectors: &[ModifiedSector], writer: &mut W) -> std::io::Result<()> {
+    for sector in sectors {
+        writer.write_all(&sector.bcd_msf())?;
+        writer.write_all(&sector.q)?;
+    }
+
+    Ok(())
+}
+
+/// Encode a number below 100 as binary-coded decimal
+fn bcd(x: u8) -> u8 {
+    ((x / 10) << 4) | (x % 10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first sector of a pair in each copy of the key, at 03:08:05 and 09:24:25, with the
+    /// relative address in their Q frames changed and the CRC left as it was
+    fn sectors() -> Vec<ModifiedSector> {
+        vec![
+            ModifiedSector {
+                lba: 13955,
+                q: [
+                    0x41, 0x01, 0x01, 0x03, 0x16, 0x05, 0x00, 0x03, 0x08, 0x05, 0x8E, 0x2F,
+                ],
+            },
+            ModifiedSector {
+                lba: 42175,
+                q: [
+                    0x41, 0x01, 0x01, 0x09, 0x22, 0x35, 0x00, 0x09, 0x24, 0x25, 0x1C, 0xD0,
+                ],
+            },
+        ]
+    }
+
+    #[test]
+    fn both_copies_of_the_key_are_in_range() {
+        let in_range = |lba| ModifiedSector { lba, q: [0; 12] }.in_libcrypt_range();
+
+        // 02:59:74, 03:00:00, 04:59:74, 05:00:00
+        assert!(!in_range(13349));
+        assert!(in_range(13350));
+        assert!(in_range(22349));
+        assert!(!in_range(22350));
+
+        // 08:59:74, 09:00:00, 09:59:74, 10:00:00
+        assert!(!in_range(40349));
+        assert!(in_range(40350));
+        assert!(in_range(44849));
+        assert!(!in_range(44850));
+
+        assert!(sectors().iter().all(ModifiedSector::in_libcrypt_range));
+    }
+
+    #[test]
+    fn sbi_holds_addresses_and_frames_without_crcs() {
+        let mut sbi = Vec::new();
+        write_sbi(&sectors(), &mut sbi).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            b'S', b'B', b'I', 0x00,
+            0x03, 0x08, 0x05, 0x01,
+            0x41, 0x01, 0x01, 0x03, 0x16, 0x05, 0x00, 0x03, 0x08, 0x05,
+            0x09, 0x24, 0x25, 0x01,
+            0x41, 0x01, 0x01, 0x09, 0x22, 0x35, 0x00, 0x09, 0x24, 0x25,
+        ];
+        assert_eq!(sbi, expected);
+    }
+
+    #[test]
+    fn lsd_holds_addresses_and_whole_frames() {
+        let mut lsd = Vec::new();
+        write_lsd(&sectors(), &mut lsd).unwrap();
+
+        #[rustfmt::skip]
+        let expected = [
+            0x03, 0x08, 0x05,
+            0x41, 0x01, 0x01, 0x03, 0x16, 0x05, 0x00, 0x03, 0x08, 0x05, 0x8E, 0x2F,
+            0x09, 0x24, 0x25,
+            0x41, 0x01, 0x01, 0x09, 0x22, 0x35, 0x00, 0x09, 0x24, 0x25, 0x1C, 0xD0,
+        ];
+        assert_eq!(lsd, expected);
+    }
+}
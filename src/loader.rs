@@ -2,10 +2,36 @@ use crate::{
     error::{Error, Result},
     mds::Mds,
 };
-use std::{fs::read, path::Path};
+use encoding_rs::{Encoding, UTF_8};
+use std::{
+    fs::read,
+    path::{Path, PathBuf},
+};
+
+/// Settings for how an .mds file is read, which apply to every command
+#[derive(Debug)]
+pub struct LoadOptions {
+    /// The encoding of 8-bit filenames in the .mds
+    pub filename_encoding: &'static Encoding,
+
+    /// Directories to look for data files in when they aren't next to the .mds
+    pub data_dirs: Vec<PathBuf>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            filename_encoding: UTF_8,
+            data_dirs: Vec::new(),
+        }
+    }
+}
 
 /// Create a new Mds by reading the file at `path`
-pub fn load_mds<P: AsRef<Path>>(path: P) -> Result<Mds> {
+pub fn load_mds<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Mds> {
     let bytes = read(path).map_err(Error::Io)?;
-    Mds::from_bytes(&bytes)
+    let mut mds = Mds::from_bytes(&bytes)?;
+    mds.set_filename_encoding(options.filename_encoding);
+
+    Ok(mds)
 }
@@ -0,0 +1,246 @@
+use crate::{
+    error::{Error, Result},
+    loader::LoadOptions,
+    mds::Track,
+};
+use std::{
+    fs::read_dir,
+    path::{Path, PathBuf},
+};
+
+// Filenames in an .mds were written on Windows, so they may use backslashes, be absolute paths
+// on a drive which doesn't exist here, or differ in case from the files on disk. Files are looked
+// for next to the .mds first, then in each directory given with --data-dir.
+
+/// Find every file holding a track's data, in the order they should be read
+pub fn locate_track_files<P: AsRef<Path>>(
+    mds_path: P,
+    track: &Track,
+    options: &LoadOptions,
+) -> Result<Vec<PathBuf>> {
+    let names = track.filenames();
+    if names.is_empty() {
+        Err(Error::MissingInputFile(Vec::new()))?;
+    }
+
+    names
+        .iter()
+        .map(|name| locate_data_file(&mds_path, name, &options.data_dirs))
+        .collect()
+}
+
+/// Find the data file called `name` in an .mds or cue sheet at `image_path`. Fails with every
+/// path that was tried if the file can't be found.
+pub fn locate_data_file<P: AsRef<Path>>(
+    image_path: P,
+    name: &str,
+    data_dirs: &[PathBuf],
+) -> Result<PathBuf> {
+    let candidates = candidates(image_path.as_ref(), name, data_dirs);
+
+    for (dir, relative) in &candidates {
+        let path = dir.join(relative);
+        if path.is_file() {
+            return Ok(path);
+        }
+
+        if let Some(path) = find_ignoring_case(dir, relative) {
+            return Ok(path);
+        }
+    }
+
+    let tried = candidates
+        .into_iter()
+        .map(|(dir, relative)| dir.join(relative))
+        .collect();
+
+    Err(Error::MissingInputFile(tried))
+}
+
+/// The path of every file holding a track's data, for showing to the user. Files which can't be
+/// found are given as the path they were first looked for at, along with `false`.
+pub fn track_file_paths<P: AsRef<Path>>(
+    mds_path: P,
+    track: &Track,
+    options: &LoadOptions,
+) -> Vec<(PathBuf, bool)> {
+    track
+        .filenames()
+        .iter()
+        .map(
+            |name| match locate_data_file(&mds_path, name, &options.data_dirs) {
+                Ok(path) => (path, true),
+                Err(_) => {
+                    let (dir, relative) = candidates(mds_path.as_ref(), name, &[]).remove(0);
+                    (dir.join(relative), false)
+                }
+            },
+        )
+        .collect()
+}
+
+/// Every directory the file could be in, along with its path relative to that directory, in the
+/// order they should be tried
+fn candidates(image_path: &Path, name: &str, data_dirs: &[PathBuf]) -> Vec<(PathBuf, PathBuf)> {
+    let image_dir = image_path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+    // Names like "*.mdf" stand for the image's own name with another extension
+    let name = match name.strip_prefix("*.") {
+        Some(extension) => image_path
+            .with_extension(extension)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        None => name.replace('\\', "/"),
+    };
+
+    let file_name = name.rsplit('/').next().unwrap_or_default().to_owned();
+    let is_absolute = name.starts_with('/') || has_drive_letter(&name);
+
+    let mut relative_paths = Vec::new();
+    if !is_absolute {
+        relative_paths.push(PathBuf::from(&name));
+    }
+    relative_paths.push(PathBuf::from(&file_name));
+
+    let mut candidates = Vec::new();
+    if let Some(path) = name.strip_prefix('/') {
+        candidates.push((PathBuf::from("/"), PathBuf::from(path)));
+    }
+
+    for dir in std::iter::once(&image_dir).chain(data_dirs) {
+        for relative in &relative_paths {
+            let candidate = (dir.clone(), relative.clone());
+            if !candidates.contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+
+    candidates
+}
+
+/// Whether a path starts with a Windows drive letter, like "C:"
+fn has_drive_letter(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+/// Find `relative` inside `dir`, matching each part of the path without regard to case
+fn find_ignoring_case(dir: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut path = dir.to_path_buf();
+
+    for component in relative.iter() {
+        let wanted = component.to_string_lossy().to_lowercase();
+        let search_dir = if path.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            &path
+        };
+
+        let entry = read_dir(search_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .find(|entry| entry.file_name().to_string_lossy().to_lowercase() == wanted)?;
+
+        path.push(entry.file_name());
+    }
+
+    path.is_file().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+    use std::fs;
+
+    /// Write an empty file at `relative` inside the disc's directory, and return its path
+    fn touch(disc: &TestDisc, relative: &str) -> PathBuf {
+        let path = disc.dir().join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, []).unwrap();
+
+        path
+    }
+
+    fn locate(disc: &TestDisc, name: &str, data_dirs: &[PathBuf]) -> Result<PathBuf> {
+        locate_data_file(disc.mds_path(), name, data_dirs)
+    }
+
+    #[test]
+    fn backslashes_separate_directories() {
+        let disc = TestDisc::new("locate-backslash", &[0; 2048], false);
+        let path = touch(&disc, "images/game.mdf");
+
+        assert_eq!(locate(&disc, r"images\game.mdf", &[]).unwrap(), path);
+    }
+
+    #[test]
+    fn absolute_paths_fall_back_to_the_file_name() {
+        let disc = TestDisc::new("locate-drive", &[0; 2048], false);
+        let path = touch(&disc, "game.mdf");
+
+        assert_eq!(locate(&disc, r"D:\Images\game.mdf", &[]).unwrap(), path);
+        assert_eq!(locate(&disc, "/mnt/images/game.mdf", &[]).unwrap(), path);
+    }
+
+    #[test]
+    fn names_match_regardless_of_case() {
+        let disc = TestDisc::new("locate-case", &[0; 2048], false);
+        let path = touch(&disc, "Images/Game.MDF");
+
+        assert_eq!(locate(&disc, r"IMAGES\game.mdf", &[]).unwrap(), path);
+    }
+
+    #[test]
+    fn wildcard_names_use_the_images_own_name() {
+        let disc = TestDisc::new("locate-wildcard", &[0; 2048], false);
+        let path = touch(&disc, "disc.mdf");
+
+        assert_eq!(locate(&disc, "*.mdf", &[]).unwrap(), path);
+    }
+
+    #[test]
+    fn data_dirs_are_searched_in_order_after_the_images_own() {
+        let disc = TestDisc::new("locate-order", &[0; 2048], false);
+        let data_dirs = [disc.dir().join("first"), disc.dir().join("second")];
+
+        let second = touch(&disc, "second/game.mdf");
+        assert_eq!(locate(&disc, "game.mdf", &data_dirs).unwrap(), second);
+
+        let first = touch(&disc, "first/game.mdf");
+        assert_eq!(locate(&disc, "game.mdf", &data_dirs).unwrap(), first);
+
+        let own = touch(&disc, "game.mdf");
+        assert_eq!(locate(&disc, "game.mdf", &data_dirs).unwrap(), own);
+    }
+
+    #[test]
+    fn missing_files_list_every_path_tried() {
+        let disc = TestDisc::new("locate-missing", &[0; 2048], false);
+        let dir = disc.dir();
+        let data_dirs = [dir.join("extra")];
+
+        let Err(Error::MissingInputFile(tried)) = locate(&disc, r"sub\game.mdf", &data_dirs) else {
+            panic!("a missing file was found");
+        };
+        assert_eq!(
+            tried,
+            [
+                dir.join("sub/game.mdf"),
+                dir.join("game.mdf"),
+                dir.join("extra/sub/game.mdf"),
+                dir.join("extra/game.mdf"),
+            ]
+        );
+
+        let Err(Error::MissingInputFile(tried)) = locate(&disc, "/nowhere/game.mdf", &[]) else {
+            panic!("a missing file was found");
+        };
+        assert_eq!(
+            tried,
+            [PathBuf::from("/nowhere/game.mdf"), dir.join("game.mdf")]
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{
+    args::FilesystemType,
+    error::Result,
+    filesystem::{format_time, open_filesystem, Entry, EntryKind},
+    loader::{load_mds, LoadOptions},
+};
+use std::path::Path;
+
+/// Print the entries of a directory in the image's filesystem, or the details of a single file
+pub fn ls<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    path: &str,
+    filesystem: Option<&FilesystemType>,
+) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let (mut disc, fs) = open_filesystem(&mds, mds_file.as_ref(), options, filesystem)?;
+    let entry = fs.lookup(&mut disc, path)?;
+
+    println!("{}, volume {}", fs.description(), fs.volume_name());
+
+    if entry.kind == EntryKind::Directory {
+        let mut entries = fs.read_dir(&mut disc, &entry)?;
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for entry in &entries {
+            print_entry(entry);
+        }
+    } else {
+        print_entry(&entry);
+    }
+
+    Ok(())
+}
+
+fn print_entry(entry: &Entry) {
+    let modified = entry
+        .modified
+        .map(format_time)
+        .unwrap_or_else(|| "-".repeat(19));
+
+    let (kind, name) = match &entry.kind {
+        EntryKind::File => ('-', entry.name.clone()),
+        EntryKind::Directory => ('d', format!("{}/", entry.name)),
+        EntryKind::Symlink(target) => ('l', format!("{} -> {target}", entry.name)),
+    };
+
+    println!("{kind} {:>12}  {modified}  {name}", entry.size);
+}
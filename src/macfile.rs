@@ -0,0 +1,156 @@
+use crate::{
+    error::{Error, Result},
+    filesystem::{DiscReader, Entry, Filesystem, MacMetadata},
+};
+use encoding_rs::MACINTOSH;
+use std::{io::Write, time::SystemTime};
+
+// Files on Mac filesystems have a resource fork and Finder information alongside their data,
+// which other filesystems have nowhere to keep. There are two common ways of carrying them
+// around: AppleDouble (https://www.rfc-editor.org/rfc/rfc1740), which puts them in a separate
+// file next to the data, and MacBinary
+// (https://files.stairways.com/other/macbinaryii-standard-info.txt), which wraps everything up
+// into one file.
+
+const APPLE_DOUBLE_MAGIC: u32 = 0x0005_1607;
+const APPLE_DOUBLE_VERSION: u32 = 0x0002_0000;
+
+/// AppleDouble entry IDs
+const RESOURCE_FORK: u32 = 2;
+const FILE_DATES: u32 = 8;
+const FINDER_INFO: u32 = 9;
+
+/// How AppleDouble records a date it doesn't know
+const UNKNOWN_DATE: i32 = i32::MIN;
+
+/// Seconds from 1904-01-01, the epoch of Mac dates, to 1970-01-01
+const MAC_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Seconds from 1970-01-01 to 2000-01-01, the epoch of AppleDouble dates
+const APPLE_DOUBLE_EPOCH: i64 = 946_684_800;
+
+const MACBINARY_HEADER_SIZE: usize = 128;
+const MACBINARY_II: u8 = 129;
+
+/// Write an AppleDouble file holding an entry's Finder information and resource fork, which goes
+/// next to its data fork as `._name`
+pub fn write_apple_double(
+    disc: &mut DiscReader,
+    entry: &Entry,
+    mac: &MacMetadata,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let date = |time: Option<SystemTime>| {
+        time.and_then(|time| i32::try_from(unix_seconds(time) - APPLE_DOUBLE_EPOCH).ok())
+            .unwrap_or(UNKNOWN_DATE)
+    };
+
+    let mut dates = Vec::with_capacity(16);
+    for time in [mac.created, entry.modified, None, None] {
+        dates.extend(date(time).to_be_bytes());
+    }
+
+    // The resource fork goes last, so it can be copied straight from the disc after the header
+    let entries = [
+        (FILE_DATES, dates.len() as u32),
+        (FINDER_INFO, mac.finder_info.len() as u32),
+        (RESOURCE_FORK, mac.resource_fork_size() as u32),
+    ];
+
+    let mut header = Vec::new();
+    header.extend(APPLE_DOUBLE_MAGIC.to_be_bytes());
+    header.extend(APPLE_DOUBLE_VERSION.to_be_bytes());
+    header.extend([0; 16]);
+    header.extend((entries.len() as u16).to_be_bytes());
+
+    let mut offset = header.len() as u32 + entries.len() as u32 * 12;
+    for (id, length) in entries {
+        header.extend(id.to_be_bytes());
+        header.extend(offset.to_be_bytes());
+        header.extend(length.to_be_bytes());
+        offset += length;
+    }
+
+    header.extend(dates);
+    header.extend(mac.finder_info);
+    writer.write_all(&header).map_err(Error::Io)?;
+
+    disc.copy_extents(&mac.resource_fork, writer)
+}
+
+/// Write an entry as a MacBinary II file, holding its Finder information, data fork, and
+/// resource fork
+pub fn write_macbinary(
+    fs: &dyn Filesystem,
+    disc: &mut DiscReader,
+    entry: &Entry,
+    mac: &MacMetadata,
+    writer: &mut dyn Write,
+) -> Result<()> {
+    let date = |time: Option<SystemTime>| {
+        time.and_then(|time| u32::try_from(unix_seconds(time) + MAC_EPOCH_OFFSET).ok())
+            .unwrap_or(0)
+    };
+
+    // Names are stored the way the Mac had them, where colons were the path separator rather
+    // than slashes
+    let name = entry.name.replace(':', "/");
+    let (name, _, _) = MACINTOSH.encode(&name);
+    let name = &name[..name.len().min(63)];
+    let finder_info = &mac.finder_info;
+    let resource_size = mac.resource_fork_size();
+
+    let mut header = [0; MACBINARY_HEADER_SIZE];
+    header[1] = name.len() as u8;
+    header[2..2 + name.len()].copy_from_slice(name);
+    // Type and creator codes
+    header[65..73].copy_from_slice(&finder_info[0..8]);
+    // The Finder flags are split in two, with the high byte here and the low byte below
+    header[73] = finder_info[8];
+    // The icon's position in its window, and the window it's in
+    header[75..81].copy_from_slice(&finder_info[10..16]);
+    header[83..87].copy_from_slice(&(entry.size as u32).to_be_bytes());
+    header[87..91].copy_from_slice(&(resource_size as u32).to_be_bytes());
+    header[91..95].copy_from_slice(&date(mac.created).to_be_bytes());
+    header[95..99].copy_from_slice(&date(entry.modified).to_be_bytes());
+    header[101] = finder_info[9];
+    header[122] = MACBINARY_II;
+    header[123] = MACBINARY_II;
+    let crc = crc16(&header[..124]);
+    header[124..126].copy_from_slice(&crc.to_be_bytes());
+    writer.write_all(&header).map_err(Error::Io)?;
+
+    // Each fork is padded to a multiple of 128 bytes
+    fs.read_file(disc, entry, writer)?;
+    write_padding(writer, entry.size)?;
+    disc.copy_extents(&mac.resource_fork, writer)?;
+    write_padding(writer, resource_size)
+}
+
+fn write_padding(writer: &mut dyn Write, size: u64) -> Result<()> {
+    let padding =
+        (MACBINARY_HEADER_SIZE - size as usize % MACBINARY_HEADER_SIZE) % MACBINARY_HEADER_SIZE;
+    writer
+        .write_all(&[0; MACBINARY_HEADER_SIZE][..padding])
+        .map_err(Error::Io)
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(err) => -(err.duration().as_secs() as i64),
+    }
+}
+
+/// The CRC-16/XMODEM of the header, which MacBinary II uses to tell its files apart from others
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
@@ -1,29 +1,118 @@
 mod args;
+mod bits;
+mod chd;
 mod convert;
+mod create;
+mod cue;
+mod dat;
 mod error;
+mod extract;
+mod filesystem;
+mod flac;
+mod hash;
 mod info;
+mod libcrypt;
 mod loader;
+mod locate;
+mod ls;
+mod macfile;
 mod mds;
+mod platform;
+mod sector;
+mod subchannel;
+mod subq;
+#[cfg(test)]
+mod testing;
 mod timecode;
 mod util;
+mod verify;
 
 use args::{Args, Command, ConvertArgs, OutputFormat};
 use clap::Parser;
-use convert::{convert_to_cue_bin, convert_to_iso};
+use convert::{
+    convert_to_ccd, convert_to_chd, convert_to_cue_bin, convert_to_flac, convert_to_iso,
+    convert_to_wav,
+};
+use create::create;
+use dat::verify_dat;
+use extract::{extract_boot, extract_path, extract_track};
+use hash::hash;
 use info::info;
+use libcrypt::libcrypt;
+use loader::LoadOptions;
+use ls::ls;
+use platform::output_path;
+use subq::subq;
+use verify::verify;
 
 fn main() {
     let args = Args::parse();
+    let mut options = LoadOptions {
+        data_dirs: args.data_dirs.clone(),
+        ..Default::default()
+    };
+    if let Some(encoding) = args.filename_encoding {
+        options.filename_encoding = encoding;
+    }
 
     let result = match &args.command {
-        Command::Info(args) => info(&args.mds_file),
-        Command::Convert(ConvertArgs { mds_file, format }) => match format {
-            OutputFormat::Iso => convert_to_iso(&mds_file),
-            OutputFormat::Cue => convert_to_cue_bin(&mds_file),
+        Command::Create(args) => create(&args.from),
+        Command::Extract(args) => match args.track {
+            Some(number) => extract_track(&args.mds_file, &options, number, args.wav),
+            None => {
+                let path = args.path.as_deref().unwrap_or("/");
+                let filesystem = args.filesystem.as_ref();
+                let forks = &args.resource_forks;
+                extract_path(
+                    &args.mds_file,
+                    &options,
+                    path,
+                    &args.output,
+                    filesystem,
+                    forks,
+                )
+            }
         },
+        Command::ExtractBoot(args) => extract_boot(&args.mds_file, &options),
+        Command::Hash(args) => hash(&args.mds_file, &options),
+        Command::Info(args) => info(&args.mds_file, &options, &args.format),
+        Command::Libcrypt(args) => output_path(&args.mds_file, &options, args.name.as_deref())
+            .and_then(|output| libcrypt(&args.mds_file, &options, &output)),
+        Command::Ls(args) => ls(
+            &args.mds_file,
+            &options,
+            &args.path,
+            args.filesystem.as_ref(),
+        ),
+        Command::Subq(args) => subq(&args.mds_file, &options),
+        Command::Verify(args) => verify(&args.mds_file, &options),
+        Command::VerifyDat(args) => {
+            verify_dat(&args.mds_file, &args.dat, args.emit_dat.clone(), &options)
+        }
+        Command::Convert(ConvertArgs {
+            mds_file,
+            format,
+            session,
+            raw,
+            subchannels,
+            split_tracks,
+            cdg,
+            name,
+        }) => output_path(mds_file, &options, name.as_deref()).and_then(|output| {
+            let output = output.as_path();
+            match format {
+                OutputFormat::Iso => convert_to_iso(mds_file, &options, output, *session, *raw),
+                OutputFormat::Cue => convert_to_cue_bin(mds_file, &options, output, *split_tracks),
+                OutputFormat::Ccd => convert_to_ccd(mds_file, &options, output),
+                OutputFormat::Chd => convert_to_chd(mds_file, &options, output, *subchannels),
+                OutputFormat::Flac => convert_to_flac(mds_file, &options, output, *split_tracks),
+                OutputFormat::Wav => convert_to_wav(mds_file, &options, output, *cdg),
+            }
+        }),
     };
 
     if let Err(err) = result {
         eprintln!("{err}");
+        std::process::exit(1);
     }
 }
@@ -1,11 +1,19 @@
 use super::types::{Bytes, Res};
+use encoding_rs::{Encoding, UTF_16LE, UTF_8};
 use nom::{
+    bytes::complete::{take, take_till},
     combinator::map_res,
     number::complete::{le_u32, le_u8},
     sequence::tuple,
 };
 
-#[derive(Debug)]
+/// The number of bytes in an .mds file that a filename block takes
+pub const FILENAME_BLOCK_SIZE: usize = 0x10;
+
+/// The number of unused bytes at the end of a filename block
+const PADDING_SIZE: usize = 0x0B;
+
+#[derive(Clone, Copy, Debug)]
 pub enum NameFormat {
     EightBit,
     SixteenBit,
@@ -25,24 +33,184 @@ impl TryInto<NameFormat> for u8 {
     }
 }
 
+impl From<NameFormat> for u8 {
+    fn from(format: NameFormat) -> Self {
+        match format {
+            NameFormat::EightBit => 0,
+            NameFormat::SixteenBit => 1,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct FilenameBlock {
     pub filename_offset: u32,
     pub filename_format: NameFormat,
+    padding: Vec<u8>,
+
+    /// The filename as it is stored in the .mds, without its terminator
+    pub name: Vec<u8>,
+
+    /// How 8-bit filenames are decoded. The .mds doesn't say, since Alcohol 120% writes them in
+    /// whatever codepage Windows was set up to use.
+    encoding: &'static Encoding,
 }
 
-pub fn filename_block(input: Bytes) -> Res<FilenameBlock> {
-    let (rest, (filename_offset, filename_format)) = tuple((le_u32, name_format))(input)?;
+impl FilenameBlock {
+    /// Create a block for an 8-bit filename, which still needs to be given a `filename_offset`
+    pub fn new(name: &str) -> Self {
+        Self {
+            filename_offset: 0,
+            filename_format: NameFormat::EightBit,
+            padding: vec![0; PADDING_SIZE],
+            name: name.as_bytes().to_vec(),
+            encoding: UTF_8,
+        }
+    }
+
+    /// The filename, decoded into a string. 16-bit filenames are UTF-16, and 8-bit ones are
+    /// decoded with the encoding given to `set_encoding`, which is UTF-8 by default.
+    pub fn name(&self) -> String {
+        let encoding = match self.filename_format {
+            NameFormat::EightBit => self.encoding,
+            NameFormat::SixteenBit => UTF_16LE,
+        };
+
+        encoding
+            .decode_without_bom_handling(&self.name)
+            .0
+            .to_string()
+    }
+
+    /// Set the encoding used to decode 8-bit filenames
+    pub fn set_encoding(&mut self, encoding: &'static Encoding) {
+        self.encoding = encoding;
+    }
+
+    /// The filename as it is stored in the .mds, including its terminator
+    pub fn encoded_name(&self) -> Vec<u8> {
+        let terminator_size = match self.filename_format {
+            NameFormat::EightBit => 1,
+            NameFormat::SixteenBit => 2,
+        };
+
+        let mut name = self.name.clone();
+        name.resize(name.len() + terminator_size, 0);
+        name
+    }
+}
+
+/// Parse the filename block at `block_offset`, along with the filename it points to
+pub fn filename_block(input: Bytes, block_offset: usize) -> Res<FilenameBlock> {
+    let (rest, (filename_offset, filename_format, padding)) =
+        tuple((le_u32, name_format, take(PADDING_SIZE)))(&input[block_offset..])?;
+    let name_input = &input[filename_offset as usize..];
+    let name = match filename_format {
+        NameFormat::EightBit => take_till(is_zero)(name_input)?.1,
+        NameFormat::SixteenBit => {
+            // Stop at the first zero code unit, rather than the first zero byte
+            let len = name_input
+                .chunks_exact(2)
+                .take_while(|unit| *unit != [0, 0])
+                .count();
+            &name_input[..len * 2]
+        }
+    };
 
     Ok((
         rest,
         FilenameBlock {
             filename_offset,
             filename_format,
+            padding: padding.to_vec(),
+            name: name.to_vec(),
+            encoding: UTF_8,
         },
     ))
 }
 
+pub fn write_filename_block(block: &FilenameBlock, out: &mut Vec<u8>) {
+    out.extend_from_slice(&block.filename_offset.to_le_bytes());
+    out.push(block.filename_format.into());
+    out.extend_from_slice(&block.padding);
+}
+
+/// Encode the filename a block points to, including its terminator
+pub fn write_filename(block: &FilenameBlock, out: &mut Vec<u8>) {
+    out.extend_from_slice(&block.encoded_name());
+}
+
+fn is_zero(x: u8) -> bool {
+    x == 0
+}
+
 fn name_format(input: Bytes) -> Res<NameFormat> {
     map_res(le_u8, |x| x.try_into())(input)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An .mds fragment with a filename block at the start, pointing at `name` right after it
+    fn block_with_name(format: NameFormat, name: &[u8]) -> Vec<u8> {
+        let mut input = (FILENAME_BLOCK_SIZE as u32).to_le_bytes().to_vec();
+        input.push(format.into());
+        input.extend_from_slice(&[0; PADDING_SIZE]);
+        input.extend_from_slice(name);
+
+        // Whatever follows the filename's terminator isn't part of it
+        input.extend_from_slice(b"\0\0junk");
+        input
+    }
+
+    #[test]
+    fn reads_16_bit_names_as_utf_16() {
+        let name: Vec<u8> = "ディスク.mdf"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let input = block_with_name(NameFormat::SixteenBit, &name);
+
+        let (rest, block) = filename_block(&input, 0).unwrap();
+        assert_eq!(rest.len(), input.len() - FILENAME_BLOCK_SIZE);
+        assert_eq!(block.filename_offset, FILENAME_BLOCK_SIZE as u32);
+        assert!(matches!(block.filename_format, NameFormat::SixteenBit));
+
+        // ASCII characters have a zero byte, which mustn't end the name early
+        assert_eq!(block.name, name);
+        assert_eq!(block.name(), "ディスク.mdf");
+        assert_eq!(block.encoded_name(), [&name[..], &[0, 0]].concat());
+    }
+
+    #[test]
+    fn decodes_8_bit_names_with_the_chosen_encoding() {
+        let cases: [(&str, &[u8], &str); 2] = [
+            ("windows-1251", b"\xC4\xE8\xF1\xEA.mdf", "Диск.mdf"),
+            (
+                "shift_jis",
+                b"\x83\x66\x83\x42\x83\x58\x83\x4E.mdf",
+                "ディスク.mdf",
+            ),
+        ];
+
+        for (label, name, decoded) in cases {
+            let input = block_with_name(NameFormat::EightBit, name);
+            let (_, mut block) = filename_block(&input, 0).unwrap();
+            assert_eq!(block.name, name);
+            assert_ne!(block.name(), decoded);
+
+            block.set_encoding(Encoding::for_label(label.as_bytes()).unwrap());
+            assert_eq!(block.name(), decoded);
+            assert_eq!(block.encoded_name(), [name, b"\0"].concat());
+        }
+    }
+
+    #[test]
+    fn names_default_to_utf_8() {
+        let input = block_with_name(NameFormat::EightBit, "Ñame.mdf".as_bytes());
+        let (_, block) = filename_block(&input, 0).unwrap();
+
+        assert_eq!(block.name(), "Ñame.mdf");
+    }
+}
@@ -24,10 +24,31 @@ pub struct Header {
     pub version: Version,
     pub media_type: MediaType,
     num_sessions: u16,
+    dvd_padding: Vec<u8>,
     session_offset: u32,
+    dpm_offset: u32,
 }
 
+/// The number of bytes at the start of an .mds file that the header takes
+pub const HEADER_SIZE: usize = 0x58;
+
+/// The number of bytes in the header which are only used by DVD images
+const DVD_PADDING_SIZE: usize = 0x3A;
+
 impl Header {
+    /// Create the header for an .mds file with `num_sessions` sessions, which are stored right
+    /// after the header
+    pub fn new(media_type: MediaType, num_sessions: u16) -> Self {
+        Self {
+            version: Version([1, 3]),
+            media_type,
+            num_sessions,
+            dvd_padding: vec![0; DVD_PADDING_SIZE],
+            session_offset: HEADER_SIZE as u32,
+            dpm_offset: 0,
+        }
+    }
+
     pub fn num_sessions(&self) -> usize {
         self.num_sessions.into()
     }
@@ -62,6 +83,20 @@ impl Display for MediaType {
     }
 }
 
+impl From<MediaType> for u16 {
+    fn from(media_type: MediaType) -> Self {
+        use MediaType::*;
+
+        match media_type {
+            CdRom => 0x00,
+            CdR => 0x01,
+            CdRw => 0x02,
+            DvdRom => 0x10,
+            DvdR => 0x12,
+        }
+    }
+}
+
 pub struct UnknownMediaType(pub u16);
 
 impl TryInto<MediaType> for u16 {
@@ -82,19 +117,31 @@ impl TryInto<MediaType> for u16 {
 }
 
 pub fn header(input: Bytes) -> Res<Header> {
-    let (input, (_, version, media_type, num_sessions, _, session_offset)) =
-        tuple((id, version, media_type, le_u16, dvd_padding, le_u32))(input)?;
+    let (input, (_, version, media_type, num_sessions, dvd_padding, session_offset, dpm_offset)) =
+        tuple((id, version, media_type, le_u16, dvd_padding, le_u32, le_u32))(input)?;
 
     let header = Header {
         version,
         media_type,
         num_sessions,
+        dvd_padding: dvd_padding.to_vec(),
         session_offset,
+        dpm_offset,
     };
 
     Ok((input, header))
 }
 
+pub fn write_header(header: &Header, out: &mut Vec<u8>) {
+    out.extend_from_slice(b"MEDIA DESCRIPTOR");
+    out.extend_from_slice(&header.version.0);
+    out.extend_from_slice(&u16::from(header.media_type).to_le_bytes());
+    out.extend_from_slice(&header.num_sessions.to_le_bytes());
+    out.extend_from_slice(&header.dvd_padding);
+    out.extend_from_slice(&header.session_offset.to_le_bytes());
+    out.extend_from_slice(&header.dpm_offset.to_le_bytes());
+}
+
 fn id(input: Bytes) -> Res<Bytes> {
     tag("MEDIA DESCRIPTOR")(input)
 }
@@ -108,5 +155,5 @@ fn media_type(input: Bytes) -> Res<MediaType> {
 }
 
 fn dvd_padding(input: Bytes) -> Res<Bytes> {
-    take(0x3Ausize)(input)
+    take(DVD_PADDING_SIZE)(input)
 }
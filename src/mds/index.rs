@@ -2,9 +2,12 @@ use nom::{number::complete::le_u32, sequence::tuple};
 
 use super::types::{Bytes, Res};
 
+/// The number of bytes in an .mds file that an index block takes
+pub const INDEX_BLOCK_SIZE: usize = 0x08;
+
 #[derive(Debug)]
 pub struct IndexBlock {
-    _index0_sectors: u32,
+    pub index0_sectors: u32,
     pub index1_sectors: u32,
 }
 
@@ -14,8 +17,13 @@ pub fn index_block(input: Bytes) -> Res<IndexBlock> {
     Ok((
         input,
         IndexBlock {
-            _index0_sectors: index0_sectors,
+            index0_sectors,
             index1_sectors,
         },
     ))
 }
+
+pub fn write_index_block(block: &IndexBlock, out: &mut Vec<u8>) {
+    out.extend_from_slice(&block.index0_sectors.to_le_bytes());
+    out.extend_from_slice(&block.index1_sectors.to_le_bytes());
+}
@@ -1,10 +1,16 @@
 use super::{
-    header::{header, Header, MediaType, Version},
-    session::{session, Session, SESSION_SIZE},
-    track::Track,
+    filename::{write_filename, write_filename_block, FILENAME_BLOCK_SIZE},
+    header,
+    header::write_header,
+    index::{write_index_block, INDEX_BLOCK_SIZE},
+    session,
+    session::{write_session, SESSION_SIZE, TRACK_SIZE},
+    track::write_track,
     types::{Bytes, Res},
+    Header, MediaType, Session, Track, Version,
 };
 use crate::error::{Error, Result};
+use encoding_rs::Encoding;
 use nom::Finish;
 
 #[derive(Debug)]
@@ -12,37 +18,52 @@ pub struct Mds {
     header: Header,
     num_bytes: usize,
     sessions: Vec<Session>,
+
+    /// Parts of the file which aren't in any block the parser understands, such as DVD
+    /// structures, along with where they were found. They are kept so that they can be written
+    /// back out untouched.
+    unparsed: Vec<(usize, Vec<u8>)>,
 }
 
 impl Mds {
+    pub fn new(media_type: MediaType, sessions: Vec<Session>) -> Self {
+        let mut mds = Self {
+            header: Header::new(media_type, sessions.len() as u16),
+            num_bytes: 0,
+            sessions,
+            unparsed: Vec::new(),
+        };
+        mds.layout();
+
+        mds
+    }
+
     pub fn sessions(&self) -> impl Iterator<Item = &Session> {
         self.sessions.iter()
     }
 
-    pub fn single_session(&self) -> Result<&Session> {
+    /// Look up a session by its 1-based number. When `number` is `None`, the last session on the
+    /// disc is returned, since that is the one a drive would mount.
+    pub fn session(&self, number: Option<usize>) -> Result<&Session> {
         if self.sessions.is_empty() {
             Err(Error::NoSessions)?;
         }
 
-        if self.sessions.len() > 1 {
-            Err(Error::TooManySessions)?;
+        match number {
+            Some(n) => n
+                .checked_sub(1)
+                .and_then(|i| self.sessions.get(i))
+                .ok_or(Error::NoSuchSession(n)),
+            None => Ok(&self.sessions[self.sessions.len() - 1]),
         }
-
-        Ok(&self.sessions[1])
     }
 
-    pub fn single_track(&self) -> Result<&Track> {
-        self.single_session().and_then(|session| {
-            let mut tracks = session.data_tracks();
-
-            let first_track = tracks.next().ok_or(Error::NoDataTracks)?;
-
-            if tracks.next().is_some() {
-                Err(Error::MultiTrackNotSupported)?;
-            }
-
-            Ok(first_track)
-        })
+    /// Look up a track by its number
+    pub fn track(&self, number: usize) -> Result<&Track> {
+        self.sessions()
+            .flat_map(|session| session.data_tracks())
+            .find(|track| track.number() == number)
+            .ok_or(Error::NoSuchTrack(number))
     }
 
     pub fn version(&self) -> Version {
@@ -57,12 +78,189 @@ impl Mds {
         mds(bytes)
             .finish()
             .map(|(_, mds)| mds)
-            .map_err(|_| Error::ParseError)
+            .map_err(|_| Error::Parse)
+    }
+
+    /// Set the encoding used to decode 8-bit filenames, which depends on the codepage of the
+    /// machine the image was made on
+    pub fn set_filename_encoding(&mut self, encoding: &'static Encoding) {
+        let blocks = self
+            .sessions
+            .iter_mut()
+            .flat_map(|session| session.toc_entries_mut())
+            .flat_map(|track| track.filename_blocks_mut());
+
+        for block in blocks {
+            block.set_encoding(encoding);
+        }
     }
 
     pub fn byte_len(&self) -> usize {
         self.num_bytes
     }
+
+    /// Point every data track at a single file named `filename`, which holds the tracks one after
+    /// another in order, each starting with its pregap
+    pub fn into_single_file(mut self, filename: &str) -> Self {
+        let tracks = self
+            .sessions
+            .iter_mut()
+            .flat_map(|session| session.data_tracks_mut());
+        let mut offset = 0;
+
+        for track in tracks {
+            track.set_data_file(filename, offset);
+            offset += ((track.pregap_sectors() + track.num_sectors()) * track.sector_size()) as u64;
+        }
+
+        self.layout();
+        self
+    }
+
+    /// Encode this into the contents of an .mds file. An .mds which was parsed from a file is
+    /// written back out exactly as it was read, with every block in the same place.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![0; self.num_bytes];
+
+        for (offset, bytes) in self.blocks().iter().chain(&self.unparsed) {
+            let end = offset + bytes.len();
+            if end > out.len() {
+                out.resize(end, 0);
+            }
+
+            out[*offset..end].copy_from_slice(bytes);
+        }
+
+        out
+    }
+
+    /// Every block in the .mds, encoded, along with where it is stored
+    fn blocks(&self) -> Vec<(usize, Vec<u8>)> {
+        let mut blocks = Vec::new();
+        let mut encode = |offset, write: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = Vec::new();
+            write(&mut bytes);
+            blocks.push((offset, bytes));
+        };
+
+        encode(0, &|out| write_header(&self.header, out));
+
+        for (i, session) in self.sessions().enumerate() {
+            let session_offset = self.header.session_offset() + SESSION_SIZE * i;
+            encode(session_offset, &|out| write_session(session, out));
+
+            for (j, track) in session.toc_entries().enumerate() {
+                let track_offset = session.first_track_offset() + TRACK_SIZE * j;
+                encode(track_offset, &|out| write_track(track, out));
+
+                if let Some((offset, index)) = track.index_block() {
+                    encode(offset, &|out| write_index_block(index, out));
+                }
+
+                for (offset, block) in track.filename_blocks() {
+                    encode(offset, &|out| write_filename_block(block, out));
+                    encode(block.filename_offset as usize, &|out| {
+                        write_filename(block, out)
+                    });
+                }
+            }
+        }
+
+        blocks
+    }
+
+    /// Lay out the blocks the way Alcohol 120% writes them: the header, then every session, every
+    /// track, every index block, the filename blocks, and finally the filenames. Each filename is
+    /// only stored once, and tracks which use the same files share their filename blocks.
+    fn layout(&mut self) {
+        let num_tracks: usize = self.sessions().map(|s| s.toc_entries().count()).sum();
+        let num_indexes = self
+            .sessions()
+            .flat_map(|s| s.toc_entries())
+            .filter(|track| track.index_block().is_some())
+            .count();
+
+        // Each track needs its filename blocks to be next to each other, so blocks are shared
+        // between tracks which use exactly the same list of files
+        let mut file_lists: Vec<Vec<Vec<u8>>> = Vec::new();
+        let mut filenames: Vec<Vec<u8>> = Vec::new();
+        for track in self.sessions().flat_map(|s| s.toc_entries()) {
+            let names: Vec<Vec<u8>> = track
+                .filename_blocks()
+                .map(|(_, block)| block.encoded_name())
+                .collect();
+
+            for name in &names {
+                if !filenames.contains(name) {
+                    filenames.push(name.clone());
+                }
+            }
+
+            if !names.is_empty() && !file_lists.contains(&names) {
+                file_lists.push(names);
+            }
+        }
+
+        let mut file_list_offsets = Vec::with_capacity(file_lists.len());
+        let mut num_filename_blocks = 0;
+        for names in &file_lists {
+            file_list_offsets.push(num_filename_blocks);
+            num_filename_blocks += names.len();
+        }
+
+        let track_offset = self.header.session_offset() + SESSION_SIZE * self.sessions.len();
+        let index_offset = track_offset + TRACK_SIZE * num_tracks;
+        let filename_block_offset = index_offset + INDEX_BLOCK_SIZE * num_indexes;
+        let mut filename_offsets = Vec::with_capacity(filenames.len());
+        let mut next_filename_offset =
+            filename_block_offset + FILENAME_BLOCK_SIZE * num_filename_blocks;
+
+        for filename in &filenames {
+            filename_offsets.push(next_filename_offset);
+            next_filename_offset += filename.len();
+        }
+
+        let mut next_track_offset = track_offset;
+        let mut next_index_offset = index_offset;
+
+        for session in &mut self.sessions {
+            session.set_first_track_offset(next_track_offset as u32);
+            next_track_offset += TRACK_SIZE * session.toc_entries().count();
+
+            for track in session.toc_entries_mut() {
+                let track_index_offset = if track.index_block().is_some() {
+                    next_index_offset += INDEX_BLOCK_SIZE;
+                    next_index_offset - INDEX_BLOCK_SIZE
+                } else {
+                    0
+                };
+
+                let names: Vec<Vec<u8>> = track
+                    .filename_blocks()
+                    .map(|(_, block)| block.encoded_name())
+                    .collect();
+                let track_filename_block_offset = match file_lists.iter().position(|f| *f == names)
+                {
+                    Some(i) => filename_block_offset + FILENAME_BLOCK_SIZE * file_list_offsets[i],
+                    None => 0,
+                };
+
+                for block in track.filename_blocks_mut() {
+                    let name = block.encoded_name();
+                    let i = filenames.iter().position(|f| *f == name).unwrap();
+                    block.filename_offset = filename_offsets[i] as u32;
+                }
+
+                track.set_block_offsets(
+                    track_index_offset as u32,
+                    track_filename_block_offset as u32,
+                );
+            }
+        }
+
+        self.num_bytes = next_filename_offset;
+        self.unparsed.clear();
+    }
 }
 
 fn mds(input: Bytes) -> Res<Mds> {
@@ -82,12 +280,228 @@ fn mds(input: Bytes) -> Res<Mds> {
         session_offset += SESSION_SIZE;
     }
 
-    Ok((
-        rest,
-        Mds {
-            num_bytes,
-            header,
-            sessions,
-        },
-    ))
+    let mut mds = Mds {
+        num_bytes,
+        header,
+        sessions,
+        unparsed: Vec::new(),
+    };
+
+    // Hold onto anything which isn't covered by a block, so that it can be written back out
+    let mut covered = vec![false; num_bytes];
+    for (offset, bytes) in mds.blocks() {
+        let end = (offset + bytes.len()).min(num_bytes);
+        covered[offset.min(end)..end].fill(true);
+    }
+
+    let mut i = 0;
+    while i < num_bytes {
+        let len = covered[i..]
+            .iter()
+            .take_while(|&&c| c == covered[i])
+            .count();
+        if !covered[i] {
+            mds.unparsed.push((i, input[i..i + len].to_vec()));
+        }
+        i += len;
+    }
+
+    Ok((rest, mds))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// A track block, with its unknown bytes and padding filled in so that losing them shows
+    fn track_block(
+        point: u8,
+        index_offset: u32,
+        filename_offset: u32,
+        start_sector: i32,
+    ) -> Vec<u8> {
+        let mut block = vec![0xAA, 0x00, 0x14, 0x00, point, 0, 0, 0, 0, 0x00, 0x02, 0x00];
+        block.extend_from_slice(&index_offset.to_le_bytes());
+        block.extend_from_slice(&2352u16.to_le_bytes());
+        block.extend_from_slice(&[0x5A; 0x12]);
+        block.extend_from_slice(&start_sector.to_le_bytes());
+        block.extend_from_slice(&(start_sector as u64 * 2352).to_le_bytes());
+        block.extend_from_slice(&1u32.to_le_bytes());
+        block.extend_from_slice(&filename_offset.to_le_bytes());
+        block.extend_from_slice(&[0xA5; 0x18]);
+        assert_eq!(block.len(), TRACK_SIZE);
+
+        block
+    }
+
+    /// A lead-in entry, which has no index or filename blocks
+    fn lead_in_block(point: u8) -> Vec<u8> {
+        let mut block = vec![0; TRACK_SIZE];
+        block[2] = 0x14;
+        block[4] = point;
+        block[9] = 1;
+
+        block
+    }
+
+    fn session_block(number: u16, start: i32, end: i32, track: u16, track_offset: u32) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.extend_from_slice(&start.to_le_bytes());
+        block.extend_from_slice(&end.to_le_bytes());
+        block.extend_from_slice(&number.to_le_bytes());
+        block.extend_from_slice(&[2, 1]);
+        block.extend_from_slice(&track.to_le_bytes());
+        block.extend_from_slice(&track.to_le_bytes());
+        block.extend_from_slice(&0u32.to_le_bytes());
+        block.extend_from_slice(&track_offset.to_le_bytes());
+        assert_eq!(block.len(), SESSION_SIZE);
+
+        block
+    }
+
+    /// A two session CD-R, with junk in the header's DVD padding, a gap between the track and
+    /// index blocks, and bytes after the last filename that no block points to
+    pub fn multi_session_mds() -> Vec<u8> {
+        let track_offset = 0x88;
+        let index_offset = 0x1D0;
+        let filename_block_offset = 0x1E0;
+        let filename_offset = 0x1F0u32;
+
+        let mut mds = b"MEDIA DESCRIPTOR".to_vec();
+        mds.extend_from_slice(&[1, 5]);
+        mds.extend_from_slice(&1u16.to_le_bytes());
+        mds.extend_from_slice(&2u16.to_le_bytes());
+        mds.extend((0..0x3A).map(|i| i as u8 ^ 0x3C));
+        mds.extend_from_slice(&0x58u32.to_le_bytes());
+        mds.extend_from_slice(&0u32.to_le_bytes());
+        assert_eq!(mds.len(), 0x58);
+
+        mds.extend(session_block(1, -150, 1000, 1, track_offset));
+        mds.extend(session_block(
+            2,
+            12400,
+            13000,
+            2,
+            track_offset + 2 * TRACK_SIZE as u32,
+        ));
+
+        mds.extend(lead_in_block(0xA0));
+        mds.extend(track_block(1, index_offset, filename_block_offset, 0));
+        mds.extend(lead_in_block(0xA0));
+        mds.extend(track_block(
+            2,
+            index_offset + 8,
+            filename_block_offset,
+            12550,
+        ));
+
+        // Nothing points at these
+        mds.extend_from_slice(&[0xDE, 0xAD, 0xBE, 0xEF, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(mds.len(), index_offset as usize);
+
+        for (index0, index1) in [(150, 850), (150, 450)] {
+            mds.extend_from_slice(&u32::to_le_bytes(index0));
+            mds.extend_from_slice(&u32::to_le_bytes(index1));
+        }
+
+        mds.extend_from_slice(&filename_offset.to_le_bytes());
+        mds.push(0);
+        mds.extend_from_slice(&[0x77; 0x0B]);
+        assert_eq!(mds.len(), filename_offset as usize);
+
+        mds.extend_from_slice(b"disc.mdf\0");
+        mds.extend_from_slice(b"trailing");
+
+        mds
+    }
+
+    /// A one track image whose data is split across the files in `names`, with `num_filenames`
+    /// written as the track's count of filename blocks
+    fn split_mds(num_filenames: u32, names: &[&str]) -> Vec<u8> {
+        let index_offset = 0x110;
+        let filename_block_offset = index_offset + 8;
+
+        let mut mds = b"MEDIA DESCRIPTOR".to_vec();
+        mds.extend_from_slice(&[1, 5]);
+        mds.extend_from_slice(&0u16.to_le_bytes());
+        mds.extend_from_slice(&1u16.to_le_bytes());
+        mds.extend_from_slice(&[0; 0x3A]);
+        mds.extend_from_slice(&0x58u32.to_le_bytes());
+        mds.extend_from_slice(&0u32.to_le_bytes());
+
+        mds.extend(session_block(1, -150, 100, 1, 0x70));
+        mds.extend(lead_in_block(0xA0));
+        let mut track = track_block(1, index_offset, filename_block_offset, 0);
+        track[0x30..0x34].copy_from_slice(&num_filenames.to_le_bytes());
+        mds.extend(track);
+        assert_eq!(mds.len(), index_offset as usize);
+
+        mds.extend_from_slice(&0u32.to_le_bytes());
+        mds.extend_from_slice(&100u32.to_le_bytes());
+
+        let mut filename_offset =
+            filename_block_offset as usize + FILENAME_BLOCK_SIZE * names.len();
+        for name in names {
+            mds.extend_from_slice(&(filename_offset as u32).to_le_bytes());
+            mds.extend_from_slice(&[0; 0x0C]);
+            filename_offset += name.len() + 1;
+        }
+        for name in names {
+            mds.extend_from_slice(name.as_bytes());
+            mds.push(0);
+        }
+
+        mds
+    }
+
+    #[test]
+    fn every_filename_block_is_read() {
+        let names = ["disc.md0", "disc.md1", "disc.md2"];
+        let mds = Mds::from_bytes(&split_mds(3, &names)).unwrap();
+
+        assert_eq!(mds.track(1).unwrap().filenames(), names);
+    }
+
+    #[test]
+    fn images_without_a_filename_count_have_one_file() {
+        let mds = Mds::from_bytes(&split_mds(0, &["disc.mdf"])).unwrap();
+
+        assert_eq!(mds.track(1).unwrap().filenames(), ["disc.mdf"]);
+    }
+
+    #[test]
+    fn session_pregaps_are_not_stored() {
+        let mds = Mds::from_bytes(&multi_session_mds()).unwrap();
+
+        for session in mds.sessions() {
+            let track = session.data_tracks().next().unwrap();
+            assert_eq!(track.pregap_sectors(), 0);
+            assert_eq!(track.index1_offset(), track.track_start_offset);
+        }
+    }
+
+    #[test]
+    fn parsed_mds_is_written_back_unchanged() {
+        let bytes = multi_session_mds();
+        let mds = Mds::from_bytes(&bytes).unwrap();
+
+        assert_eq!(mds.sessions().count(), 2);
+        assert_eq!(mds.track(1).unwrap().track_start_sector, 0);
+        assert_eq!(mds.track(2).unwrap().track_start_sector, 12550);
+        assert_eq!(mds.unparsed.len(), 2);
+
+        assert_eq!(mds.to_bytes(), bytes);
+    }
+
+    #[test]
+    fn new_mds_round_trips() {
+        let bytes = Mds::from_bytes(&multi_session_mds())
+            .unwrap()
+            .into_single_file("disc.mdf")
+            .to_bytes();
+        let mds = Mds::from_bytes(&bytes).unwrap();
+
+        assert!(mds.unparsed.is_empty());
+        assert_eq!(mds.to_bytes(), bytes);
+    }
 }
@@ -1,6 +1,7 @@
 mod filename;
 mod header;
 mod index;
+#[allow(clippy::module_inception)]
 mod mds;
 mod session;
 mod track;
@@ -11,7 +12,10 @@ mod types;
 // out there is at https://psx-spx.consoledev.net/cdromdrive/#cdrom-disk-images-mdsmdf-alcohol-120.
 // Beware some sites which have completely incorrect information listed!
 
-pub use header::{header, Header, Version};
+pub use header::{header, Header, MediaType, Version};
 pub use mds::Mds;
 pub use session::{session, Session};
-pub use track::{Track, TrackMode};
+pub use track::{toc_msf, SubChannels, Track, TrackMode};
+
+#[cfg(test)]
+pub use mds::tests::multi_session_mds;
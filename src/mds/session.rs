@@ -1,6 +1,6 @@
 use super::track::{track, Track};
 use super::types::{Bytes, Res};
-use crate::timecode::{Timecode, FRAMES_PER_SECOND};
+use crate::error::{Error, Result};
 use nom::{
     bytes::complete::take,
     number::complete::{le_i32, le_u16, le_u32, le_u8},
@@ -10,7 +10,7 @@ use nom::{
 /// The number of bytes in an .mds file that a session takes
 pub const SESSION_SIZE: usize = 0x18;
 /// The number of bytes in an .mds file that a track takes
-const TRACK_SIZE: usize = 0x50;
+pub const TRACK_SIZE: usize = 0x50;
 
 #[derive(Debug)]
 pub struct Session {
@@ -19,25 +19,96 @@ pub struct Session {
     /// start at sector -150 (0xFFFFFF6A).
     pub start_sector: i32,
 
-    /// The location of the first track in timecode format. For discs with a pregap, this will
-    /// typically be 00:02:00. Otherwise, it would be 00:00:00.
-    pub start_time: Timecode,
     pub end_sector: i32,
-    _session_number: u16,
-    _num_data_blocks: u8,
-    _num_lead_in_data_blocks: u8,
-    _first_track_num: u16,
-    _last_track_num: u16,
-    _first_track_offset: u32,
+    session_number: u16,
+    num_data_blocks: u8,
+    num_lead_in_data_blocks: u8,
+    first_track_num: u16,
+    last_track_num: u16,
+    zero: u32,
+    first_track_offset: u32,
     tracks: Vec<Track>,
 }
 
 impl Session {
+    /// Create a session holding `tracks`, which should include the session's lead-in entries as
+    /// well as its data tracks
+    pub fn new(
+        session_number: u16,
+        start_sector: i32,
+        end_sector: i32,
+        mut tracks: Vec<Track>,
+    ) -> Self {
+        for track in &mut tracks {
+            track.set_session_start(start_sector);
+        }
+
+        let data_tracks = tracks.iter().filter(|track| track.number() < 0xA0);
+        let first_track_num = data_tracks.clone().map(Track::number).min().unwrap_or(0);
+        let last_track_num = data_tracks.clone().map(Track::number).max().unwrap_or(0);
+        let num_lead_in_data_blocks = tracks.len() - data_tracks.count();
+
+        Self {
+            start_sector,
+            end_sector,
+            session_number,
+            num_data_blocks: tracks.len() as u8,
+            num_lead_in_data_blocks: num_lead_in_data_blocks as u8,
+            first_track_num: first_track_num as u16,
+            last_track_num: last_track_num as u16,
+            zero: 0,
+            first_track_offset: 0,
+            tracks,
+        }
+    }
+
     /// An iterator over all the data tracks in this disc image. Pregap / postgap tracks are
     /// omitted; only user data tracks are included
     pub fn data_tracks(&self) -> impl Iterator<Item = &Track> {
         self.tracks.iter().filter(|track| track.number() < 0xA0)
     }
+
+    /// Where this session's track blocks are stored in the .mds
+    pub fn first_track_offset(&self) -> usize {
+        self.first_track_offset as usize
+    }
+
+    pub fn set_first_track_offset(&mut self, first_track_offset: u32) {
+        self.first_track_offset = first_track_offset;
+    }
+
+    pub fn toc_entries_mut(&mut self) -> impl Iterator<Item = &mut Track> {
+        self.tracks.iter_mut()
+    }
+
+    pub fn data_tracks_mut(&mut self) -> impl Iterator<Item = &mut Track> {
+        self.tracks.iter_mut().filter(|track| track.number() < 0xA0)
+    }
+
+    /// Every entry in this session's table of contents. Alongside the data tracks, this includes
+    /// the A0, A1, and A2 points in the lead-in which give the first track, last track, and the
+    /// start of the lead-out.
+    pub fn toc_entries(&self) -> impl Iterator<Item = &Track> {
+        self.tracks.iter()
+    }
+
+    /// The 1-based number of this session on the disc
+    pub fn number(&self) -> usize {
+        self.session_number.into()
+    }
+
+    /// The only data track in this session. Fails if the session has no data tracks, or more
+    /// than one.
+    pub fn single_track(&self) -> Result<&Track> {
+        let mut tracks = self.data_tracks();
+        let first_track = tracks.next().ok_or(Error::NoDataTracks)?;
+
+        if tracks.next().is_some() {
+            Err(Error::MultiTrackNotSupported)?;
+        }
+
+        Ok(first_track)
+    }
 }
 
 pub fn session(input: Bytes, session_offset: usize) -> Res<Session> {
@@ -52,7 +123,7 @@ pub fn session(input: Bytes, session_offset: usize) -> Res<Session> {
             num_lead_in_data_blocks,
             first_track_num,
             last_track_num,
-            _,
+            zero,
             first_track_offset,
         ),
     ) = tuple((
@@ -73,24 +144,21 @@ pub fn session(input: Bytes, session_offset: usize) -> Res<Session> {
 
     for i in 0..num_data_blocks {
         let block_offset = data_blocks_offset + TRACK_SIZE * i as usize;
-        let (_, track) = track(input, block_offset)?;
+        let (_, mut track) = track(input, block_offset)?;
+        track.set_session_start(start_sector);
         tracks.push(track);
     }
 
-    let f = start_sector % FRAMES_PER_SECOND;
-    let s = start_sector / FRAMES_PER_SECOND - f;
-    let start_time = Timecode::from_msf(0, s, f);
-
     let session = Session {
         start_sector,
-        start_time,
         end_sector,
-        _session_number: session_number,
-        _num_data_blocks: num_data_blocks,
-        _num_lead_in_data_blocks: num_lead_in_data_blocks,
-        _first_track_num: first_track_num,
-        _last_track_num: last_track_num,
-        _first_track_offset: first_track_offset,
+        session_number,
+        num_data_blocks,
+        num_lead_in_data_blocks,
+        first_track_num,
+        last_track_num,
+        zero,
+        first_track_offset,
         tracks,
     };
 
@@ -99,3 +167,15 @@ pub fn session(input: Bytes, session_offset: usize) -> Res<Session> {
 
     Ok((rest, session))
 }
+
+pub fn write_session(session: &Session, out: &mut Vec<u8>) {
+    out.extend_from_slice(&session.start_sector.to_le_bytes());
+    out.extend_from_slice(&session.end_sector.to_le_bytes());
+    out.extend_from_slice(&session.session_number.to_le_bytes());
+    out.push(session.num_data_blocks);
+    out.push(session.num_lead_in_data_blocks);
+    out.extend_from_slice(&session.first_track_num.to_le_bytes());
+    out.extend_from_slice(&session.last_track_num.to_le_bytes());
+    out.extend_from_slice(&session.zero.to_le_bytes());
+    out.extend_from_slice(&session.first_track_offset.to_le_bytes());
+}
@@ -1,32 +1,48 @@
 use super::{
-    filename::filename_block,
+    filename::{filename_block, FilenameBlock, FILENAME_BLOCK_SIZE},
     index::{index_block, IndexBlock},
     types::{Bytes, Res},
 };
+use crate::timecode::Timecode;
 use nom::{
-    bytes::complete::{take, take_till},
+    bytes::complete::take,
     combinator::map_res,
     number::complete::{le_i32, le_u16, le_u32, le_u64, le_u8},
     sequence::tuple,
 };
-use std::{ffi::CString, path::Path};
 
 #[derive(Debug)]
 pub struct Track {
     pub mode: TrackMode,
+
+    /// The mode as it is stored in the .mds. More than one value can map to the same `mode`.
+    mode_byte: u8,
+
     pub num_subchannels: SubChannels,
-    _adr: u8,
-    _track_number: u8,
+    adr_control: u8,
+    track_number: u8,
     point: u8,
+    pub absolute_minute: u8,
+    pub absolute_second: u8,
+    pub absolute_frame: u8,
+    zero: u8,
     pub minute: u8,
     pub second: u8,
     pub frame: u8,
+    index_block_offset: u32,
     index: Option<IndexBlock>,
     sector_size: u16,
+    unknown: Vec<u8>,
     pub track_start_sector: i32,
     pub track_start_offset: u64,
-    _num_filenames: u32,
-    filename: Option<String>,
+    num_filenames: u32,
+    filename_block_offset: u32,
+    filename_blocks: Vec<FilenameBlock>,
+    padding: Vec<u8>,
+
+    /// Where the session holding this track starts. This isn't part of the track block, but is
+    /// needed to tell which of the track's pregap sectors are stored.
+    session_start_sector: i32,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -44,7 +60,7 @@ pub struct UnknownTrackMode(pub u8);
 impl TryInto<TrackMode> for u8 {
     type Error = UnknownTrackMode;
 
-    fn try_into(self) -> Result<TrackMode, Self::Error> {
+    fn try_into(self) -> std::result::Result<TrackMode, Self::Error> {
         use TrackMode::*;
 
         match self {
@@ -60,6 +76,21 @@ impl TryInto<TrackMode> for u8 {
     }
 }
 
+impl From<TrackMode> for u8 {
+    fn from(mode: TrackMode) -> Self {
+        use TrackMode::*;
+
+        match mode {
+            None => 0x00,
+            Audio => 0xA9,
+            Mode1 => 0xAA,
+            Mode2 => 0xAB,
+            Mode2Form1 => 0xAC,
+            Mode2Form2 => 0xAD,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SubChannels {
     None,
@@ -71,7 +102,7 @@ pub struct UnknonwSubChannelFlag(pub u8);
 impl TryInto<SubChannels> for u8 {
     type Error = UnknonwSubChannelFlag;
 
-    fn try_into(self) -> Result<SubChannels, Self::Error> {
+    fn try_into(self) -> std::result::Result<SubChannels, Self::Error> {
         match self {
             0x00 => Ok(SubChannels::None),
             0x08 => Ok(SubChannels::Eight),
@@ -80,11 +111,112 @@ impl TryInto<SubChannels> for u8 {
     }
 }
 
+impl From<&SubChannels> for u8 {
+    fn from(subchannels: &SubChannels) -> Self {
+        match subchannels {
+            SubChannels::None => 0x00,
+            SubChannels::Eight => 0x08,
+        }
+    }
+}
+
 impl Track {
+    /// Create the TOC entry for track `point`, which starts at `track_start_sector`. The track
+    /// has no data until it is given an index block and a data file.
+    pub fn new(
+        point: u8,
+        mode: TrackMode,
+        control: u8,
+        sector_size: u16,
+        track_start_sector: i32,
+    ) -> Self {
+        let (minute, second, frame) = toc_msf(track_start_sector);
+
+        Self {
+            mode,
+            mode_byte: mode.into(),
+            num_subchannels: SubChannels::None,
+            adr_control: 0x10 | control,
+            track_number: 0,
+            point,
+            absolute_minute: 0,
+            absolute_second: 0,
+            absolute_frame: 0,
+            zero: 0,
+            minute,
+            second,
+            frame,
+            index_block_offset: 0,
+            index: None,
+            sector_size,
+            unknown: vec![0; UNKNOWN_SIZE],
+            track_start_sector,
+            track_start_offset: 0,
+            num_filenames: 0,
+            filename_block_offset: 0,
+            filename_blocks: Vec::new(),
+            padding: vec![0; PADDING_SIZE],
+            session_start_sector: 0,
+        }
+    }
+
+    /// Create one of the A0, A1, or A2 entries in the lead-in of a session's TOC, which hold
+    /// information about the session rather than a track
+    pub fn lead_in(point: u8, control: u8, minute: u8, second: u8, frame: u8) -> Self {
+        Self {
+            minute,
+            second,
+            frame,
+            ..Self::new(point, TrackMode::None, control, 0, 0)
+        }
+    }
+
+    /// Point this track at data stored `track_start_offset` bytes into `filename`
+    pub fn set_data_file(&mut self, filename: &str, track_start_offset: u64) {
+        self.num_filenames = 1;
+        self.filename_blocks = vec![FilenameBlock::new(filename)];
+        self.track_start_offset = track_start_offset;
+    }
+
+    /// Give this track `index0_sectors` sectors of pregap and `index1_sectors` of data
+    pub fn set_index(&mut self, index0_sectors: u32, index1_sectors: u32) {
+        self.index = Some(IndexBlock {
+            index0_sectors,
+            index1_sectors,
+        });
+    }
+
+    /// Record where the session holding this track starts
+    pub fn set_session_start(&mut self, session_start_sector: i32) {
+        self.session_start_sector = session_start_sector;
+    }
+
+    /// Move the index and filename blocks this track refers to. The filename blocks keep
+    /// pointing at the same filenames unless they are moved separately.
+    pub fn set_block_offsets(&mut self, index_block_offset: u32, filename_block_offset: u32) {
+        self.index_block_offset = index_block_offset;
+        self.filename_block_offset = filename_block_offset;
+    }
+
     pub fn number(&self) -> usize {
         self.point.into()
     }
 
+    /// The ADR field of this track's TOC entry, which says what kind of data the entry holds
+    pub fn adr(&self) -> u8 {
+        self.adr_control >> 4
+    }
+
+    /// The control field of this track's TOC entry. Bit 2 is set for data tracks.
+    pub fn control(&self) -> u8 {
+        self.adr_control & 0x0F
+    }
+
+    /// The TNO field of this track's TOC entry. This is always 0 since the TOC is in the lead-in.
+    pub fn track_number(&self) -> u8 {
+        self.track_number
+    }
+
     pub fn sector_size(&self) -> usize {
         self.sector_size.into()
     }
@@ -107,20 +239,57 @@ impl Track {
             .unwrap_or_default()
     }
 
-    pub fn data_filename<P: AsRef<Path>>(&self, mds_file_name: P) -> Option<String> {
-        self.filename.as_ref().map(|name| {
-            let mut pb = mds_file_name.as_ref().to_path_buf();
-            match name.as_str() {
-                "*.mdf" => {
-                    pb.set_extension("mdf");
-                    pb.to_string_lossy().to_string()
-                }
-                name => {
-                    pb.set_file_name(name);
-                    pb.to_string_lossy().to_string()
-                }
-            }
-        })
+    /// The number of pregap (index 0) sectors which are stored in the .mdf directly ahead of the
+    /// track's own data, starting at `track_start_offset`. The pregap that opens a session, such
+    /// as the two seconds ahead of the first track on a disc, is part of the lead-in area and
+    /// isn't stored, so it isn't counted here even when the index block includes it.
+    pub fn pregap_sectors(&self) -> usize {
+        let index0_sectors = self
+            .index
+            .as_ref()
+            .map(|idx| idx.index0_sectors as i32)
+            .unwrap_or_default();
+
+        let pregap_start = self.track_start_sector - index0_sectors;
+        let stored_start = self.session_start_sector + SESSION_PREGAP_SECTORS;
+        let unstored = (stored_start - pregap_start).clamp(0, index0_sectors);
+
+        (index0_sectors - unstored) as usize
+    }
+
+    /// The byte offset in the .mdf where index 1 of this track begins, past any pregap
+    pub fn index1_offset(&self) -> u64 {
+        self.track_start_offset + (self.pregap_sectors() * self.sector_size()) as u64
+    }
+
+    /// This track's index block, and where it is stored in the .mds
+    pub fn index_block(&self) -> Option<(usize, &IndexBlock)> {
+        let offset = self.index_block_offset as usize;
+        self.index.as_ref().map(|index| (offset, index))
+    }
+
+    /// This track's filename blocks, which are stored one after another in the .mds, along with
+    /// where each one is stored
+    pub fn filename_blocks(&self) -> impl Iterator<Item = (usize, &FilenameBlock)> {
+        let offset = self.filename_block_offset as usize;
+        self.filename_blocks
+            .iter()
+            .enumerate()
+            .map(move |(i, block)| (offset + FILENAME_BLOCK_SIZE * i, block))
+    }
+
+    pub fn filename_blocks_mut(&mut self) -> impl Iterator<Item = &mut FilenameBlock> {
+        self.filename_blocks.iter_mut()
+    }
+
+    /// The names of every file holding this track's data, as they are stored in the .mds. Large
+    /// images are split across several files, such as `image.md0`, `image.md1`, and so on, which
+    /// are read one after another as though they were one file.
+    pub fn filenames(&self) -> Vec<String> {
+        self.filename_blocks
+            .iter()
+            .map(|block| block.name())
+            .collect()
     }
 
     pub fn time_str(&self) -> String {
@@ -134,42 +303,48 @@ pub fn track(input: Bytes, track_offset: usize) -> Res<Track> {
     let (
         rest,
         (
-            mode,
+            (mode, mode_byte),
             num_subchannels,
-            adr,
+            adr_control,
             track_number,
             point,
-            _,
+            absolute_minute,
+            absolute_second,
+            absolute_frame,
+            zero,
             minute,
             second,
             frame,
             index_block_offset,
             sector_size,
-            _,
+            unknown,
             track_start_sector,
             track_start_offset,
             num_filenames,
-            filename_offset,
-            _,
+            filename_block_offset,
+            padding,
         ),
     ) = tuple((
         track_mode,
-        num_subchannels, // num subchannels
-        le_u8,           // adr/control
-        le_u8,           // track number
-        le_u8,           // point
-        take(4usize),    // zero
-        le_u8,           // minute
-        le_u8,           // second
-        le_u8,           // frame
-        le_u32,          // index block offset
-        le_u16,          // sector size
-        take(0x12usize), // unknown & zero
-        le_i32,          // track start sector
-        le_u64,          // track start offset
-        le_u32,          // num filenames for this track
-        le_u32,          // offset to filename block for this track
-        take(0x18usize), // zero
+        num_subchannels,    // num subchannels
+        le_u8,              // adr/control
+        le_u8,              // track number
+        le_u8,              // point
+        le_u8,              // absolute minute
+        le_u8,              // absolute second
+        le_u8,              // absolute frame
+        le_u8,              // zero
+        le_u8,              // minute
+        le_u8,              // second
+        le_u8,              // frame
+        le_u32,             // index block offset
+        le_u16,             // sector size
+        take(UNKNOWN_SIZE), // unknown & zero
+        le_i32,             // track start sector
+        le_u64,             // track start offset
+        le_u32,             // num filenames for this track
+        le_u32,             // offset to filename block for this track
+        take(PADDING_SIZE), // zero
     ))(track_input)?;
 
     let index = if index_block_offset > 0 {
@@ -180,46 +355,89 @@ pub fn track(input: Bytes, track_offset: usize) -> Res<Track> {
         None
     };
 
-    let filename = if filename_offset > 0 {
-        let filename_block = filename_block(&input[filename_offset as usize..])?.1;
-        Some(filename(&input[filename_block.filename_offset as usize..])?.1)
-    } else {
-        None
-    };
+    // Older images may not set the number of filenames, but always have at least one when they
+    // have a filename block
+    let mut filename_blocks = Vec::new();
+    if filename_block_offset > 0 {
+        for i in 0..num_filenames.max(1) as usize {
+            let block_offset = filename_block_offset as usize + FILENAME_BLOCK_SIZE * i;
+            filename_blocks.push(filename_block(input, block_offset)?.1);
+        }
+    }
 
     let track = Track {
         mode,
+        mode_byte,
         num_subchannels,
-        _adr: adr,
-        _track_number: track_number,
+        adr_control,
+        track_number,
         point,
+        absolute_minute,
+        absolute_second,
+        absolute_frame,
+        zero,
         minute,
         second,
         frame,
+        index_block_offset,
         index,
         sector_size,
+        unknown: unknown.to_vec(),
         track_start_sector,
         track_start_offset,
-        _num_filenames: num_filenames,
-        filename,
+        num_filenames,
+        filename_block_offset,
+        filename_blocks,
+        padding: padding.to_vec(),
+        session_start_sector: 0,
     };
 
     Ok((rest, track))
 }
 
-fn is_zero(x: u8) -> bool {
-    x == 0
+pub fn write_track(track: &Track, out: &mut Vec<u8>) {
+    out.push(track.mode_byte);
+    out.push((&track.num_subchannels).into());
+    out.push(track.adr_control);
+    out.push(track.track_number);
+    out.push(track.point);
+    out.push(track.absolute_minute);
+    out.push(track.absolute_second);
+    out.push(track.absolute_frame);
+    out.push(track.zero);
+    out.push(track.minute);
+    out.push(track.second);
+    out.push(track.frame);
+    out.extend_from_slice(&track.index_block_offset.to_le_bytes());
+    out.extend_from_slice(&track.sector_size.to_le_bytes());
+    out.extend_from_slice(&track.unknown);
+    out.extend_from_slice(&track.track_start_sector.to_le_bytes());
+    out.extend_from_slice(&track.track_start_offset.to_le_bytes());
+    out.extend_from_slice(&track.num_filenames.to_le_bytes());
+    out.extend_from_slice(&track.filename_block_offset.to_le_bytes());
+    out.extend_from_slice(&track.padding);
 }
 
-fn filename(input: Bytes) -> Res<String> {
-    let (input, s) = map_res(take_till(is_zero), |x| CString::new(x))(input)?;
-    let s = s.to_string_lossy().to_string();
+/// The length of the pregap ahead of the first track of every session, which isn't stored
+const SESSION_PREGAP_SECTORS: i32 = 150;
 
-    Ok((input, s))
+/// The number of bytes of unknown data in the middle of a track block
+const UNKNOWN_SIZE: usize = 0x12;
+
+/// The number of unused bytes at the end of a track block
+const PADDING_SIZE: usize = 0x18;
+
+/// The MSF address of a sector as it is written in the TOC, which counts from the start of the
+/// two second pregap ahead of the first track
+pub fn toc_msf(sector: i32) -> (u8, u8, u8) {
+    let (m, s, f) = Timecode::from_sectors(sector + 150).msf();
+
+    (m as u8, s as u8, f as u8)
 }
 
-fn track_mode(input: Bytes) -> Res<TrackMode> {
-    map_res(le_u8, |x| x.try_into())(input)
+/// Parse a track mode, along with the byte it was read from
+fn track_mode(input: Bytes) -> Res<(TrackMode, u8)> {
+    map_res(le_u8, |x| x.try_into().map(|mode| (mode, x)))(input)
 }
 
 fn num_subchannels(input: Bytes) -> Res<SubChannels> {
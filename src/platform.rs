@@ -0,0 +1,454 @@
+use crate::{
+    args::FilesystemType,
+    error::{Error, Result},
+    filesystem::{first_disc_data_sector, open_filesystem, DiscReader},
+    loader::{load_mds, LoadOptions},
+    mds::Mds,
+    sector::USER_DATA_SIZE,
+};
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+// Game consoles each mark their discs in their own way, usually with a header at the very start
+// of the first data track which the console checks before it will boot. Sega's consoles put the
+// game's product number in that header. PlayStation discs are plain ISO 9660 with a license
+// string in the system area, and name the executable to boot in SYSTEM.CNF, which is itself
+// named after the game's serial.
+
+/// Sega headers, the platform they mark, and where the product number is kept in them
+const SEGA_HEADERS: [(&[u8], Platform, usize, usize); 4] = [
+    (b"SEGA SEGASATURN ", Platform::Saturn, 0x20, 10),
+    (b"SEGADISCSYSTEM  ", Platform::SegaCd, 0x180, 14),
+    (b"SEGABOOTDISC    ", Platform::SegaCd, 0x180, 14),
+    (b"SEGA SEGAKATANA ", Platform::Dreamcast, 0x40, 10),
+];
+
+/// The start of the volume label of the 3DO's Opera filesystem: a record type of 1, five sync
+/// bytes, and a record version of 1
+const OPERA_LABEL: &[u8] = &[0x01, 0x5A, 0x5A, 0x5A, 0x5A, 0x5A, 0x01];
+
+const PC_ENGINE_ID: &[u8] = b"PC Engine CD-ROM SYSTEM";
+
+/// The most sectors at the start of a PC Engine CD's data track to look for its ID in
+const PC_ENGINE_SECTORS: i64 = 16;
+
+/// The sector of the PlayStation's system area holding its license string
+const PLAYSTATION_LICENSE_SECTOR: i64 = 4;
+const PLAYSTATION_LICENSE: &[u8] = b"Sony Computer Entertainment";
+
+/// The sector of the ISO 9660 primary volume descriptor, and its system identifier
+const PRIMARY_DESCRIPTOR: i64 = 16;
+const PLAYSTATION_SYSTEM_ID: &[u8] = b"PLAYSTATION";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Platform {
+    PlayStation,
+    PlayStation2,
+    Saturn,
+    SegaCd,
+    Dreamcast,
+    PcEngineCd,
+    ThreeDo,
+    Pc,
+    Mac,
+}
+
+impl Platform {
+    /// A short name for the platform, used in JSON output and output file names
+    pub fn id(&self) -> &'static str {
+        match self {
+            Self::PlayStation => "ps1",
+            Self::PlayStation2 => "ps2",
+            Self::Saturn => "saturn",
+            Self::SegaCd => "sega_cd",
+            Self::Dreamcast => "dreamcast",
+            Self::PcEngineCd => "pc_engine_cd",
+            Self::ThreeDo => "3do",
+            Self::Pc => "pc",
+            Self::Mac => "mac",
+        }
+    }
+}
+
+impl Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::PlayStation => write!(f, "PlayStation"),
+            Self::PlayStation2 => write!(f, "PlayStation 2"),
+            Self::Saturn => write!(f, "Sega Saturn"),
+            Self::SegaCd => write!(f, "Sega CD"),
+            Self::Dreamcast => write!(f, "Dreamcast"),
+            Self::PcEngineCd => write!(f, "PC Engine CD"),
+            Self::ThreeDo => write!(f, "3DO"),
+            Self::Pc => write!(f, "PC"),
+            Self::Mac => write!(f, "Mac"),
+        }
+    }
+}
+
+/// What a disc is for, and the serial it was given if the platform records one
+#[derive(Debug)]
+pub struct DiscIdentity {
+    pub platform: Platform,
+    pub serial: Option<String>,
+}
+
+/// Work out which platform a disc was made for from its first data track. Discs with a
+/// filesystem but none of the consoles' markings are taken to be PC discs, or Mac discs when
+/// HFS is all they have. Returns `None` if there's nothing to go on.
+pub fn identify(mds: &Mds, mds_path: &Path, options: &LoadOptions) -> Result<Option<DiscIdentity>> {
+    let mut disc = DiscReader::new(mds, mds_path, options);
+    let start_lba = first_disc_data_sector(mds)?;
+    let mut buf = [0; USER_DATA_SIZE];
+
+    disc.read_sector(start_lba, &mut buf)?;
+
+    for (header, platform, offset, len) in SEGA_HEADERS {
+        if buf.starts_with(header) {
+            let serial = text(&buf[offset..offset + len]);
+            return Ok(Some(DiscIdentity { platform, serial }));
+        }
+    }
+
+    if buf.starts_with(OPERA_LABEL) {
+        return Ok(Some(identity(Platform::ThreeDo)));
+    }
+
+    for i in 0..PC_ENGINE_SECTORS {
+        if disc.read_sector(start_lba + i, &mut buf).is_err() {
+            break;
+        }
+        if contains(&buf, PC_ENGINE_ID) {
+            return Ok(Some(identity(Platform::PcEngineCd)));
+        }
+    }
+
+    if is_playstation(&mut disc, start_lba) {
+        return Ok(Some(playstation_identity(mds, mds_path, options)));
+    }
+
+    match open_filesystem(mds, mds_path, options, None) {
+        Ok((_, fs)) if fs.kind() == FilesystemType::Hfs => Ok(Some(identity(Platform::Mac))),
+        Ok(_) => Ok(Some(identity(Platform::Pc))),
+        Err(Error::NoFilesystem) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// PlayStation discs carry Sony's license string in the system area, and most give
+/// "PLAYSTATION" as the system identifier of their volume
+fn is_playstation(disc: &mut DiscReader, start_lba: i64) -> bool {
+    let mut buf = [0; USER_DATA_SIZE];
+
+    let licensed = disc
+        .read_sector(start_lba + PLAYSTATION_LICENSE_SECTOR, &mut buf)
+        .is_ok()
+        && contains(&buf, PLAYSTATION_LICENSE);
+
+    licensed
+        || disc
+            .read_sector(start_lba + PRIMARY_DESCRIPTOR, &mut buf)
+            .is_ok()
+            && &buf[1..6] == b"CD001"
+            && buf[8..40].starts_with(PLAYSTATION_SYSTEM_ID)
+}
+
+/// Tell a PlayStation disc from a PlayStation 2 one by its SYSTEM.CNF, which names the
+/// executable to boot with a `BOOT2` line on the PlayStation 2. Discs without one boot PSX.EXE,
+/// which says nothing about the game's serial.
+fn playstation_identity(mds: &Mds, mds_path: &Path, options: &LoadOptions) -> DiscIdentity {
+    let Some(system_cnf) = read_system_cnf(mds, mds_path, options) else {
+        return identity(Platform::PlayStation);
+    };
+
+    let boot_line = |key: &str| {
+        system_cnf.lines().find_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            (name.trim().eq_ignore_ascii_case(key)).then(|| value.trim().to_string())
+        })
+    };
+
+    let (platform, boot) = match boot_line("BOOT2") {
+        Some(boot) => (Platform::PlayStation2, Some(boot)),
+        None => (Platform::PlayStation, boot_line("BOOT")),
+    };
+
+    DiscIdentity {
+        platform,
+        serial: boot.as_deref().and_then(boot_serial),
+    }
+}
+
+fn read_system_cnf(mds: &Mds, mds_path: &Path, options: &LoadOptions) -> Option<String> {
+    let filesystem = Some(&FilesystemType::Iso9660);
+    let (mut disc, fs) = open_filesystem(mds, mds_path, options, filesystem).ok()?;
+    let entry = fs.lookup(&mut disc, "SYSTEM.CNF").ok()?;
+
+    let mut data = Vec::new();
+    fs.read_file(&mut disc, &entry, &mut data).ok()?;
+
+    Some(String::from_utf8_lossy(&data).into_owned())
+}
+
+/// The serial in a boot path like `cdrom:\SLUS_005.94;1`, which is written `SLUS-00594`
+fn boot_serial(boot: &str) -> Option<String> {
+    let name = boot.rsplit(['\\', '/', ':']).next()?;
+    let name = name.split(';').next()?.split_whitespace().next()?;
+    let (prefix, number) = name.split_once(['_', '-'])?;
+    let number = number.replace('.', "");
+
+    let valid = prefix.len() == 4
+        && prefix.chars().all(|c| c.is_ascii_alphabetic())
+        && !number.is_empty()
+        && number.chars().all(|c| c.is_ascii_digit());
+
+    valid.then(|| format!("{}-{number}", prefix.to_ascii_uppercase()))
+}
+
+fn identity(platform: Platform) -> DiscIdentity {
+    DiscIdentity {
+        platform,
+        serial: None,
+    }
+}
+
+/// A space-padded string from a header, or `None` if it's blank
+fn text(bytes: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+/// The path converted files are named after. With no template, they're named after the .mds.
+/// Otherwise the template's `{name}`, `{platform}`, and `{serial}` placeholders are filled in
+/// with the name of the .mds and what the disc is identified as. Discs without a serial use the
+/// name of the .mds in its place, but a disc which can't be identified has no `{platform}`.
+pub fn output_path<P: AsRef<Path>>(
+    mds_file: P,
+    options: &LoadOptions,
+    template: Option<&str>,
+) -> Result<PathBuf> {
+    let mds_file = mds_file.as_ref();
+    let Some(template) = template else {
+        return Ok(mds_file.to_path_buf());
+    };
+
+    // The disc is only read if the template needs it
+    let disc_identity = if template.contains("{platform}") || template.contains("{serial}") {
+        let mds = load_mds(mds_file, options)?;
+        identify(&mds, mds_file, options)?
+    } else {
+        None
+    };
+
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| Error::UnknownPlaceholder(rest[start..].to_string()))?;
+        let placeholder = &rest[start + 1..start + end];
+
+        let stem = || {
+            mds_file
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        };
+        let value = match placeholder {
+            "name" => stem(),
+            "platform" => disc_identity
+                .as_ref()
+                .map(|disc| disc.platform.id().to_string()),
+            "serial" => disc_identity
+                .as_ref()
+                .and_then(|disc| disc.serial.clone())
+                .or_else(stem),
+            _ => Err(Error::UnknownPlaceholder(placeholder.to_string()))?,
+        };
+        let value = value.ok_or_else(|| Error::NoPlaceholderValue(placeholder.to_string()))?;
+
+        name.push_str(&value.replace(['/', '\\'], "_"));
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+
+    // The extension is swapped for each output file's own, so one is added to be replaced
+    Ok(mds_file.with_file_name(format!("{name}.mds")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{filesystem::tests::hfs_volume, testing::TestDisc};
+
+    /// A data track of `count` blank sectors, with `bytes` written `offset` bytes into `sector`
+    fn image(count: usize, sector: usize, offset: usize, bytes: &[u8]) -> Vec<u8> {
+        let mut image = vec![0; USER_DATA_SIZE * count];
+        let start = sector * USER_DATA_SIZE + offset;
+        image[start..start + bytes.len()].copy_from_slice(bytes);
+
+        image
+    }
+
+    fn identify_disc(disc: &TestDisc) -> Option<DiscIdentity> {
+        identify(disc.mds(), &disc.mds_path(), disc.options()).unwrap()
+    }
+
+    fn saturn_header() -> Vec<u8> {
+        let mut header = b"SEGA SEGASATURN ".to_vec();
+        header.resize(0x20, b' ');
+        header.extend_from_slice(b"MK-81009  ");
+
+        header
+    }
+
+    #[test]
+    fn serials_come_from_boot_paths() {
+        assert_eq!(boot_serial(r"cdrom:\SLUS_005.94;1").unwrap(), "SLUS-00594");
+        assert_eq!(boot_serial(r"cdrom0:\SCES_123.45;1").unwrap(), "SCES-12345");
+        assert_eq!(
+            boot_serial(r"cdrom:\GAME\slps_000.01;1").unwrap(),
+            "SLPS-00001"
+        );
+        assert_eq!(boot_serial("cdrom:SCUS-94455").unwrap(), "SCUS-94455");
+        assert_eq!(
+            boot_serial(r"cdrom:\SLES_100.01;1 1").unwrap(),
+            "SLES-10001"
+        );
+
+        assert!(boot_serial(r"cdrom:\PSX.EXE;1").is_none());
+        assert!(boot_serial(r"cdrom:\MAIN_GAME.EXE;1").is_none());
+        assert!(boot_serial(r"cdrom:\SLU_005.94;1").is_none());
+    }
+
+    #[test]
+    fn sega_headers_give_the_platform_and_serial() {
+        let disc = TestDisc::new("saturn", &image(20, 0, 0, &saturn_header()), false);
+        let identity = identify_disc(&disc).unwrap();
+        assert_eq!(identity.platform, Platform::Saturn);
+        assert_eq!(identity.serial.unwrap(), "MK-81009");
+
+        let mut header = b"SEGADISCSYSTEM  ".to_vec();
+        header.resize(0x180, b' ');
+        header.extend_from_slice(b"GM T-12345 -00");
+        let disc = TestDisc::new("sega-cd", &image(20, 0, 0, &header), false);
+        let identity = identify_disc(&disc).unwrap();
+        assert_eq!(identity.platform, Platform::SegaCd);
+        assert_eq!(identity.serial.unwrap(), "GM T-12345 -00");
+
+        // A blank product number is no serial at all
+        let disc = TestDisc::new("dreamcast", &image(20, 0, 0, b"SEGA SEGAKATANA "), false);
+        let identity = identify_disc(&disc).unwrap();
+        assert_eq!(identity.platform, Platform::Dreamcast);
+        assert!(identity.serial.is_none());
+    }
+
+    #[test]
+    fn other_consoles_are_recognised_by_their_markings() {
+        let disc = TestDisc::new("3do", &image(20, 0, 0, OPERA_LABEL), false);
+        assert_eq!(identify_disc(&disc).unwrap().platform, Platform::ThreeDo);
+
+        let disc = TestDisc::new("pc-engine", &image(20, 1, 0x20, PC_ENGINE_ID), true);
+        assert_eq!(identify_disc(&disc).unwrap().platform, Platform::PcEngineCd);
+
+        let license = image(20, 4, 0x0C, PLAYSTATION_LICENSE);
+        let disc = TestDisc::new("playstation", &license, false);
+        let identity = identify_disc(&disc).unwrap();
+        assert_eq!(identity.platform, Platform::PlayStation);
+        assert!(identity.serial.is_none());
+
+        let mut descriptor = vec![1];
+        descriptor.extend_from_slice(b"CD001\x01\0PLAYSTATION");
+        let disc = TestDisc::new("playstation-pvd", &image(20, 16, 0, &descriptor), false);
+        assert_eq!(
+            identify_disc(&disc).unwrap().platform,
+            Platform::PlayStation
+        );
+    }
+
+    #[test]
+    fn discs_are_identified_by_their_first_data_track() {
+        let cue = "REM SESSION 01\n\
+                   FILE saturn.bin BINARY\n\
+                   TRACK 01 MODE1/2048\n\
+                   INDEX 01 00:00:00\n\
+                   REM SESSION 02\n\
+                   FILE blank.bin BINARY\n\
+                   TRACK 02 MODE1/2048\n\
+                   INDEX 01 00:00:00\n";
+        let files: [(&str, &[u8]); 2] = [
+            ("saturn.bin", &image(20, 0, 0, &saturn_header())),
+            ("blank.bin", &[0; USER_DATA_SIZE * 20]),
+        ];
+        let disc = TestDisc::from_cue("two-sessions", cue, &files);
+
+        assert_eq!(identify_disc(&disc).unwrap().platform, Platform::Saturn);
+    }
+
+    #[test]
+    fn hfs_only_discs_are_mac_discs() {
+        let disc = TestDisc::new("mac", &hfs_volume(), false);
+        assert_eq!(identify_disc(&disc).unwrap().platform, Platform::Mac);
+    }
+
+    #[test]
+    fn blank_discs_are_unidentified() {
+        let disc = TestDisc::new("blank", &[0; USER_DATA_SIZE * 20], false);
+        assert!(identify_disc(&disc).is_none());
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let disc = TestDisc::new("rename", &image(20, 0, 0, &saturn_header()), false);
+        let output = |template| output_path(disc.mds_path(), disc.options(), Some(template));
+
+        let renamed = output("{serial} - {name} ({platform})").unwrap();
+        assert_eq!(renamed.file_name().unwrap(), "MK-81009 - disc (saturn).mds");
+        assert_eq!(renamed.parent(), disc.mds_path().parent());
+
+        assert_eq!(output("plain").unwrap().file_name().unwrap(), "plain.mds");
+        assert!(matches!(
+            output("{name} {size}"),
+            Err(Error::UnknownPlaceholder(name)) if name == "size"
+        ));
+        assert!(matches!(
+            output("{name"),
+            Err(Error::UnknownPlaceholder(name)) if name == "{name"
+        ));
+    }
+
+    #[test]
+    fn missing_serials_fall_back_to_the_mds_name() {
+        let disc = TestDisc::new("no-serial", &image(20, 0, 0, OPERA_LABEL), false);
+        let output = |template| output_path(disc.mds_path(), disc.options(), Some(template));
+
+        assert_eq!(output("{serial}").unwrap().file_name().unwrap(), "disc.mds");
+
+        let blank = TestDisc::new("no-platform", &[0; USER_DATA_SIZE * 20], false);
+        assert!(matches!(
+            output_path(blank.mds_path(), blank.options(), Some("{platform}")),
+            Err(Error::NoPlaceholderValue(name)) if name == "platform"
+        ));
+    }
+
+    #[test]
+    fn names_alone_dont_read_the_disc() {
+        let output = output_path(
+            "missing/disc.mds",
+            &LoadOptions::default(),
+            Some("{name} x"),
+        );
+        assert_eq!(output.unwrap(), Path::new("missing/disc x.mds"));
+    }
+}
@@ -0,0 +1,368 @@
+use crate::mds::TrackMode;
+
+// Data sectors on a CD hold 2048 bytes of user data, surrounded by extra fields used to locate
+// the sector and to detect and correct errors. An .mdf may store whole 2352 byte sectors or only
+// part of them, depending on how the disc was imaged. The layouts are described in ECMA-130,
+// https://www.ecma-international.org/publications-and-standards/standards/ecma-130/.
+
+/// The number of bytes of user data in a Mode 1 or Mode 2 Form 1 sector
+pub const USER_DATA_SIZE: usize = 0x800;
+
+/// The number of bytes in a whole sector
+pub const RAW_SECTOR_SIZE: usize = 0x930;
+
+/// The 12 byte sync pattern and 4 byte header at the start of every raw data sector
+const SYNC_HEADER_SIZE: usize = 0x10;
+
+/// The subheader which comes after the header in Mode 2 sectors. It holds the same 4 bytes twice.
+const SUBHEADER_SIZE: usize = 0x08;
+
+/// Where the 2048 bytes of user data start in a sector of a track with the given mode, when
+/// `data_size` bytes of each sector are stored. Returns `None` if the sectors don't hold 2048
+/// bytes of user data, as is the case for audio and Mode 2 Form 2 tracks.
+pub fn user_data_offset(mode: TrackMode, data_size: usize) -> Option<usize> {
+    use TrackMode::{Mode1, Mode2, Mode2Form1};
+
+    match (mode, data_size) {
+        (Mode1 | Mode2Form1, USER_DATA_SIZE) => Some(0),
+        (Mode1, RAW_SECTOR_SIZE) => Some(SYNC_HEADER_SIZE),
+        (Mode2 | Mode2Form1, RAW_SECTOR_SIZE) => Some(SYNC_HEADER_SIZE + SUBHEADER_SIZE),
+        (Mode2 | Mode2Form1, 0x920) => Some(SUBHEADER_SIZE),
+        _ => None,
+    }
+}
+
+/// Where the EDC is stored in a Mode 1 sector
+const MODE1_EDC_OFFSET: usize = 0x810;
+
+/// Where the EDC is stored in a Mode 2 Form 1 sector
+const FORM1_EDC_OFFSET: usize = 0x818;
+
+/// Where the EDC is stored in a Mode 2 Form 2 sector
+const FORM2_EDC_OFFSET: usize = 0x92C;
+
+/// Where the P parity bytes of the ECC are stored
+const ECC_P_OFFSET: usize = 0x81C;
+
+/// Where the Q parity bytes of the ECC are stored
+const ECC_Q_OFFSET: usize = 0x8C8;
+
+/// The bit in a Mode 2 sector's submode which marks it as Form 2
+const SUBMODE_FORM2: u8 = 0x20;
+
+/// A problem found when checking a sector's error detection and correction codes
+#[derive(Clone, Copy, Debug)]
+pub enum SectorError {
+    /// The sector's EDC doesn't match its data
+    Edc,
+
+    /// The P parity bytes of the sector's ECC don't match its data
+    EccP,
+
+    /// The Q parity bytes of the sector's ECC don't match its data
+    EccQ,
+}
+
+/// Check the EDC and ECC of a raw 2352 byte sector from a track with the given mode. Mode 1 and
+/// Mode 2 Form 1 sectors have both, while Form 2 sectors only have an EDC, which may be left as
+/// zero. Sectors of any other mode, such as audio, have neither and always pass.
+pub fn check_sector(mode: TrackMode, sector: &[u8]) -> Vec<SectorError> {
+    let mut errors = Vec::new();
+
+    match mode {
+        TrackMode::Mode1 => {
+            if edc(&sector[..MODE1_EDC_OFFSET]) != stored_edc(sector, MODE1_EDC_OFFSET) {
+                errors.push(SectorError::Edc);
+            }
+
+            check_ecc(sector, &mut errors);
+        }
+        TrackMode::Mode2 | TrackMode::Mode2Form1 | TrackMode::Mode2Form2 => {
+            let submode = sector[SYNC_HEADER_SIZE + 2];
+
+            if submode & SUBMODE_FORM2 != 0 {
+                let stored = stored_edc(sector, FORM2_EDC_OFFSET);
+                if stored != 0 && edc(&sector[SYNC_HEADER_SIZE..FORM2_EDC_OFFSET]) != stored {
+                    errors.push(SectorError::Edc);
+                }
+            } else {
+                if edc(&sector[SYNC_HEADER_SIZE..FORM1_EDC_OFFSET])
+                    != stored_edc(sector, FORM1_EDC_OFFSET)
+                {
+                    errors.push(SectorError::Edc);
+                }
+
+                // The header isn't covered by the ECC in Mode 2 sectors, so it's treated as zeros
+                let mut sector = sector.to_vec();
+                sector[0x0C..SYNC_HEADER_SIZE].fill(0);
+                check_ecc(&sector, &mut errors);
+            }
+        }
+        TrackMode::None | TrackMode::Audio => {}
+    }
+
+    errors
+}
+
+fn stored_edc(sector: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(sector[offset..offset + 4].try_into().unwrap())
+}
+
+fn check_ecc(sector: &[u8], errors: &mut Vec<SectorError>) {
+    let p = ecc(sector, 86, 24, 2, 86);
+    if p[..] != sector[ECC_P_OFFSET..ECC_Q_OFFSET] {
+        errors.push(SectorError::EccP);
+    }
+
+    let q = ecc(sector, 52, 43, 86, 88);
+    if q[..] != sector[ECC_Q_OFFSET..RAW_SECTOR_SIZE] {
+        errors.push(SectorError::EccQ);
+    }
+}
+
+/// The EDC is a CRC32 with the polynomial x^32 + x^31 + x^16 + x^15 + x^4 + x^3 + x + 1, computed
+/// least significant bit first with no initial value or final xor
+fn edc(data: &[u8]) -> u32 {
+    data.iter().fold(0, |edc, &byte| {
+        (0..8).fold(edc ^ u32::from(byte), |edc, _| {
+            if edc & 1 != 0 {
+                (edc >> 1) ^ 0xD801_8001
+            } else {
+                edc >> 1
+            }
+        })
+    })
+}
+
+/// Compute one set of Reed-Solomon parity bytes over a sector, starting from its header. The
+/// data is treated as a grid of `major_count` columns by `minor_count` rows, with each parity
+/// byte computed from one diagonal of it. The P and Q parities differ only in the shape of the
+/// grid and how the diagonals are walked.
+fn ecc(
+    sector: &[u8],
+    major_count: usize,
+    minor_count: usize,
+    major_mult: usize,
+    minor_inc: usize,
+) -> Vec<u8> {
+    let data = &sector[0x0C..];
+    let size = major_count * minor_count;
+    let mut parity = vec![0; major_count * 2];
+
+    for major in 0..major_count {
+        let mut index = (major >> 1) * major_mult + (major & 1);
+        let mut ecc_a = 0;
+        let mut ecc_b = 0;
+
+        for _ in 0..minor_count {
+            let byte = data[index];
+            index += minor_inc;
+            if index >= size {
+                index -= size;
+            }
+
+            ecc_a = gf_mul2(ecc_a ^ byte);
+            ecc_b ^= byte;
+        }
+
+        ecc_a = gf_div3(gf_mul2(ecc_a) ^ ecc_b);
+        parity[major] = ecc_a;
+        parity[major + major_count] = ecc_a ^ ecc_b;
+    }
+
+    parity
+}
+
+/// Multiply by 2 in GF(2^8) with the polynomial x^8 + x^4 + x^3 + x^2 + 1
+const fn gf_mul2(x: u8) -> u8 {
+    let shifted = x << 1;
+    if x & 0x80 != 0 {
+        shifted ^ 0x1D
+    } else {
+        shifted
+    }
+}
+
+/// Division by 3 in GF(2^8), which finds the `y` for which `y ^ gf_mul2(y) == x`
+fn gf_div3(x: u8) -> u8 {
+    const TABLE: [u8; 256] = {
+        let mut table = [0; 256];
+        let mut y = 0;
+        while y < 256 {
+            table[(y as u8 ^ gf_mul2(y as u8)) as usize] = y as u8;
+            y += 1;
+        }
+        table
+    };
+
+    TABLE[x as usize]
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A raw sector at 00:02:00 with user data that counts up in sevens, and its EDC and ECC
+    /// filled in. The expected values below were worked out separately with the table-driven
+    /// encoder from ECM.
+    pub(crate) fn sector(mode: TrackMode) -> Vec<u8> {
+        let mut sector = vec![0; RAW_SECTOR_SIZE];
+        sector[1..11].fill(0xFF);
+
+        let mode2 = !matches!(mode, TrackMode::Mode1);
+        sector[0x0C..SYNC_HEADER_SIZE].copy_from_slice(&[0x00, 0x02, 0x00, 1 + mode2 as u8]);
+
+        let data_offset = user_data_offset(mode, RAW_SECTOR_SIZE).unwrap();
+        if mode2 {
+            sector[SYNC_HEADER_SIZE..data_offset].copy_from_slice(&[0, 0, 0x08, 0, 0, 0, 0x08, 0]);
+        }
+        for (i, byte) in sector[data_offset..data_offset + USER_DATA_SIZE]
+            .iter_mut()
+            .enumerate()
+        {
+            *byte = (i * 7 + 3) as u8;
+        }
+
+        let (edc_start, edc_offset) = match mode2 {
+            false => (0, MODE1_EDC_OFFSET),
+            true => (SYNC_HEADER_SIZE, FORM1_EDC_OFFSET),
+        };
+        let edc = edc(&sector[edc_start..edc_offset]);
+        sector[edc_offset..edc_offset + 4].copy_from_slice(&edc.to_le_bytes());
+
+        let mut covered = sector.clone();
+        if mode2 {
+            covered[0x0C..SYNC_HEADER_SIZE].fill(0);
+        }
+        let p = ecc(&covered, 86, 24, 2, 86);
+        sector[ECC_P_OFFSET..ECC_Q_OFFSET].copy_from_slice(&p);
+        covered[ECC_P_OFFSET..ECC_Q_OFFSET].copy_from_slice(&p);
+        let q = ecc(&covered, 52, 43, 86, 88);
+        sector[ECC_Q_OFFSET..RAW_SECTOR_SIZE].copy_from_slice(&q);
+
+        sector
+    }
+
+    /// A raw Mode 2 Form 2 sector holding the same data as `sector`, with its EDC filled in
+    pub(crate) fn form2_sector() -> Vec<u8> {
+        let mut sector = sector(TrackMode::Mode2Form1);
+        sector[SYNC_HEADER_SIZE + 2] |= SUBMODE_FORM2;
+        sector[SYNC_HEADER_SIZE + 6] |= SUBMODE_FORM2;
+
+        let edc = edc(&sector[SYNC_HEADER_SIZE..FORM2_EDC_OFFSET]);
+        sector[FORM2_EDC_OFFSET..].copy_from_slice(&edc.to_le_bytes());
+
+        sector
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn edc_matches_check_value() {
+        assert_eq!(edc(b"123456789"), 0x6EC2_EDC4);
+    }
+
+    #[test]
+    fn mode1_known_answer() {
+        let sector = sector(TrackMode::Mode1);
+
+        assert_eq!(
+            hex(&sector[MODE1_EDC_OFFSET..MODE1_EDC_OFFSET + 4]),
+            "35f4ee68"
+        );
+        assert_eq!(
+            hex(&sector[ECC_P_OFFSET..ECC_P_OFFSET + 8]),
+            "2963ee530d6d074a"
+        );
+        assert_eq!(
+            hex(&sector[ECC_Q_OFFSET..ECC_Q_OFFSET + 8]),
+            "daa85533462a66bc"
+        );
+        assert_eq!(hex(&sector[RAW_SECTOR_SIZE - 4..]), "4afdced1");
+        assert!(check_sector(TrackMode::Mode1, &sector).is_empty());
+    }
+
+    #[test]
+    fn mode2_form1_known_answer() {
+        let sector = sector(TrackMode::Mode2Form1);
+
+        assert_eq!(
+            hex(&sector[FORM1_EDC_OFFSET..FORM1_EDC_OFFSET + 4]),
+            "fb6f07d7"
+        );
+        assert_eq!(
+            hex(&sector[ECC_P_OFFSET..ECC_P_OFFSET + 8]),
+            "7e5cc75e27a46581"
+        );
+        assert_eq!(
+            hex(&sector[ECC_Q_OFFSET..ECC_Q_OFFSET + 8]),
+            "7055ee8444494076"
+        );
+        assert_eq!(hex(&sector[RAW_SECTOR_SIZE - 4..]), "b66e21a9");
+        assert!(check_sector(TrackMode::Mode2Form1, &sector).is_empty());
+        assert!(check_sector(TrackMode::Mode2, &sector).is_empty());
+    }
+
+    #[test]
+    fn mode2_header_is_not_covered_by_ecc() {
+        let mut sector = sector(TrackMode::Mode2Form1);
+        sector[0x0E] = 0x74;
+
+        assert!(check_sector(TrackMode::Mode2Form1, &sector).is_empty());
+    }
+
+    #[test]
+    fn damaged_user_data_fails_edc_and_ecc() {
+        for mode in [TrackMode::Mode1, TrackMode::Mode2Form1] {
+            let mut sector = sector(mode);
+            sector[user_data_offset(mode, RAW_SECTOR_SIZE).unwrap() + 100] ^= 0x01;
+
+            let errors = check_sector(mode, &sector);
+            assert!(matches!(
+                errors[..],
+                [SectorError::Edc, SectorError::EccP, SectorError::EccQ]
+            ));
+        }
+    }
+
+    #[test]
+    fn form2_edc_may_be_left_as_zero() {
+        let mut sector = sector(TrackMode::Mode2Form1);
+        sector[SYNC_HEADER_SIZE + 2] |= SUBMODE_FORM2;
+        sector[FORM2_EDC_OFFSET..].fill(0);
+
+        assert!(check_sector(TrackMode::Mode2Form2, &sector).is_empty());
+
+        sector[FORM2_EDC_OFFSET] = 1;
+        let errors = check_sector(TrackMode::Mode2Form2, &sector);
+        assert!(matches!(errors[..], [SectorError::Edc]));
+    }
+
+    #[test]
+    fn user_data_follows_the_stored_headers() {
+        use TrackMode::*;
+
+        assert_eq!(user_data_offset(Mode1, RAW_SECTOR_SIZE), Some(16));
+        assert_eq!(user_data_offset(Mode2Form1, RAW_SECTOR_SIZE), Some(24));
+        assert_eq!(user_data_offset(Mode2, RAW_SECTOR_SIZE), Some(24));
+        assert_eq!(user_data_offset(Mode2, 0x920), Some(8));
+        assert_eq!(user_data_offset(Mode1, USER_DATA_SIZE), Some(0));
+        assert_eq!(user_data_offset(Mode2Form1, USER_DATA_SIZE), Some(0));
+    }
+
+    #[test]
+    fn audio_and_form2_have_no_user_data() {
+        use TrackMode::{Audio, Mode1, Mode2Form2};
+
+        assert_eq!(user_data_offset(Audio, RAW_SECTOR_SIZE), None);
+        assert_eq!(user_data_offset(Mode2Form2, 0x914), None);
+        assert_eq!(user_data_offset(Mode1, 0x920), None);
+    }
+
+    #[test]
+    fn audio_is_never_checked() {
+        assert!(check_sector(TrackMode::Audio, &[0x55; RAW_SECTOR_SIZE]).is_empty());
+    }
+}
@@ -0,0 +1,427 @@
+use crate::{
+    error::{Error, Result},
+    loader::LoadOptions,
+    mds::Track,
+    timecode::Timecode,
+    util::{reader_for_track, SplitFile},
+};
+use std::{
+    io::{BufReader, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+// Subchannel data is described in ECMA-130 (https://ecma-international.org/publications-and-standards/standards/ecma-130/),
+// section 22. Each sector carries 96 bytes of it, split across eight channels named P through W.
+// Only the Q channel holds anything which is generally useful; it describes where on the disc
+// the sector is, along with the disc's catalog number and each track's ISRC.
+
+/// The number of bytes of subchannel data stored with each sector
+pub const SUBCHANNEL_SIZE: usize = 0x60;
+
+/// The number of bytes each individual channel takes up per sector
+const CHANNEL_SIZE: usize = 12;
+
+/// The subchannel data of a single sector, split up into its eight channels
+#[derive(Clone, Copy, Debug)]
+pub struct Subchannel([[u8; CHANNEL_SIZE]; 8]);
+
+impl Subchannel {
+    /// Deinterleave raw P-W subchannel data, as it is stored in an .mdf. Each raw byte holds one
+    /// bit of every channel, with P in the most significant bit and W in the least.
+    pub fn from_raw(raw: &[u8]) -> Self {
+        let mut channels = [[0; CHANNEL_SIZE]; 8];
+
+        for (i, byte) in raw.iter().take(SUBCHANNEL_SIZE).enumerate() {
+            for (c, channel) in channels.iter_mut().enumerate() {
+                let bit = (byte >> (7 - c)) & 1;
+                channel[i / 8] |= bit << (7 - i % 8);
+            }
+        }
+
+        Self(channels)
+    }
+
+    /// The 12 bytes belonging to a single channel, where channel 0 is P and channel 7 is W
+    pub fn channel(&self, channel: usize) -> &[u8; CHANNEL_SIZE] {
+        &self.0[channel]
+    }
+
+    /// The channels of this sector one after another, P first. This is the layout used by
+    /// CloneCD's .sub files.
+    pub fn to_bytes(self) -> [u8; SUBCHANNEL_SIZE] {
+        let mut bytes = [0; SUBCHANNEL_SIZE];
+
+        for (chunk, channel) in bytes.chunks_exact_mut(CHANNEL_SIZE).zip(self.0.iter()) {
+            chunk.copy_from_slice(channel);
+        }
+
+        bytes
+    }
+
+    /// The R-W channels of this sector as six-bit symbols, one for each byte of raw subchannel
+    /// data, with R in the most significant bit. These carry CD+G graphics on karaoke discs.
+    pub fn rw_symbols(&self) -> [u8; SUBCHANNEL_SIZE] {
+        let mut symbols = [0; SUBCHANNEL_SIZE];
+
+        for (i, symbol) in symbols.iter_mut().enumerate() {
+            for (c, channel) in self.0.iter().enumerate().skip(2) {
+                let bit = (channel[i / 8] >> (7 - i % 8)) & 1;
+                *symbol |= bit << (7 - c);
+            }
+        }
+
+        symbols
+    }
+
+    /// Decode the Q channel of this sector
+    pub fn q(&self) -> QFrame {
+        QFrame::from_bytes(self.channel(1))
+    }
+}
+
+/// A decoded Q subchannel frame
+#[derive(Debug)]
+pub struct QFrame {
+    /// The control nibble, which holds the track's audio/data and copy permission flags
+    pub control: u8,
+    pub data: QData,
+
+    /// Whether the frame's CRC matched its contents. Frames which fail this check are either
+    /// damaged, or were corrupted on purpose as a form of copy protection.
+    pub crc_valid: bool,
+}
+
+#[derive(Debug)]
+pub enum QData {
+    /// Mode 1: the sector's position within its track (`relative`) and on the disc (`absolute`)
+    Position {
+        track: u8,
+        index: u8,
+        relative: Timecode,
+        absolute: Timecode,
+    },
+
+    /// Mode 2: the disc's media catalog number
+    Catalog(String),
+
+    /// Mode 3: the track's international standard recording code
+    Isrc(String),
+
+    /// Any other mode, which this program doesn't know how to read
+    Unknown(u8),
+}
+
+impl QFrame {
+    fn from_bytes(q: &[u8; CHANNEL_SIZE]) -> Self {
+        let control = q[0] >> 4;
+        let adr = q[0] & 0x0F;
+
+        let data = match adr {
+            1 => QData::Position {
+                track: bcd(q[1]),
+                index: bcd(q[2]),
+                relative: Timecode::from_msf(bcd(q[3]), bcd(q[4]), bcd(q[5])),
+                absolute: Timecode::from_msf(bcd(q[7]), bcd(q[8]), bcd(q[9])),
+            },
+            2 => QData::Catalog(catalog(q)),
+            3 => QData::Isrc(isrc(q)),
+            x => QData::Unknown(x),
+        };
+
+        let crc = u16::from_be_bytes([q[10], q[11]]);
+        let crc_valid = crc == !crc16(&q[0..10]);
+
+        Self {
+            control,
+            data,
+            crc_valid,
+        }
+    }
+}
+
+/// An iterator over the subchannel data of each sector in a track, produced by
+/// `track_subchannels`. Each item is the sector's LBA along with its subchannel.
+pub struct Subchannels<R> {
+    reader: R,
+    buf: Vec<u8>,
+    data_size: usize,
+    lba: i32,
+    remaining: usize,
+}
+
+impl<R: Read> Subchannels<R> {
+    /// Read subchannel data starting at the current position of `reader`, which must be at the
+    /// start of the sector numbered `lba`
+    pub fn new(reader: R, sector_size: usize, lba: i32, num_sectors: usize) -> Self {
+        Self {
+            reader,
+            buf: vec![0; sector_size],
+            data_size: sector_size - SUBCHANNEL_SIZE,
+            lba,
+            remaining: num_sectors,
+        }
+    }
+}
+
+impl<R: Read> Iterator for Subchannels<R> {
+    type Item = Result<(i32, Subchannel)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        if let Err(err) = self.reader.read_exact(&mut self.buf) {
+            self.remaining = 0;
+            return Some(Err(Error::Io(err)));
+        }
+
+        let item = (self.lba, Subchannel::from_raw(&self.buf[self.data_size..]));
+        self.lba += 1;
+        self.remaining -= 1;
+
+        Some(Ok(item))
+    }
+}
+
+/// Read the subchannel data of every sector in `track`, starting with its pregap. Fails if the
+/// track was imaged without subchannels.
+pub fn track_subchannels<P: AsRef<Path>>(
+    track: &Track,
+    mds_file_name: P,
+    options: &LoadOptions,
+) -> Result<Subchannels<BufReader<SplitFile>>> {
+    if track.sector_subchannel_size() == 0 {
+        Err(Error::NoSubchannels)?;
+    }
+
+    let mut reader = reader_for_track(mds_file_name, track, options)?;
+    reader
+        .seek(SeekFrom::Start(track.track_start_offset))
+        .map_err(Error::Io)?;
+
+    let pregap_sectors = track.pregap_sectors();
+    let first_lba = track.track_start_sector - pregap_sectors as i32;
+    let num_sectors = pregap_sectors + track.num_sectors();
+
+    Ok(Subchannels::new(
+        reader,
+        track.sector_size(),
+        first_lba,
+        num_sectors,
+    ))
+}
+
+/// Decode a binary-coded decimal byte
+fn bcd(x: u8) -> u8 {
+    (x >> 4) * 10 + (x & 0x0F)
+}
+
+/// The media catalog number is 13 BCD digits, packed into the nibbles following the control/ADR
+/// byte
+fn catalog(q: &[u8; CHANNEL_SIZE]) -> String {
+    q[1..8]
+        .iter()
+        .flat_map(|x| [x >> 4, x & 0x0F])
+        .take(13)
+        .map(|digit| char::from(b'0' + digit % 10))
+        .collect()
+}
+
+/// An ISRC is five 6-bit characters (two for the country and three for the owner), followed by
+/// seven BCD digits (two for the year and five for the serial number)
+fn isrc(q: &[u8; CHANNEL_SIZE]) -> String {
+    let bits = u64::from_be_bytes(q[1..9].try_into().unwrap());
+    let mut s = String::with_capacity(12);
+
+    for i in 0..5 {
+        let c = ((bits >> (58 - i * 6)) & 0x3F) as u8;
+        s.push(match c {
+            0x00..=0x09 => char::from(b'0' + c),
+            0x11..=0x2A => char::from(b'A' + c - 0x11),
+            _ => '?',
+        });
+    }
+
+    for i in 0..7 {
+        let digit = ((bits >> (28 - i * 4)) & 0x0F) as u8;
+        s.push(char::from(b'0' + digit % 10));
+    }
+
+    s
+}
+
+/// The CRC-16/CCITT checksum used by Q subchannel frames. The checksum stored on the disc is the
+/// inverse of the result.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, &byte| {
+        (0..8).fold(crc ^ (u16::from(byte) << 8), |crc, _| {
+            if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The Q channel of the first sector of a data track starting at 00:02:00, as it appears in
+    /// real .sub files
+    const FIRST_DATA_SECTOR_Q: [u8; CHANNEL_SIZE] = [
+        0x41, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x28, 0x32,
+    ];
+
+    /// Interleave eight channels into raw subchannel data, the inverse of `from_raw`
+    fn interleave(channels: &[[u8; CHANNEL_SIZE]; 8]) -> [u8; SUBCHANNEL_SIZE] {
+        let mut raw = [0; SUBCHANNEL_SIZE];
+
+        for (i, byte) in raw.iter_mut().enumerate() {
+            for (c, channel) in channels.iter().enumerate() {
+                let bit = (channel[i / 8] >> (7 - i % 8)) & 1;
+                *byte |= bit << (7 - c);
+            }
+        }
+
+        raw
+    }
+
+    fn q_frame(q: [u8; CHANNEL_SIZE]) -> QFrame {
+        let mut channels = [[0; CHANNEL_SIZE]; 8];
+        channels[1] = q;
+
+        Subchannel::from_raw(&interleave(&channels)).q()
+    }
+
+    #[test]
+    fn from_raw_puts_p_in_the_top_bit() {
+        let subchannel = Subchannel::from_raw(&[0x80; SUBCHANNEL_SIZE]);
+
+        assert_eq!(subchannel.channel(0), &[0xFF; CHANNEL_SIZE]);
+        for channel in 1..8 {
+            assert_eq!(subchannel.channel(channel), &[0; CHANNEL_SIZE]);
+        }
+    }
+
+    #[test]
+    fn from_raw_and_to_bytes_round_trip() {
+        let mut channels = [[0; CHANNEL_SIZE]; 8];
+        for (c, channel) in channels.iter_mut().enumerate() {
+            for (i, byte) in channel.iter_mut().enumerate() {
+                *byte = (c * 31 + i * 7) as u8 ^ 0x5A;
+            }
+        }
+
+        let raw = interleave(&channels);
+        let subchannel = Subchannel::from_raw(&raw);
+
+        for (c, channel) in channels.iter().enumerate() {
+            assert_eq!(subchannel.channel(c), channel);
+        }
+        assert_eq!(subchannel.to_bytes().to_vec(), channels.concat());
+        assert_eq!(interleave(&subchannel.0), raw);
+    }
+
+    #[test]
+    fn rw_symbols_are_the_low_six_bits_of_each_raw_byte() {
+        let raw: Vec<u8> = (0..SUBCHANNEL_SIZE as u8)
+            .map(|i| i.wrapping_mul(37))
+            .collect();
+        let subchannel = Subchannel::from_raw(&raw);
+        let expected: Vec<u8> = raw.iter().map(|byte| byte & 0x3F).collect();
+
+        assert_eq!(subchannel.rw_symbols().to_vec(), expected);
+    }
+
+    #[test]
+    fn crc16_matches_the_ccitt_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x31C3);
+    }
+
+    #[test]
+    fn decodes_a_position_frame() {
+        let frame = q_frame(FIRST_DATA_SECTOR_Q);
+
+        assert_eq!(frame.control, 0x4);
+        assert!(frame.crc_valid);
+        match frame.data {
+            QData::Position {
+                track,
+                index,
+                relative,
+                absolute,
+            } => {
+                assert_eq!((track, index), (1, 1));
+                assert_eq!(relative.sectors(), 0);
+                assert_eq!(absolute.sectors(), 150);
+            }
+            data => panic!("expected a position frame, got {data:?}"),
+        }
+    }
+
+    #[test]
+    fn reports_a_bad_crc() {
+        let mut q = FIRST_DATA_SECTOR_Q;
+        q[9] = 0x01;
+        assert!(!q_frame(q).crc_valid);
+
+        // The stored CRC is inverted, so an uninverted one is wrong too
+        let mut q = FIRST_DATA_SECTOR_Q;
+        q[10] = !q[10];
+        q[11] = !q[11];
+        assert!(!q_frame(q).crc_valid);
+    }
+
+    #[test]
+    fn decodes_bcd_timecodes() {
+        let mut q = [
+            0x01, 0x12, 0x01, 0x03, 0x59, 0x74, 0x00, 0x45, 0x10, 0x33, 0, 0,
+        ];
+        let crc = !crc16(&q[0..10]);
+        q[10..12].copy_from_slice(&crc.to_be_bytes());
+
+        let frame = q_frame(q);
+        assert!(frame.crc_valid);
+        match frame.data {
+            QData::Position {
+                track,
+                relative,
+                absolute,
+                ..
+            } => {
+                assert_eq!(track, 12);
+                assert_eq!(relative.msf(), (3, 59, 74));
+                assert_eq!(absolute.msf(), (45, 10, 33));
+            }
+            data => panic!("expected a position frame, got {data:?}"),
+        }
+    }
+
+    #[test]
+    fn decodes_a_catalog_number() {
+        let q = [
+            0x02, 0x01, 0x23, 0x45, 0x67, 0x89, 0x01, 0x20, 0x00, 0x00, 0, 0,
+        ];
+
+        match q_frame(q).data {
+            QData::Catalog(mcn) => assert_eq!(mcn, "0123456789012"),
+            data => panic!("expected a catalog frame, got {data:?}"),
+        }
+    }
+
+    #[test]
+    fn decodes_an_isrc() {
+        // USRC17607839: five 6-bit characters, then seven BCD digits
+        let q = [
+            0x03, 0x96, 0x38, 0x93, 0x04, 0x76, 0x07, 0x83, 0x90, 0x00, 0, 0,
+        ];
+
+        match q_frame(q).data {
+            QData::Isrc(isrc) => assert_eq!(isrc, "USRC17607839"),
+            data => panic!("expected an ISRC frame, got {data:?}"),
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{
+    error::Result,
+    loader::{load_mds, LoadOptions},
+    subchannel::{track_subchannels, QData, QFrame},
+};
+use std::path::Path;
+
+/// Print the decoded Q subchannel of every sector in the image
+pub fn subq<P: AsRef<Path>>(mds_file: P, options: &LoadOptions) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+
+    println!("{}", mds_file.as_ref().to_str().unwrap_or("--none--"));
+
+    for session in mds.sessions() {
+        println!("Session {}", session.number());
+
+        for track in session.data_tracks() {
+            println!("  Track {}", track.number());
+
+            for sector in track_subchannels(track, &mds_file, options)? {
+                let (lba, subchannel) = sector?;
+                println!("    {lba:<9} {}", format_q(&subchannel.q()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn format_q(q: &QFrame) -> String {
+    let crc = if q.crc_valid { "ok" } else { "BAD" };
+    let data = match &q.data {
+        QData::Position {
+            track,
+            index,
+            relative,
+            absolute,
+        } => format!("Track {track:02} Index {index:02} Rel {relative} Abs {absolute}"),
+        QData::Catalog(mcn) => format!("MCN  {mcn}"),
+        QData::Isrc(isrc) => format!("ISRC {isrc}"),
+        QData::Unknown(mode) => format!("Unknown mode {mode}"),
+    };
+
+    format!("Ctl {:X} | {data:<48} | CRC {crc}", q.control)
+}
@@ -0,0 +1,87 @@
+use crate::{
+    convert::mds_from_cue, cue::parse_cue, filesystem::DiscReader, loader::LoadOptions, mds::Mds,
+    sector::USER_DATA_SIZE,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A disc built from a cue sheet, whose .bin files and .mds are written to a temporary directory
+/// that is removed again when the disc is dropped. This is the one fixture tests use for anything
+/// that needs an image on disk.
+pub struct TestDisc {
+    dir: PathBuf,
+    mds: Mds,
+    options: LoadOptions,
+}
+
+impl TestDisc {
+    /// A disc with a single Mode 1 data track holding `data` as its user data. With `raw`, each
+    /// sector is stored whole, with junk where the sync pattern, header, EDC, and ECC would be, so
+    /// that reading from the wrong place shows.
+    pub fn new(name: &str, data: &[u8], raw: bool) -> Self {
+        if raw {
+            let sectors: Vec<u8> = data
+                .chunks(USER_DATA_SIZE)
+                .flat_map(|user_data| {
+                    let mut sector = vec![0xEE; 0x10];
+                    sector.extend_from_slice(user_data);
+                    sector.resize(0x930, 0xEE);
+                    sector
+                })
+                .collect();
+            let cue = "FILE disc.bin BINARY\nTRACK 01 MODE1/2352\nINDEX 01 00:00:00\n";
+            Self::from_cue(name, cue, &[("disc.bin", &sectors)])
+        } else {
+            let cue = "FILE disc.bin BINARY\nTRACK 01 MODE1/2048\nINDEX 01 00:00:00\n";
+            Self::from_cue(name, cue, &[("disc.bin", data)])
+        }
+    }
+
+    /// A disc described by `cue`, with each of `files` written next to it
+    pub fn from_cue(name: &str, cue: &str, files: &[(&str, &[u8])]) -> Self {
+        let dir = std::env::temp_dir().join(format!("mds-test-{}-{name}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for (filename, contents) in files {
+            fs::write(dir.join(filename), contents).unwrap();
+        }
+
+        let mds = mds_from_cue(&parse_cue(cue).unwrap(), dir.join("disc.cue")).unwrap();
+        fs::write(dir.join("disc.mds"), mds.to_bytes()).unwrap();
+
+        Self {
+            dir,
+            mds,
+            options: LoadOptions::default(),
+        }
+    }
+
+    /// The directory the disc's files are written to
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn mds(&self) -> &Mds {
+        &self.mds
+    }
+
+    pub fn mds_path(&self) -> PathBuf {
+        self.dir.join("disc.mds")
+    }
+
+    pub fn options(&self) -> &LoadOptions {
+        &self.options
+    }
+
+    pub fn reader(&self) -> DiscReader<'_> {
+        DiscReader::new(&self.mds, &self.mds_path(), &self.options)
+    }
+}
+
+impl Drop for TestDisc {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
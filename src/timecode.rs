@@ -1,5 +1,8 @@
 use crate::mds::Track;
-use std::ops::{Add, AddAssign};
+use std::{
+    fmt::Display,
+    ops::{Add, AddAssign},
+};
 
 /// A "frame" in CD terms is 75 sectors. That is, if you played an audio CD at 75 frames per second
 /// it would sound right. This is the definition of a 1x CD drive.
@@ -30,6 +33,16 @@ impl Timecode {
         Self(frames)
     }
 
+    /// Create a new timecode which is `sectors` sectors long
+    pub fn from_sectors(sectors: i32) -> Self {
+        Self(sectors)
+    }
+
+    /// The total length of this timecode in sectors
+    pub fn sectors(&self) -> i32 {
+        self.0
+    }
+
     /// Extract the minutes, seconds, and frame components of this timecode
     pub fn msf(&self) -> (i32, i32, i32) {
         let f = self.0 % FRAMES_PER_SECOND;
@@ -53,3 +66,11 @@ impl AddAssign for Timecode {
         self.0 += rhs.0
     }
 }
+
+/// Timecodes are written as MM:SS:FF, the way cue sheets and most CD tools show them
+impl Display for Timecode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (m, s, fr) = self.msf();
+        write!(f, "{m:02}:{s:02}:{fr:02}")
+    }
+}
@@ -1,10 +1,12 @@
 use crate::{
     error::{Error, Result},
+    loader::LoadOptions,
+    locate::locate_track_files,
     mds::Track,
 };
 use std::{
     fs::File,
-    io::{BufReader, BufWriter},
+    io::{self, BufReader, BufWriter, Read, Seek, SeekFrom},
     path::{Path, PathBuf},
 };
 
@@ -28,16 +30,97 @@ pub fn writer_with_extension<P: AsRef<Path>>(
     Ok(BufWriter::new(out_file))
 }
 
-/// Create a new `BufReader` which will read track data from an .mdf
-pub fn reader_for_track<P: AsRef<Path>>(mds_path: P, track: &Track) -> Result<BufReader<File>> {
-    let path = track
-        .data_filename(mds_path)
-        .ok_or(Error::MissingInputFile)?;
-    let file = File::open(path).map_err(Error::Io)?;
+/// Create a new `BufReader` which will read track data from an .mdf, or from every part of an
+/// .mdf which has been split into several files
+pub fn reader_for_track<P: AsRef<Path>>(
+    mds_path: P,
+    track: &Track,
+    options: &LoadOptions,
+) -> Result<BufReader<SplitFile>> {
+    let paths = locate_track_files(mds_path, track, options)?;
+    let file = SplitFile::open(&paths).map_err(Error::Io)?;
 
     Ok(BufReader::new(file))
 }
 
+/// Several files which are read as though they were one, each following on from the last
+pub struct SplitFile {
+    files: Vec<File>,
+
+    /// The position in the combined stream where each file starts, followed by the total length
+    starts: Vec<u64>,
+    position: u64,
+
+    /// The file which was read from last, and the offset it was left at. Reads carrying on from
+    /// there don't need to seek it first.
+    file_position: Option<(usize, u64)>,
+}
+
+impl SplitFile {
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> io::Result<Self> {
+        let mut files = Vec::with_capacity(paths.len());
+        let mut starts = vec![0];
+
+        for path in paths {
+            let file = File::open(path)?;
+            let len = file.metadata()?.len();
+
+            files.push(file);
+            starts.push(starts[starts.len() - 1] + len);
+        }
+
+        Ok(Self {
+            files,
+            starts,
+            position: 0,
+            file_position: None,
+        })
+    }
+
+    fn len(&self) -> u64 {
+        self.starts[self.starts.len() - 1]
+    }
+}
+
+impl Read for SplitFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Find the file the position falls in. Reads stop at the end of that file, so a read
+        // spanning two files is split into two calls.
+        let Some(i) = (0..self.files.len()).find(|&i| self.position < self.starts[i + 1]) else {
+            return Ok(0);
+        };
+
+        let file = &mut self.files[i];
+        let offset = self.position - self.starts[i];
+        if self.file_position.take() != Some((i, offset)) {
+            file.seek(SeekFrom::Start(offset))?;
+        }
+
+        let max_len = (self.starts[i + 1] - self.position).min(buf.len() as u64) as usize;
+        let n = file.read(&mut buf[..max_len])?;
+        self.position += n as u64;
+        self.file_position = Some((i, offset + n as u64));
+
+        Ok(n)
+    }
+}
+
+impl Seek for SplitFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Seek to a negative position")
+        })?;
+
+        Ok(self.position)
+    }
+}
+
 /// Append an "s" when count != 1
 pub fn pluralize(s: &str, count: usize) -> String {
     if count == 1 {
@@ -46,3 +129,59 @@ pub fn pluralize(s: &str, count: usize) -> String {
         format!("{s}s")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestDisc;
+
+    /// A stream of 30 numbered bytes, split across three files of different lengths
+    fn split_file(disc: &TestDisc) -> SplitFile {
+        let data: Vec<u8> = (0..30).collect();
+        let paths: Vec<PathBuf> = [&data[..10], &data[10..25], &data[25..]]
+            .iter()
+            .enumerate()
+            .map(|(i, part)| {
+                let path = disc.dir().join(format!("disc.md{i}"));
+                std::fs::write(&path, part).unwrap();
+                path
+            })
+            .collect();
+
+        SplitFile::open(&paths).unwrap()
+    }
+
+    #[test]
+    fn split_files_read_as_one() {
+        let disc = TestDisc::new("split-whole", &[0; 2048], false);
+        let mut file = split_file(&disc);
+
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).unwrap();
+        assert_eq!(data, (0..30).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn reads_cross_file_boundaries() {
+        let disc = TestDisc::new("split-boundaries", &[0; 2048], false);
+        let mut file = split_file(&disc);
+        let mut buf = [0; 6];
+
+        file.seek(SeekFrom::Start(7)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [7, 8, 9, 10, 11, 12]);
+
+        // Carrying on from there, and then going back into a file which was already read from
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [13, 14, 15, 16, 17, 18]);
+        file.seek(SeekFrom::Current(-10)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [9, 10, 11, 12, 13, 14]);
+
+        file.seek(SeekFrom::End(-8)).unwrap();
+        file.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, [22, 23, 24, 25, 26, 27]);
+        assert_eq!(file.read(&mut buf).unwrap(), 2);
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+    }
+}
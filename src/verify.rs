@@ -0,0 +1,208 @@
+use crate::{
+    error::{Error, Result},
+    loader::{load_mds, LoadOptions},
+    mds::{Track, TrackMode},
+    sector::{check_sector, SectorError, RAW_SECTOR_SIZE},
+    util::reader_for_track,
+};
+use std::{
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+/// A sector whose EDC or ECC didn't match its data
+#[derive(Debug)]
+pub struct Mismatch {
+    pub lba: i32,
+    pub track: usize,
+    pub error: SectorError,
+}
+
+/// Check the EDC and ECC of every data sector in an image, printing each mismatch along with a
+/// summary of every track. Fails if any sector didn't match.
+pub fn verify<P: AsRef<Path>>(mds_file: P, options: &LoadOptions) -> Result<()> {
+    let mds = load_mds(&mds_file, options)?;
+    let tracks = mds.sessions().flat_map(|session| session.data_tracks());
+    let mut num_mismatches = 0;
+
+    for track in tracks {
+        let number = track.number();
+        let mode = track.mode;
+
+        if matches!(mode, TrackMode::Audio | TrackMode::None) {
+            println!("Track {number} ({mode:?}): skipped, it has no EDC or ECC");
+            continue;
+        }
+
+        if track.sector_data_size() != RAW_SECTOR_SIZE {
+            println!("Track {number} ({mode:?}): skipped, it wasn't imaged with raw sectors");
+            continue;
+        }
+
+        let mismatches = verify_track(&mds_file, track, options)?;
+        let num_sectors = track.pregap_sectors() + track.num_sectors();
+        println!(
+            "Track {number} ({mode:?}): {num_sectors} sectors checked, {} mismatches",
+            mismatches.len()
+        );
+
+        for mismatch in &mismatches {
+            let kind = match mismatch.error {
+                SectorError::Edc => "EDC",
+                SectorError::EccP => "ECC P parity",
+                SectorError::EccQ => "ECC Q parity",
+            };
+            println!(
+                "  Track {}, LBA {}: {kind} mismatch",
+                mismatch.track, mismatch.lba
+            );
+        }
+
+        num_mismatches += mismatches.len();
+    }
+
+    if num_mismatches > 0 {
+        Err(Error::VerificationFailed(num_mismatches))?;
+    }
+
+    println!("Every sector verified successfully");
+    Ok(())
+}
+
+/// Check the EDC and ECC of every sector in a track, starting with its pregap. The track must
+/// have been imaged with raw sectors.
+pub fn verify_track<P: AsRef<Path>>(
+    mds_path: P,
+    track: &Track,
+    options: &LoadOptions,
+) -> Result<Vec<Mismatch>> {
+    let data_size = track.sector_data_size();
+    if data_size != RAW_SECTOR_SIZE {
+        Err(Error::RawSectorsRequired(track.mode, data_size))?;
+    }
+
+    let mut reader = reader_for_track(&mds_path, track, options)?;
+    reader
+        .seek(SeekFrom::Start(track.track_start_offset))
+        .map_err(Error::Io)?;
+
+    let pregap_sectors = track.pregap_sectors();
+    let first_lba = track.track_start_sector - pregap_sectors as i32;
+    let mut buf = vec![0; track.sector_size()];
+    let mut mismatches = Vec::new();
+
+    for lba in first_lba..first_lba + (pregap_sectors + track.num_sectors()) as i32 {
+        reader.read_exact(&mut buf).map_err(Error::Io)?;
+
+        for error in check_sector(track.mode, &buf[..data_size]) {
+            mismatches.push(Mismatch {
+                lba,
+                track: track.number(),
+                error,
+            });
+        }
+    }
+
+    Ok(mismatches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        sector::tests::{form2_sector, sector},
+        testing::TestDisc,
+    };
+
+    #[test]
+    fn reports_where_each_mismatch_is() {
+        let mode1 = sector(TrackMode::Mode1);
+        let form2 = form2_sector();
+
+        // Three sectors of each, with the second Mode 1 sector and the third Form 2 one damaged
+        let mut data = [mode1.repeat(3), form2.repeat(3)].concat();
+        data[RAW_SECTOR_SIZE + 0x100] ^= 0x01;
+        data[RAW_SECTOR_SIZE * 5 + 0x100] ^= 0x01;
+
+        let cue = "FILE disc.bin BINARY\n\
+                   TRACK 01 MODE1/2352\n\
+                   INDEX 01 00:00:00\n\
+                   TRACK 02 MODE2/2352\n\
+                   INDEX 01 00:00:03\n";
+        let disc = TestDisc::from_cue("verify", cue, &[("disc.bin", &data)]);
+        let tracks: Vec<&Track> = disc
+            .mds()
+            .sessions()
+            .flat_map(|s| s.data_tracks())
+            .collect();
+
+        let mismatches = verify_track(disc.mds_path(), tracks[0], disc.options()).unwrap();
+        assert!(matches!(
+            mismatches[..],
+            [
+                Mismatch {
+                    lba: 1,
+                    track: 1,
+                    error: SectorError::Edc
+                },
+                Mismatch {
+                    lba: 1,
+                    track: 1,
+                    error: SectorError::EccP
+                },
+                Mismatch {
+                    lba: 1,
+                    track: 1,
+                    error: SectorError::EccQ
+                },
+            ]
+        ));
+
+        let mismatches = verify_track(disc.mds_path(), tracks[1], disc.options()).unwrap();
+        assert!(matches!(
+            mismatches[..],
+            [Mismatch {
+                lba: 5,
+                track: 2,
+                error: SectorError::Edc
+            }]
+        ));
+    }
+
+    #[test]
+    fn undamaged_tracks_have_no_mismatches() {
+        let data = sector(TrackMode::Mode1).repeat(4);
+        let cue = "FILE disc.bin BINARY\nTRACK 01 MODE1/2352\nINDEX 01 00:00:00\n";
+        let disc = TestDisc::from_cue("verify-clean", cue, &[("disc.bin", &data)]);
+        let track = disc
+            .mds()
+            .sessions()
+            .next()
+            .unwrap()
+            .data_tracks()
+            .next()
+            .unwrap();
+
+        assert!(verify_track(disc.mds_path(), track, disc.options())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn tracks_need_raw_sectors() {
+        let disc = TestDisc::new("verify-cooked", &[0; 2048], false);
+        let track = disc
+            .mds()
+            .sessions()
+            .next()
+            .unwrap()
+            .data_tracks()
+            .next()
+            .unwrap();
+
+        assert!(matches!(
+            verify_track(disc.mds_path(), track, disc.options()),
+            Err(Error::RawSectorsRequired(TrackMode::Mode1, 0x800))
+        ));
+    }
+}